@@ -3,17 +3,33 @@ use std::time::Duration;
 use serde::{Deserialize, Serialize};
 use serialimage::{OptimumExposure, OptimumExposureBuilder};
 
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Default)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct OptExposureConfig {
     pub percentile_pix: f32,
     pub pixel_tgt: f32,
     pub pixel_tol: f32,
     pub pixel_exclusion: u32,
-    pub min_exopsure: Duration,
+    #[serde(alias = "min_exopsure")]
+    pub min_exposure: Duration,
     pub max_exposure: Duration,
     pub max_bin: u16,
 }
 
+impl Default for OptExposureConfig {
+    fn default() -> Self {
+        Self {
+            percentile_pix: 0.995,
+            pixel_tgt: 0.61,
+            pixel_tol: 0.076,
+            pixel_exclusion: 100,
+            min_exposure: Duration::from_millis(1),
+            max_exposure: Duration::from_secs(10),
+            max_bin: 1,
+        }
+    }
+}
+
 impl OptExposureConfig {
     pub fn get_optimum_exp_config(&self) -> Option<OptimumExposure> {
         OptimumExposureBuilder::default()
@@ -21,7 +37,7 @@ impl OptExposureConfig {
         .pixel_tgt(self.pixel_tgt)
         .pixel_uncertainty(self.pixel_tol)
         .pixel_exclusion(self.pixel_exclusion)
-        .min_allowed_exp(self.min_exopsure)
+        .min_allowed_exp(self.min_exposure)
         .max_allowed_exp(self.max_exposure)
         .max_allowed_bin(self.max_bin)
         .build()
@@ -1,5 +1,6 @@
 use log::error;
-use std::fmt::Debug;
+use chrono::{DateTime, Utc};
+use std::{fmt::Debug, time::SystemTime};
 
 // ============================================ PUBLIC =============================================
 
@@ -15,4 +16,25 @@ pub fn log_err<T, E: Debug>(label: &str, result: Result<T, E>) -> Option<T> {
         },
         Ok(result) => Some(result)
     }
+}
+
+/// Format time as UTC with the chrono format string, e.g. %Y-%m-%d %H:%M:%S
+pub fn format_utc_time(time: SystemTime, format: &str) -> String {
+    DateTime::<Utc>::from(time).format(format).to_string()
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn utc_time_is_formatted() {
+        assert_eq!(format_utc_time(UNIX_EPOCH, "%Y-%m-%d %H:%M:%S"), "1970-01-01 00:00:00");
+        let time = UNIX_EPOCH + Duration::from_millis(1709327707250);
+        assert_eq!(format_utc_time(time, "%Y%m%d_%H%M%S%.3f"), "20240301_211507.250");
+    }
 }
\ No newline at end of file
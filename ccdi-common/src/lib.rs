@@ -3,7 +3,7 @@ mod messages;
 mod file;
 mod autoexp;
 
-pub use helpers::{to_string, log_err};
+pub use helpers::{to_string, log_err, format_utc_time};
 pub use messages::*;
 pub use file::*;
 pub use autoexp::*;
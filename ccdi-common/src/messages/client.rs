@@ -1,11 +1,14 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::SystemTime};
 
 use ccdi_imager_interface::{ExposureArea, ExposureParams, ImagerProperties};
 use crate::ImgSize;
 use serde_derive::{Deserialize, Serialize};
 use serialimage::DynamicSerialImage;
 
-use crate::{OptExposureConfig, StorageDetail, StorageState};
+use crate::{
+    CommandResult, Event, ExposureStatus, HostStatus, OptExposureConfig, PowerStatus, Preset,
+    ShutdownStage, StorageDetail, StorageState, WorkerStatus,
+};
 
 use super::gui_config::GuiConfig;
 
@@ -16,12 +19,32 @@ pub enum ClientMessage {
     Reconnect,
     View(Box<ViewState>),
     PngImage(Arc<Vec<u8>>),
+    /// Config file as it is on disk in YAML format, without environment and command line overrides
+    ConfigText(String),
+    /// Events added to the event log, all events are sent after a client connects
+    Events(Vec<Event>),
+    /// Reply to a client command, sent only to the connection which sent it
+    CommandResult(CommandResult),
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct RawImage {
     pub params: ExposureParams,
     pub data: DynamicSerialImage,
+    pub context: FrameContext,
+}
+
+/// State of the camera while the frame was exposed, written to headers of saved files
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FrameContext {
+    /// Name of the device which took the frame
+    pub instrument: String,
+    /// Start of the exposure
+    pub start: SystemTime,
+    /// Requested sensor temperature in degrees celsius
+    pub target_temperature: f64,
+    /// Heater duty cycle 0 - 1
+    pub heating_pwm: f64,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Default)]
@@ -33,6 +56,11 @@ pub struct ViewState {
     pub camera_params: CameraParams,
     pub storage_detail: StorageDetail,
     pub config: GuiConfig,
+    pub host: HostStatus,
+    pub power: PowerStatus,
+    pub watchdog: WatchdogStats,
+    pub workers: Vec<WorkerStatus>,
+    pub presets: Vec<Preset>,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -115,27 +143,41 @@ impl Default for CameraParams {
     }
 }
 
+/// Counters of exposures recovered by the exposure watchdog
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Default)]
+pub struct WatchdogStats {
+    /// Exposures which were not read out in time
+    pub timeouts: usize,
+    /// Exposures restarted after a timeout
+    pub retries: usize,
+    /// Camera reconnections after repeated timeouts
+    pub reconnects: usize,
+}
+
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct LogicStatus {
     pub camera: ConnectionState,
-    pub exposure: ConnectionState,
+    pub exposure: ExposureStatus,
     pub storage: StorageState,
     pub trigger: ConnectionState,
     pub required: ConnectionState,
     pub loop_enabled: ConnectionState,
     pub save: ConnectionState,
+    /// Current step of the shutdown sequence, None when running normally
+    pub shutdown: Option<ShutdownStage>,
 }
 
 impl Default for LogicStatus {
     fn default() -> Self {
         Self {
             camera: ConnectionState::Disconnected,
-            exposure: ConnectionState::Disconnected,
+            exposure: ExposureStatus::default(),
             trigger: ConnectionState::Disconnected,
             required: ConnectionState::Disconnected,
             storage: StorageState::Unknown,
             save: ConnectionState::Disconnected,
             loop_enabled: ConnectionState::Disconnected,
+            shutdown: None,
         }
     }
 }
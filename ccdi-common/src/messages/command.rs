@@ -0,0 +1,25 @@
+use serde_derive::{Serialize, Deserialize};
+
+use crate::StateMessage;
+
+// ============================================ PUBLIC =============================================
+
+/// Message sent from a client, answered by ClientMessage::CommandResult with the same id
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ClientCommand {
+    /// Assigned by the client, unique within one connection
+    pub id: u64,
+    /// Connection which sent the command, filled in by the server
+    #[serde(default)]
+    pub connection: usize,
+    pub message: Box<StateMessage>,
+}
+
+/// Acknowledgement of a client command, error describes why the command was rejected
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CommandResult {
+    pub id: u64,
+    pub connection: usize,
+    /// Accepted command carries the reason if its value was adjusted, e.g. clamped to a limit
+    pub result: Result<Option<String>, String>,
+}
@@ -8,7 +8,9 @@ use crate::ConnectionState;
 pub enum StorageState {
     Unknown,
     Error(String),
-    Available(StorageCapacity)
+    Available(StorageCapacity),
+    /// Free space is below the configured minimum, saving is paused
+    Full(StorageCapacity),
 }
 
 impl StorageState {
@@ -17,6 +19,7 @@ impl StorageState {
             StorageState::Unknown => ConnectionState::Disconnected,
             StorageState::Error(_) => ConnectionState::Disconnected,
             StorageState::Available(_) => ConnectionState::Established,
+            StorageState::Full(_) => ConnectionState::Established,
         }
     }
 }
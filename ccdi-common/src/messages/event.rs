@@ -0,0 +1,33 @@
+use std::time::SystemTime;
+
+use serde_derive::{Serialize, Deserialize};
+
+// ============================================ PUBLIC =============================================
+
+/// Entry of the event log shown in the client
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Event {
+    /// Increasing number used by clients to skip events they already have
+    pub id: u64,
+    pub time: SystemTime,
+    pub severity: Severity,
+    pub source: EventSource,
+    pub message: String,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum EventSource {
+    Camera,
+    Storage,
+    Io,
+    /// Result of a command sent from a client
+    Client,
+    System,
+}
@@ -0,0 +1,45 @@
+use serde_derive::{Serialize, Deserialize};
+
+use crate::ConnectionState;
+
+// ============================================ PUBLIC =============================================
+
+/// Phase of the exposure lifecycle
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, Default)]
+pub enum ExposureState {
+    /// No exposure in progress
+    #[default]
+    Idle,
+    /// Exposure started, waiting for exposure time to elapse
+    Exposing,
+    /// Exposure time elapsed, waiting for the sensor readout
+    Reading,
+    /// Image is ready and being downloaded from the camera
+    Downloading,
+    /// Last exposure was cancelled
+    Cancelled,
+    /// Last exposure failed
+    Failed,
+}
+
+impl ExposureState {
+    /// True if the camera is busy with an exposure
+    pub fn is_active(&self) -> bool {
+        matches!(self, Self::Exposing | Self::Reading | Self::Downloading)
+    }
+
+    pub fn as_connection_state(&self) -> ConnectionState {
+        match self {
+            Self::Exposing | Self::Reading | Self::Downloading => ConnectionState::Established,
+            Self::Failed => ConnectionState::Connecting,
+            Self::Idle | Self::Cancelled => ConnectionState::Disconnected,
+        }
+    }
+}
+
+/// Exposure state together with the reason of the last transition
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Default)]
+pub struct ExposureStatus {
+    pub state: ExposureState,
+    pub reason: String,
+}
@@ -3,6 +3,7 @@ use serde_derive::{Serialize, Deserialize};
 // ============================================ PUBLIC =============================================
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GuiConfig {
     pub cooling: ButtonSet<f64>,
     pub heating: ButtonSet<f64>,
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use serde_derive::{Serialize, Deserialize};
+
+// ============================================ PUBLIC =============================================
+
+/// Health of the computer running the service
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Default)]
+pub struct HostStatus {
+    /// CPU temperature in degrees celsius
+    pub cpu_temperature: Option<f32>,
+    /// Raspberry Pi throttling flags as reported by the firmware
+    pub throttle_flags: Option<u32>,
+    /// Load average over 1, 5 and 15 minutes
+    pub load_average: Option<[f32; 3]>,
+    pub memory_total_megabytes: Option<u64>,
+    pub memory_available_megabytes: Option<u64>,
+    pub uptime: Option<Duration>,
+}
+
+impl HostStatus {
+    /// Supply voltage is currently too low
+    pub fn under_voltage(&self) -> bool {
+        self.has_flag(UNDER_VOLTAGE)
+    }
+
+    /// CPU frequency is currently reduced by the firmware
+    pub fn throttling(&self) -> bool {
+        self.has_flag(FREQUENCY_CAPPED | THROTTLED | SOFT_TEMPERATURE_LIMIT)
+    }
+
+    /// Under voltage or throttling happened any time since boot
+    pub fn throttling_occurred(&self) -> bool {
+        self.has_flag(UNDER_VOLTAGE_OCCURRED | FREQUENCY_CAPPED_OCCURRED | THROTTLED_OCCURRED)
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+const UNDER_VOLTAGE: u32 = 1 << 0;
+const FREQUENCY_CAPPED: u32 = 1 << 1;
+const THROTTLED: u32 = 1 << 2;
+const SOFT_TEMPERATURE_LIMIT: u32 = 1 << 3;
+const UNDER_VOLTAGE_OCCURRED: u32 = 1 << 16;
+const FREQUENCY_CAPPED_OCCURRED: u32 = 1 << 17;
+const THROTTLED_OCCURRED: u32 = 1 << 18;
+
+impl HostStatus {
+    fn has_flag(&self, mask: u32) -> bool {
+        self.throttle_flags.map(|flags| flags & mask != 0).unwrap_or(false)
+    }
+}
@@ -12,7 +12,18 @@ pub enum IoMessage {
     SetStatus(StatusMode)
 }
 
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum StatusMode {
-    On,
-}
\ No newline at end of file
+    /// No camera connected
+    Disconnected,
+    /// Camera connected, exposure loop not running
+    Idle,
+    /// Exposure loop running, frames are not stored
+    Exposing,
+    /// Exposure loop running and frames are stored
+    Saving,
+    /// Storage not available or nearly full
+    StorageError,
+    /// Service is shutting down
+    ShuttingDown,
+}
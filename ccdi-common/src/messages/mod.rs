@@ -5,6 +5,14 @@ mod storage;
 mod common;
 mod gui_config;
 mod io;
+mod host;
+mod power;
+mod exposure;
+mod shutdown;
+mod worker;
+mod preset;
+mod event;
+mod command;
 
 pub use client::*;
 pub use state::*;
@@ -12,4 +20,12 @@ pub use process::*;
 pub use storage::*;
 pub use common::*;
 pub use gui_config::*;
-pub use io::*;
\ No newline at end of file
+pub use io::*;
+pub use host::*;
+pub use power::*;
+pub use exposure::*;
+pub use shutdown::*;
+pub use worker::*;
+pub use preset::*;
+pub use event::*;
+pub use command::*;
\ No newline at end of file
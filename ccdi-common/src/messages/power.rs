@@ -0,0 +1,25 @@
+use serde_derive::{Serialize, Deserialize};
+
+// ============================================ PUBLIC =============================================
+
+/// Supply voltage measured by the power monitor
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Default)]
+pub struct PowerStatus {
+    /// Last measured supply voltage in volts
+    pub voltage: Option<f64>,
+    /// Voltage change in volts per hour computed over the trend window
+    pub trend: Option<f64>,
+    pub state: PowerState,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, Default)]
+pub enum PowerState {
+    /// Power monitoring disabled or voltage could not be read
+    #[default]
+    Unknown,
+    Normal,
+    /// Voltage below warning threshold
+    Low,
+    /// Voltage below shutdown threshold for the configured time
+    Critical,
+}
@@ -0,0 +1,23 @@
+use serde_derive::{Serialize, Deserialize};
+
+use crate::{CameraParams, ImageParams};
+
+// ============================================ PUBLIC =============================================
+
+/// Named set of camera and image parameters, e.g. "focus", "deep" or "flats"
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub camera_params: CameraParams,
+    pub image_params: ImageParams,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum PresetMessage {
+    /// Save current parameters under the name, existing preset with the same name is replaced
+    Create(String),
+    Apply(String),
+    /// Rename preset (old name, new name)
+    Rename((String, String)),
+    Delete(String),
+}
@@ -0,0 +1,16 @@
+use serde_derive::{Serialize, Deserialize};
+
+// ============================================ PUBLIC =============================================
+
+/// Steps performed before the service exits
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ShutdownStage {
+    /// Exposure loop disabled, waiting for the running exposure to finish
+    StopExposure,
+    /// Storage disabled, waiting for queued images to be written
+    FinishWrites,
+    /// Waiting for the camera to reach the warm up temperature
+    WarmUp,
+    /// Closing the camera and stopping the worker threads
+    CloseDevices,
+}
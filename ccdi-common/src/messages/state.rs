@@ -2,7 +2,10 @@ use std::sync::Arc;
 
 use serde_derive::{Deserialize, Serialize};
 
-use crate::{StorageDetail, StorageMessage, StorageState};
+use crate::{
+    ClientCommand, PowerStatus, PresetMessage, StorageDetail, StorageLogRecord, StorageMessage,
+    StorageState,
+};
 
 pub use ccdi_imager_interface::OptConfigCmd;
 
@@ -10,6 +13,8 @@ pub use ccdi_imager_interface::OptConfigCmd;
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum StateMessage {
+    /// Message from a client which expects a CommandResult
+    Command(ClientCommand),
     ClientInformation((String, String)), // String for testing.
     ExposureMessage(ExposureCommand),
     ImageParam(ImageParamMessage),
@@ -19,8 +24,25 @@ pub enum StateMessage {
     UpdateStorageState(StorageState),
     TriggerValueChanged(bool),
     StorageMessage(StorageMessage),
-    UpdateStorageDetail(StorageDetail),
+    UpdateStorageDetail(Box<StorageDetail>),
+    /// Files and directories removed by the retention policy
+    RetentionDeleted(Vec<StorageLogRecord>),
+    UpdatePowerStatus(PowerStatus),
+    Preset(PresetMessage),
+    Config(ConfigMessage),
+    /// Shut down the service and run the turn off command
     PowerOff,
+    /// Shut down the service without turning off the computer, sent on SIGTERM / SIGINT
+    Shutdown,
+}
+
+/// Service config edited from the client in YAML format
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ConfigMessage {
+    /// Request content of config.yaml, answered by ClientMessage::ConfigText
+    Load,
+    /// Validate with overrides applied, save to config.yaml and apply
+    Save(String),
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
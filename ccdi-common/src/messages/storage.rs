@@ -13,6 +13,94 @@ pub enum StorageMessage {
     UpdateCadence(Duration),
     ProcessImage(Arc<RawImage>),
     SetDirectory(String),
+    /// Formats written for each saved frame
+    SetFormats(Vec<OutputFormat>),
+    /// Compression of saved FITS files
+    SetCompression(FitsCompression),
+    /// Write every frame to a SER video until stopped or a limit is reached, cadence is ignored
+    StartRecording(RecordingLimits),
+    StopRecording,
+    /// Root of saved frames, internal storage is used while a selected drive is not mounted
+    SetTarget(StorageTarget),
+    /// Restore settings saved before restart
+    RestoreSettings(StorageSettings),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize, Default)]
+pub enum StorageTarget {
+    /// Storage root from the config
+    #[default]
+    Internal,
+    /// Mount point of a removable drive
+    Removable(String),
+}
+
+/// Mounted removable drive which can be selected as storage target
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RemovableDrive {
+    pub device: String,
+    pub mount_point: String,
+    pub capacity: StorageState,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    Fits,
+    /// 16-bit TIFF
+    Tiff,
+    /// 16-bit PNG
+    Png,
+    Xisf,
+    /// Header keywords in JSON next to the image files
+    Sidecar,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 5] = [
+        OutputFormat::Fits,
+        OutputFormat::Tiff,
+        OutputFormat::Png,
+        OutputFormat::Xisf,
+        OutputFormat::Sidecar,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Fits => "fits",
+            OutputFormat::Tiff => "tif",
+            OutputFormat::Png => "png",
+            OutputFormat::Xisf => "xisf",
+            OutputFormat::Sidecar => "json",
+        }
+    }
+}
+
+/// Tile compression of FITS files, both are lossless for integer frames
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FitsCompression {
+    #[default]
+    None,
+    Rice,
+    Gzip,
+}
+
+/// Storage settings persisted across restarts
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StorageSettings {
+    pub storage_name: String,
+    pub cadence: Duration,
+    pub counter: usize,
+    pub storage_enabled: bool,
+    /// Empty in files saved by older versions, formats from config are used then
+    #[serde(default)]
+    pub formats: Vec<OutputFormat>,
+    /// Missing in files saved by older versions, compression from config is used then
+    #[serde(default)]
+    pub compression: Option<FitsCompression>,
+    #[serde(default)]
+    pub target: StorageTarget,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -23,12 +111,59 @@ pub struct StorageDetail {
     pub storage_log: Vec<StorageLogRecord>,
     pub storage_enabled: bool,
     pub state: StorageState,
+    pub formats: Vec<OutputFormat>,
+    pub compression: FitsCompression,
+    pub queue: QueueStatus,
+    pub target: StorageTarget,
+    pub drives: Vec<RemovableDrive>,
+    /// Selected drive is not mounted, frames are written to the internal storage
+    pub fallback: bool,
+    /// Current or last finished video recording
+    pub recording: Option<Box<RecordingStatus>>,
+}
+
+/// Frames waiting to be written and frames lost because the write queue was full
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct QueueStatus {
+    pub depth: usize,
+    pub capacity: usize,
+    pub dropped: usize,
+    /// Megabytes written per second over the last frames
+    pub throughput: f64,
+}
+
+/// Recording stops at whichever limit is reached first, without limits it runs until stopped
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RecordingLimits {
+    pub frames: Option<usize>,
+    pub duration: Option<Duration>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RecordingStatus {
+    pub active: bool,
+    pub file: String,
+    pub limits: RecordingLimits,
+    pub frames: usize,
+    /// Frames dropped by the storage queue or not matching the size of the first frame
+    pub dropped: usize,
+    /// Frame rate over the last few frames
+    pub fps: f64,
+    pub elapsed: Duration,
+    /// Reason why the recording stopped early
+    pub error: Option<String>,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct StorageLogRecord {
     pub name: String,
     pub status: StorageLogStatus,
+    /// Size of the uncompressed image data divided by the file size
+    #[serde(default)]
+    pub compression_ratio: Option<f64>,
+    /// Written to the internal storage because the selected drive was missing
+    #[serde(default)]
+    pub fallback: bool,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -46,6 +181,13 @@ impl Default for StorageDetail {
             storage_log: Vec::new(),
             storage_enabled: false,
             state: StorageState::Unknown,
+            formats: Vec::new(),
+            compression: FitsCompression::None,
+            queue: QueueStatus::default(),
+            target: StorageTarget::Internal,
+            drives: Vec::new(),
+            fallback: false,
+            recording: None,
         }
     }
 }
\ No newline at end of file
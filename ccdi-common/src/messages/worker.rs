@@ -0,0 +1,22 @@
+use serde_derive::{Serialize, Deserialize};
+
+// ============================================ PUBLIC =============================================
+
+/// Health of a supervised worker thread
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct WorkerStatus {
+    pub name: String,
+    pub state: WorkerState,
+    /// Number of restarts after a panic or failure
+    pub restarts: usize,
+    pub last_error: Option<String>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum WorkerState {
+    Running,
+    /// Worker failed and waits for restart
+    Restarting,
+    /// Worker finished, its channel was closed
+    Stopped,
+}
@@ -2,7 +2,7 @@ use std::{fmt::Debug, time::Duration};
 
 use ccdi_imager_interface::{
     BasicProperties, DeviceDescriptor, DeviceProperty, ExposureArea, ExposureParams, ImagerDevice,
-    ImagerDriver, ImagerProperties, OptConfigCmd, TemperatureRequest,
+    ImagerDriver, ImagerProperties, OptConfigCmd, RoiRules, TemperatureRequest,
 };
 
 use log::{info, warn};
@@ -12,6 +12,16 @@ use cameraunit_asi::{
     OptimumExposure, ROI,
 };
 
+/// ASI sensors read width in multiples of 8 and height in multiples of 2
+const ASI_ROI_RULES: RoiRules = RoiRules {
+    x_align: 1,
+    y_align: 1,
+    width_align: 8,
+    height_align: 2,
+    min_width: 8,
+    min_height: 2,
+};
+
 pub struct ASICameraDriver {
     opt: Option<OptimumExposure>,
 }
@@ -70,6 +80,11 @@ impl ImagerDriver for ASICameraDriver {
                 height: croi.height as usize,
             };
         } else {
+            roi = ASI_ROI_RULES.snap(
+                &roi,
+                cam.get_ccd_width() as usize,
+                cam.get_ccd_height() as usize,
+            );
             let roi = ROI {
                 x_min: roi.x as u32,
                 y_min: roi.y as u32,
@@ -232,6 +247,10 @@ fn read_basic_props(device: &CameraUnitASI) -> BasicProperties {
             width: roi.width as usize,
             height: roi.height as usize,
         },
+        max_gain: device.get_max_gain().ok().map(|gain| gain.clamp(0, u16::MAX as i64) as u16),
+        min_exposure: device.get_min_exposure().ok().map(|time| time.as_secs_f64()),
+        max_exposure: device.get_max_exposure().ok().map(|time| time.as_secs_f64()),
+        roi_rules: ASI_ROI_RULES,
     }
 }
 
@@ -4,7 +4,7 @@ use cameraunit::DynamicSerialImage;
 pub use cameraunit::{ImageMetaData, SerialImageBuffer};
 use ccdi_imager_interface::{
     BasicProperties, DeviceDescriptor, DeviceProperty, ExposureArea, ExposureParams, ImagerDevice,
-    ImagerDriver, ImagerProperties, OptConfigCmd, TemperatureRequest,
+    ImagerDriver, ImagerProperties, OptConfigCmd, RoiRules, TemperatureRequest,
 };
 use image::{DynamicImage, ImageBuffer};
 
@@ -75,6 +75,10 @@ impl ImagerDevice for DemoImagerDevice {
                     width: 6000,
                     height: 4000,
                 },
+                max_gain: Some(1000),
+                min_exposure: Some(0.000032),
+                max_exposure: Some(3600.0),
+                roi_rules: RoiRules::default(),
             },
             other: list_demo_properties(self),
         })
@@ -197,5 +201,5 @@ fn generate_pixel(x: usize, y: usize, dx: f64, dy: f64) -> (u16, u16, u16) {
     }
 
     let out = ((k as f64 - 50.0) * 100.0).clamp(0.0, 65535.0) as u16;
-    (600 * (out % 57), out * 2, 2000 * (out % 17))
+    (600 * (out % 57), out.wrapping_mul(2), 2000 * (out % 17))
 }
@@ -4,7 +4,7 @@ use std::{fmt::Debug, time::Duration};
 
 use ccdi_imager_interface::{
     BasicProperties, DeviceDescriptor, DeviceProperty, ExposureArea, ExposureParams, ImagerDevice,
-    ImagerDriver, ImagerProperties, OptConfigCmd, TemperatureRequest,
+    ImagerDriver, ImagerProperties, OptConfigCmd, RoiRules, TemperatureRequest,
 };
 
 use log::{info, warn};
@@ -14,6 +14,20 @@ use cameraunit_fli::{
     OptimumExposure, ROI,
 };
 
+/// Binning of all exposures, the area size is passed to the SDK in binned pixels
+const FLI_BINNING: usize = 1;
+
+/// FLISetImageArea takes the start in unbinned and the size in binned pixels, so the area is
+/// aligned to the binning and the smallest readout is one binned pixel
+const FLI_ROI_RULES: RoiRules = RoiRules {
+    x_align: FLI_BINNING,
+    y_align: FLI_BINNING,
+    width_align: FLI_BINNING,
+    height_align: FLI_BINNING,
+    min_width: FLI_BINNING,
+    min_height: FLI_BINNING,
+};
+
 pub struct FLICameraDriver {
     opt: Option<OptimumExposure>,
 }
@@ -67,13 +81,18 @@ impl ImagerDriver for FLICameraDriver {
                 height: croi.height as usize,
             };
         } else {
+            roi = FLI_ROI_RULES.snap(
+                &roi,
+                cam.get_ccd_width() as usize,
+                cam.get_ccd_height() as usize,
+            );
             let roi = ROI {
                 x_min: roi.x as u32,
                 y_min: roi.y as u32,
                 width: roi.width as u32,
                 height: roi.height as u32,
-                bin_x: 1,
-                bin_y: 1,
+                bin_x: FLI_BINNING as u32,
+                bin_y: FLI_BINNING as u32,
             };
             cam.set_roi(&roi).map_err(|x| x.to_string())?;
         }
@@ -133,8 +152,8 @@ impl ImagerDevice for FLICameraImager {
             y_min: params.area.y as u32,
             width: params.area.width as u32,
             height: params.area.height as u32,
-            bin_x: 1,
-            bin_y: 1,
+            bin_x: FLI_BINNING as u32,
+            bin_y: FLI_BINNING as u32,
         };
         self.device.set_roi(&roi).map_err(|x| x.to_string())?;
         self.device.start_exposure().map_err(|x| x.to_string())?;
@@ -221,6 +240,10 @@ fn read_basic_props(device: &CameraUnitFLI) -> BasicProperties {
             width: roi.width as usize,
             height: roi.height as usize,
         },
+        max_gain: device.get_max_gain().ok().map(|gain| gain.clamp(0, u16::MAX as i64) as u16),
+        min_exposure: device.get_min_exposure().ok().map(|time| time.as_secs_f64()),
+        max_exposure: device.get_max_exposure().ok().map(|time| time.as_secs_f64()),
+        roi_rules: FLI_ROI_RULES,
     }
 }
 
@@ -264,3 +287,20 @@ fn prop_f32(name: &str, value: f32, precision: usize) -> DeviceProperty {
         value: format!("{:0.prec$}", value, prec = precision),
     }
 }
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roi_is_snapped_to_binned_pixels() {
+        let area = ExposureArea { x: 10, y: 11, width: 805, height: 601 };
+        assert_eq!(FLI_ROI_RULES.snap(&area, 4096, 4096), area);
+
+        let empty = ExposureArea { x: 4095, y: 0, width: 0, height: 0 };
+        let snapped = FLI_ROI_RULES.snap(&empty, 4096, 4096);
+        assert_eq!(snapped, ExposureArea { x: 4095, y: 0, width: 1, height: 1 });
+    }
+}
@@ -59,6 +59,13 @@ pub struct BasicProperties {
     pub temperature: f32,
     pub exposure: f32,
     pub roi: ExposureArea,
+    /// Highest gain accepted by the camera, None if not known
+    pub max_gain: Option<u16>,
+    /// Exposure time range in seconds, None if not known
+    pub min_exposure: Option<f64>,
+    pub max_exposure: Option<f64>,
+    /// Geometry of region of interest accepted by the camera
+    pub roi_rules: RoiRules,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -98,3 +105,74 @@ impl ExposureArea {
         (self.x, self.y, self.width, self.height)
     }
 }
+
+/// Alignment and minimum size of region of interest, published by each driver
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RoiRules {
+    /// Start coordinates must be multiples of these
+    pub x_align: usize,
+    pub y_align: usize,
+    /// Width and height must be multiples of these
+    pub width_align: usize,
+    pub height_align: usize,
+    pub min_width: usize,
+    pub min_height: usize,
+}
+
+impl Default for RoiRules {
+    /// Any area inside the sensor
+    fn default() -> Self {
+        Self {
+            x_align: 1,
+            y_align: 1,
+            width_align: 1,
+            height_align: 1,
+            min_width: 1,
+            min_height: 1,
+        }
+    }
+}
+
+impl RoiRules {
+    /// Nearest valid area inside the sensor of given size, area size is rounded down and its
+    /// start is moved to fit
+    pub fn snap(&self, area: &ExposureArea, width: usize, height: usize) -> ExposureArea {
+        let (x, width) = snap_axis(
+            area.x,
+            area.width,
+            self.x_align,
+            self.width_align,
+            self.min_width,
+            width,
+        );
+        let (y, height) = snap_axis(
+            area.y,
+            area.height,
+            self.y_align,
+            self.height_align,
+            self.min_height,
+            height,
+        );
+
+        ExposureArea { x, y, width, height }
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+fn snap_axis(
+    start: usize,
+    size: usize,
+    start_align: usize,
+    size_align: usize,
+    min_size: usize,
+    sensor: usize,
+) -> (usize, usize) {
+    let start_align = start_align.max(1);
+    let size_align = size_align.max(1);
+    let max_size = sensor / size_align * size_align;
+    let min_size = (min_size.div_ceil(size_align) * size_align).min(max_size);
+    let size = (size / size_align * size_align).clamp(min_size, max_size);
+    let start = start.min(sensor - size) / start_align * start_align;
+    (start, size)
+}
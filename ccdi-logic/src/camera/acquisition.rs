@@ -0,0 +1,555 @@
+use std::{
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use ccdi_common::{
+    log_err, CameraParams, ClientMessage, ExposureCommand, ExposureState, ExposureStatus,
+    ImageParams, ProcessMessage, Severity,
+};
+use ccdi_imager_interface::{DeviceDescriptor, ExposureArea, ImagerDriver, ImagerProperties};
+use log::info;
+
+use crate::{
+    supervisor::{start_supervised_thread, WorkerMonitor},
+    validate_roi, ServiceConfig, SharedConfig, StorageSender,
+};
+
+use super::{connected::ConnectedCameraController, exposure::WatchdogEvent};
+
+// ============================================ PUBLIC =============================================
+
+/// Command sent from the camera controller to the acquisition thread
+#[derive(Clone, PartialEq, Debug)]
+pub enum AcquisitionCommand {
+    UpdateCameraParams(CameraParams),
+    UpdateImageParams(ImageParams),
+    Exposure(ExposureCommand),
+    UpdateTrigger(bool),
+    /// Close the device and exit the thread
+    Close,
+}
+
+/// Report sent from the acquisition thread to the camera controller
+#[derive(Clone, PartialEq, Debug)]
+pub enum AcquisitionReport {
+    /// Thread (re)started and needs current parameters
+    Started,
+    Snapshot(AcquisitionSnapshot),
+    Detail((Severity, String)),
+    /// ROI reported by the camera after connection
+    Roi(ExposureArea),
+    Watchdog(WatchdogEvent),
+    ClientMessages(Vec<ClientMessage>),
+    /// Device closed and thread finished
+    Closed,
+}
+
+/// Camera state as seen by the acquisition thread
+#[derive(Clone, PartialEq, Debug)]
+pub struct AcquisitionSnapshot {
+    pub connected: bool,
+    pub properties: Option<Arc<ImagerProperties>>,
+    pub exposure: ExposureStatus,
+}
+
+impl Default for AcquisitionSnapshot {
+    fn default() -> Self {
+        Self {
+            connected: false,
+            properties: None,
+            exposure: ExposureStatus {
+                state: ExposureState::Idle,
+                reason: String::from("Camera not connected"),
+            },
+        }
+    }
+}
+
+/// Camera driver, device and exposure loop run in this thread so that long downloads do not
+/// block the logic thread.
+pub fn start_acquisition_thread(
+    demo_mode: String,
+    config: SharedConfig,
+    process_tx: Sender<ProcessMessage>,
+    storage_tx: StorageSender,
+    command_rx: Receiver<AcquisitionCommand>,
+    report_tx: Sender<AcquisitionReport>,
+    workers: WorkerMonitor,
+) -> Result<JoinHandle<()>, String> {
+    start_supervised_thread("acquisition", workers, move || {
+        let mut version = config.version();
+        let current = config.get();
+        let driver = create_driver(&demo_mode, &current);
+        let mut acquisition = Acquisition::new(
+            driver,
+            current,
+            process_tx.clone(),
+            storage_tx.clone(),
+            report_tx.clone(),
+        );
+
+        acquisition.report(AcquisitionReport::Started);
+
+        loop {
+            match command_rx.recv_timeout(acquisition.poll_interval()) {
+                // Close requested or camera controller dropped - close the device and exit
+                Ok(AcquisitionCommand::Close) | Err(RecvTimeoutError::Disconnected) => {
+                    acquisition.close();
+                    return Ok(());
+                }
+                Ok(command) => acquisition.process(command),
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+
+            acquisition.periodic();
+
+            // ROI, exposure and watchdog settings are used from the next camera connection
+            if let Some(new_config) = config.changed(&mut version) {
+                acquisition.config = new_config;
+            }
+        }
+    })
+}
+
+// =========================================== PRIVATE =============================================
+
+/// Poll interval while an exposure is running to pick up the image as soon as it is ready
+const ACTIVE_POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// Poll interval while idle or disconnected
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+fn create_driver(demo_mode: &str, config: &ServiceConfig) -> Box<dyn ImagerDriver> {
+    match demo_mode {
+        #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
+        "asi" => {
+            let mut drv = ccdi_imager_asicam::ASICameraDriver::new();
+            drv.update_opt_config(config.exp.get_optimum_exp_config().unwrap());
+            Box::new(drv)
+        },
+        "fli" => {
+            let mut drv = ccdi_imager_fli::FLICameraDriver::new();
+            drv.update_opt_config(config.exp.get_optimum_exp_config().unwrap());
+            Box::new(drv)
+        }
+        _ => Box::new(ccdi_imager_demo::DemoImagerDriver::new()),
+    }
+}
+
+struct Acquisition {
+    driver: Box<dyn ImagerDriver>,
+    state: State,
+    connected: Option<ConnectedCameraController>,
+    config: Arc<ServiceConfig>,
+    process_tx: Sender<ProcessMessage>,
+    storage_tx: StorageSender,
+    report_tx: Sender<AcquisitionReport>,
+    camera_params: CameraParams,
+    image_params: Option<ImageParams>,
+    trigger_active: bool,
+    last_periodic: Option<Instant>,
+    last_snapshot: Option<AcquisitionSnapshot>,
+    detail: String,
+}
+
+impl Acquisition {
+    fn new(
+        driver: Box<dyn ImagerDriver>,
+        config: Arc<ServiceConfig>,
+        process_tx: Sender<ProcessMessage>,
+        storage_tx: StorageSender,
+        report_tx: Sender<AcquisitionReport>,
+    ) -> Self {
+        Self {
+            driver,
+            state: State::Error,
+            connected: None,
+            config,
+            process_tx,
+            storage_tx,
+            report_tx,
+            camera_params: CameraParams::new(),
+            image_params: None,
+            trigger_active: false,
+            last_periodic: None,
+            last_snapshot: None,
+            detail: String::new(),
+        }
+    }
+
+    fn poll_interval(&self) -> Duration {
+        match self.exposure_active() {
+            true => ACTIVE_POLL_INTERVAL,
+            false => IDLE_POLL_INTERVAL,
+        }
+    }
+
+    fn process(&mut self, command: AcquisitionCommand) {
+        match command {
+            AcquisitionCommand::UpdateCameraParams(params) => {
+                self.camera_params = params.clone();
+                if let Some(camera) = self.connected.as_mut() {
+                    camera.update_camera_params(params);
+                }
+            }
+            AcquisitionCommand::UpdateImageParams(params) => {
+                self.image_params = Some(params.clone());
+                if let Some(camera) = self.connected.as_mut() {
+                    camera.update_image_params(params);
+                }
+            }
+            AcquisitionCommand::UpdateTrigger(value) => {
+                self.trigger_active = value;
+                if let Some(camera) = self.connected.as_mut() {
+                    camera.update_trigger_status(value);
+                }
+            }
+            AcquisitionCommand::Exposure(command) => {
+                let detail = match self.connected.as_mut() {
+                    None => Some((
+                        Severity::Warning,
+                        String::from("Not connected - cannot handle exposure command"),
+                    )),
+                    Some(connected) => connected.exposure_command(command).err().map(|message| {
+                        (Severity::Error, format!("Exposure command failed: {}", message))
+                    }),
+                };
+
+                if let Some(detail) = detail {
+                    self.report(AcquisitionReport::Detail(detail));
+                }
+            }
+            AcquisitionCommand::Close => {}
+        }
+
+        self.send_snapshot();
+    }
+
+    fn periodic(&mut self) {
+        if let Some(last_periodic) = self.last_periodic {
+            if last_periodic.elapsed() < self.poll_interval() {
+                return;
+            }
+        }
+
+        self.last_periodic = Some(Instant::now());
+        let old_state = self.state;
+
+        self.state = match self.state {
+            State::Error => self.handle_error_state(),
+            State::Connected => self.handle_connected_state(),
+        };
+
+        if self.state != old_state {
+            info!("Camera state {:?} -> {:?}", old_state, self.state);
+        }
+
+        if let Some(ref mut camera) = self.connected {
+            let messages = camera.flush_messages();
+            if !messages.is_empty() {
+                self.report(AcquisitionReport::ClientMessages(messages));
+            }
+        }
+
+        self.send_snapshot();
+    }
+
+    fn close(mut self) {
+        if let Some(mut camera) = self.connected.take() {
+            camera.turn_off();
+        }
+
+        info!("Acquisition thread closed");
+        self.report(AcquisitionReport::Closed);
+    }
+
+    fn exposure_active(&self) -> bool {
+        self.connected
+            .as_ref()
+            .map(|cam| cam.exposure_active())
+            .unwrap_or(false)
+    }
+
+    fn snapshot(&self) -> AcquisitionSnapshot {
+        match self.connected.as_ref() {
+            None => AcquisitionSnapshot::default(),
+            Some(camera) => AcquisitionSnapshot {
+                connected: self.state == State::Connected,
+                properties: Some(camera.get_properties()),
+                exposure: camera.exposure_status(),
+            },
+        }
+    }
+
+    fn send_snapshot(&mut self) {
+        let snapshot = self.snapshot();
+
+        if self.last_snapshot.as_ref() != Some(&snapshot) {
+            self.last_snapshot = Some(snapshot.clone());
+            self.report(AcquisitionReport::Snapshot(snapshot));
+        }
+    }
+
+    fn report(&self, report: AcquisitionReport) {
+        log_err("Send acquisition report", self.report_tx.send(report));
+    }
+
+    /// Report detail to the event log unless it repeats the previous one
+    fn set_detail(&mut self, severity: Severity, detail: &str) {
+        if self.detail != detail {
+            self.detail = detail.to_owned();
+            self.report(AcquisitionReport::Detail((severity, detail.to_owned())));
+        }
+    }
+
+    fn handle_error_state(&mut self) -> State {
+        if let Some(old_device) = self.connected.take() {
+            old_device.close();
+            self.set_detail(Severity::Info, "Closing old device");
+        }
+
+        match self.driver.list_devices() {
+            Err(_) => {
+                self.set_detail(Severity::Error, "Could not list devices");
+                State::Error
+            }
+            Ok(devices) => match devices.as_slice() {
+                [] => {
+                    self.set_detail(Severity::Warning, "No devices present in list");
+                    State::Error
+                }
+                [device_id, ..] => self.connect_and_init(device_id),
+            },
+        }
+    }
+
+    fn connect_and_init(&mut self, id: &DeviceDescriptor) -> State {
+        match self.driver.connect_device(id, &self.config.roi) {
+            Err(_) => {
+                self.set_detail(Severity::Error, "Connect device failed");
+                State::Error
+            }
+            Ok(device) => {
+                self.set_detail(Severity::Info, "Device connected, reading basic info");
+                let (device, roi) = device;
+
+                if let Some(image_params) = self.image_params.as_mut() {
+                    image_params.w = roi.width as u16;
+                    image_params.h = roi.height as u16;
+                    image_params.x = roi.x as u16;
+                    image_params.y = roi.y as u16;
+                }
+
+                self.report(AcquisitionReport::Roi(roi));
+
+                match ConnectedCameraController::new(
+                    id.name.clone(),
+                    device,
+                    self.config.render_size,
+                    self.process_tx.clone(),
+                    self.storage_tx.clone(),
+                    self.config.exp.clone(),
+                    self.config.watchdog.clone(),
+                ) {
+                    Ok(mut connected) => {
+                        let sensor = connected.get_properties().basic;
+
+                        match validate_roi(&self.config.roi, sensor.width, sensor.height) {
+                            Ok(_) => self.set_detail(Severity::Info, "Camera initialized"),
+                            Err(error) => self.set_detail(
+                                Severity::Warning,
+                                &format!("Camera initialized, config {}", error),
+                            ),
+                        }
+
+                        connected.update_camera_params(self.camera_params.clone());
+                        connected.update_trigger_status(self.trigger_active);
+                        if let Some(image_params) = self.image_params.as_ref() {
+                            connected.update_image_params(image_params.clone());
+                        }
+                        self.connected = Some(connected);
+                        State::Connected
+                    }
+                    Err(message) => {
+                        self.set_detail(Severity::Error, &format!("Init failed: {}", message));
+                        self.connected = None;
+                        State::Error
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_connected_state(&mut self) -> State {
+        if let Some(ref mut controller) = self.connected {
+            let result = controller.periodic(self.camera_params.temperature);
+
+            for event in controller.flush_watchdog_events() {
+                self.report(AcquisitionReport::Watchdog(event));
+            }
+
+            match result {
+                Ok(_) => State::Connected,
+                Err(message) => {
+                    self.set_detail(
+                        Severity::Error,
+                        &format!("Periodic task failed: {}", message),
+                    );
+
+                    // Device must be released, otherwise it cannot be opened again
+                    if let Some(device) = self.connected.take() {
+                        device.close();
+                    }
+
+                    State::Error
+                }
+            }
+        } else {
+            State::Error
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum State {
+    Error,
+    Connected,
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+    };
+
+    use cameraunit::DynamicSerialImage;
+    use ccdi_imager_demo::DemoImagerDriver;
+    use ccdi_imager_interface::{ExposureParams, ImagerDevice, OptConfigCmd, TemperatureRequest};
+
+    use crate::{storage_channel, StorageQueueConfig, WatchdogConfig};
+
+    use super::*;
+
+    /// Demo camera which never finishes an exposure and can be opened only once, like a hung
+    /// camera on USB
+    struct HungDriver {
+        open: Arc<AtomicUsize>,
+    }
+
+    struct HungDevice {
+        device: Box<dyn ImagerDevice>,
+        open: Arc<AtomicUsize>,
+    }
+
+    impl ImagerDriver for HungDriver {
+        fn list_devices(&mut self) -> Result<Vec<DeviceDescriptor>, String> {
+            DemoImagerDriver::new().list_devices()
+        }
+
+        fn connect_device(
+            &mut self,
+            descriptor: &DeviceDescriptor,
+            roi_request: &ExposureArea,
+        ) -> Result<(Box<dyn ImagerDevice>, ExposureArea), String> {
+            if self.open.fetch_add(1, Ordering::SeqCst) > 0 {
+                self.open.fetch_sub(1, Ordering::SeqCst);
+                return Err(String::from("Device is already open"));
+            }
+
+            let (device, roi) = DemoImagerDriver::new().connect_device(descriptor, roi_request)?;
+            Ok((Box::new(HungDevice { device, open: self.open.clone() }), roi))
+        }
+    }
+
+    impl ImagerDevice for HungDevice {
+        fn read_properties(&mut self) -> Result<ImagerProperties, String> {
+            self.device.read_properties()
+        }
+
+        fn close(&mut self) {
+            self.open.fetch_sub(1, Ordering::SeqCst);
+            self.device.close()
+        }
+
+        fn start_exposure(&mut self, params: &ExposureParams) -> Result<(), String> {
+            self.device.start_exposure(params)
+        }
+
+        fn image_ready(&mut self) -> Result<bool, String> {
+            Ok(false)
+        }
+
+        fn download_image(
+            &mut self,
+            params: &mut ExposureParams,
+        ) -> Result<DynamicSerialImage, String> {
+            self.device.download_image(params)
+        }
+
+        fn set_temperature(&mut self, request: TemperatureRequest) -> Result<(), String> {
+            self.device.set_temperature(request)
+        }
+
+        fn update_opt_config(&mut self, config: OptConfigCmd) {
+            self.device.update_opt_config(config)
+        }
+
+        fn cancel_capture(&mut self) -> Result<(), String> {
+            self.device.cancel_capture()
+        }
+    }
+
+    #[test]
+    fn hung_camera_is_closed_and_reconnected() {
+        let open = Arc::new(AtomicUsize::new(0));
+        let config = ServiceConfig {
+            watchdog: WatchdogConfig { readout_margin: Duration::ZERO, max_retries: 1 },
+            ..Default::default()
+        };
+
+        let (process_tx, _process_rx) = channel();
+        let (storage_tx, _storage_rx) = storage_channel(&StorageQueueConfig::default());
+        let (report_tx, report_rx) = channel();
+        let driver = Box::new(HungDriver { open: open.clone() });
+        let mut acquisition =
+            Acquisition::new(driver, Arc::new(config), process_tx, storage_tx, report_tx);
+
+        assert_eq!(tick(&mut acquisition), State::Connected);
+        let params = CameraParams { time: 0.0, autoexp: false, ..CameraParams::new() };
+        acquisition.process(AcquisitionCommand::UpdateCameraParams(params));
+        acquisition.process(AcquisitionCommand::Exposure(ExposureCommand::Start));
+
+        // Timeout and retry, timeout and reconnect
+        assert_eq!(tick(&mut acquisition), State::Connected);
+        assert_eq!(tick(&mut acquisition), State::Error);
+        assert_eq!(open.load(Ordering::SeqCst), 0);
+        assert_eq!(tick(&mut acquisition), State::Connected);
+        assert_eq!(open.load(Ordering::SeqCst), 1);
+
+        let events: Vec<WatchdogEvent> = report_rx
+            .try_iter()
+            .filter_map(|report| match report {
+                AcquisitionReport::Watchdog(event) => Some(event),
+                _ => None,
+            })
+            .collect();
+
+        use WatchdogEvent::*;
+        assert_eq!(events, vec![Timeout, Retry, Timeout, Reconnect]);
+    }
+
+    /// Periodic tasks after the exposure deadline, returns the new camera state
+    fn tick(acquisition: &mut Acquisition) -> State {
+        std::thread::sleep(Duration::from_millis(1));
+        acquisition.last_periodic = None;
+        acquisition.periodic();
+        acquisition.state
+    }
+}
@@ -1,12 +1,15 @@
 use std::sync::{Arc, mpsc::Sender};
 
 use ccdi_common::{
-    CameraParams, ClientMessage, ConnectionState, ExposureCommand, ImageParams, OptExposureConfig, ProcessMessage, StorageMessage
+    CameraParams, ClientMessage, ExposureCommand, ExposureStatus, ImageParams, OptExposureConfig,
+    ProcessMessage
 };
 use ccdi_imager_interface::{ImagerDevice, ImagerProperties, TemperatureRequest};
 use ccdi_common::ImgSize;
 
-use super::{properties::PropertiesController, exposure::ExposureController};
+use crate::{StorageSender, WatchdogConfig};
+
+use super::{properties::PropertiesController, exposure::{ExposureController, WatchdogEvent}};
 
 // ============================================ PUBLIC =============================================
 
@@ -20,16 +23,24 @@ pub struct ConnectedCameraController {
 
 impl ConnectedCameraController {
     pub fn new(
+        instrument: String,
         mut device: Box<dyn ImagerDevice>,
         render_size: ImgSize,
         process_tx: Sender<ProcessMessage>,
-        storage_tx: Sender<StorageMessage>,
+        storage_tx: StorageSender,
         opt: OptExposureConfig,
+        watchdog: WatchdogConfig,
     ) -> Result<Self, String> {
         let properties = PropertiesController::new(device.as_mut())?;
 
         let exposure = ExposureController::new(
-            render_size, properties.get_properties().basic, process_tx, storage_tx, opt
+            instrument,
+            render_size,
+            properties.get_properties().basic,
+            process_tx,
+            storage_tx,
+            opt,
+            watchdog,
         );
 
         let last_temperature_set = None;
@@ -81,11 +92,16 @@ impl ConnectedCameraController {
 
     }
 
-    pub fn exposure_status(&self) -> ConnectionState {
-        match self.exposure.exposure_active() {
-            false => ConnectionState::Disconnected,
-            true => ConnectionState::Established
-        }
+    pub fn flush_watchdog_events(&mut self) -> Vec<WatchdogEvent> {
+        self.exposure.flush_watchdog_events()
+    }
+
+    pub fn exposure_status(&self) -> ExposureStatus {
+        self.exposure.exposure_status()
+    }
+
+    pub fn exposure_active(&self) -> bool {
+        self.exposure.exposure_active()
     }
 
     pub fn update_trigger_status(&mut self, value: bool) {
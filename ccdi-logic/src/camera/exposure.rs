@@ -1,38 +1,59 @@
 use std::{
-    mem::swap,
     sync::{mpsc::Sender, Arc},
+    time::{Duration, Instant, SystemTime},
 };
 
 use ccdi_common::{
-    log_err, CameraParams, ClientMessage, ConvertRawImage, ExposureCommand, ImageParams,
-    OptConfigCmd, OptExposureConfig, ProcessMessage, RawImage, StorageMessage,
+    log_err, CameraParams, ClientMessage, ConvertRawImage, ExposureCommand, ExposureState,
+    ExposureStatus, FrameContext, ImageParams, OptConfigCmd, OptExposureConfig, ProcessMessage,
+    RawImage, StorageMessage,
 };
 use ccdi_imager_interface::{BasicProperties, ExposureArea, ExposureParams, ImagerDevice};
-use log::debug;
+use log::{debug, warn};
 use ccdi_common::ImgSize;
 
+use crate::{StorageSender, WatchdogConfig};
+
+use super::lifecycle::ExposureLifecycle;
+
 // ============================================ PUBLIC =============================================
 
 pub struct ExposureController {
+    instrument: String,
     properties: BasicProperties,
     image_params: ImageParams,
     camera_params: CameraParams,
-    current_exposure: Option<ExposureParams>,
+    current_exposure: Option<(ExposureParams, FrameContext)>,
+    lifecycle: ExposureLifecycle,
     process_tx: Sender<ProcessMessage>,
-    storage_tx: Sender<StorageMessage>,
+    storage_tx: StorageSender,
     trigger_active: bool,
     save_active: bool,
+    exposure_started: Option<Instant>,
+    watchdog: WatchdogConfig,
+    watchdog_retries: usize,
+    watchdog_events: Vec<WatchdogEvent>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum WatchdogEvent {
+    Timeout,
+    Retry,
+    Reconnect,
 }
 
 impl ExposureController {
     pub fn new(
+        instrument: String,
         render_size: ImgSize,
         properties: BasicProperties,
         process_tx: Sender<ProcessMessage>,
-        storage_tx: Sender<StorageMessage>,
+        storage_tx: StorageSender,
         opt: OptExposureConfig,
+        watchdog: WatchdogConfig,
     ) -> Self {
         Self {
+            instrument,
             properties,
             image_params: ImageParams::new(
                 render_size,
@@ -46,10 +67,15 @@ impl ExposureController {
             ),
             camera_params: CameraParams::new(),
             current_exposure: None,
+            lifecycle: ExposureLifecycle::new(),
             process_tx,
             storage_tx,
             trigger_active: false,
             save_active: false,
+            exposure_started: None,
+            watchdog,
+            watchdog_retries: 0,
+            watchdog_events: Vec::new(),
         }
     }
 
@@ -57,24 +83,25 @@ impl ExposureController {
         &mut self,
         device: &mut dyn ImagerDevice,
     ) -> Result<Vec<ClientMessage>, String> {
-        if self.current_exposure.is_some() && device.image_ready()? {
-            debug!("Image ready to download");
-            let mut exposure = None;
-            swap(&mut exposure, &mut self.current_exposure);
-
-            if let Some(mut params) = exposure {
-                let data = device.download_image(&mut params)?;
-                let raw_image = RawImage { params, data };
-                debug!("Image downloaded");
-                self.call_process_message(Arc::new(raw_image));
-            }
-        }
+        self.check_watchdog(device)?;
 
+        let image = match self.lifecycle.is_active() {
+            true => self.update_exposure(device)?,
+            false => None,
+        };
+
+        // Frame being read out is still queued, the queue may exceed its capacity by one
         if !self.exposure_active()
             && self.camera_params.loop_enabled
             && (self.trigger_active || !self.camera_params.trigger_required)
+            && !self.storage_tx.blocks_exposures()
         {
-            self.start_exposure(device)?;
+            self.start_exposure(device, "Exposure loop")?;
+        }
+
+        // Next exposure is already running, hand over the image to keep the dead time short
+        if let Some(image) = image {
+            self.call_process_message(image);
         }
 
         Ok(vec![])
@@ -94,22 +121,30 @@ impl ExposureController {
         command: ExposureCommand,
     ) -> Result<(), String> {
         match command {
-            ExposureCommand::Start => self.start_exposure(device)?,
+            ExposureCommand::Start => self.start_exposure(device, "Started by user")?,
             ExposureCommand::Update(params) => self.update_exposure_conf(device, params)?,
-            ExposureCommand::Cancel => {
-                device.cancel_capture()?;
-            }
+            ExposureCommand::Cancel => self.cancel_exposure(device)?,
         };
         Ok(())
     }
 
     pub fn exposure_active(&self) -> bool {
-        self.current_exposure.is_some()
+        self.lifecycle.is_active()
+    }
+
+    pub fn exposure_status(&self) -> ExposureStatus {
+        self.lifecycle.status().clone()
     }
 
     pub fn update_trigger_status(&mut self, value: bool) {
         self.trigger_active = value;
     }
+
+    pub fn flush_watchdog_events(&mut self) -> Vec<WatchdogEvent> {
+        let mut result = Vec::new();
+        result.append(&mut self.watchdog_events);
+        result
+    }
 }
 
 // =========================================== PRIVATE =============================================
@@ -127,21 +162,142 @@ impl ExposureController {
         log_err("Self process message", self.process_tx.send(message));
     }
 
-    fn start_exposure(&mut self, device: &mut dyn ImagerDevice) -> Result<(), String> {
+    fn start_exposure(
+        &mut self,
+        device: &mut dyn ImagerDevice,
+        reason: &str,
+    ) -> Result<(), String> {
         debug!("Starting exposure");
-        if self.current_exposure.is_some() {
+        if self.lifecycle.is_active() {
             return Err("Exposure already in progress.".to_string());
         }
 
         let params = self.make_exposure_description();
-        let result = device.start_exposure(&params);
 
-        if result.is_ok() {
-            self.current_exposure = Some(params)
+        if let Err(error) = device.start_exposure(&params) {
+            return self.fail(&format!("Start exposure failed: {}", error));
         }
 
+        let context = FrameContext {
+            instrument: self.instrument.clone(),
+            start: SystemTime::now(),
+            target_temperature: self.camera_params.temperature,
+            heating_pwm: self.camera_params.heating_pwm,
+        };
+
+        self.current_exposure = Some((params, context));
+        self.exposure_started = Some(Instant::now());
+        self.lifecycle.transition(ExposureState::Exposing, reason)?;
+
         debug!("Exposure started");
-        result
+        Ok(())
+    }
+
+    /// Advance running exposure: detect end of exposure time, download a ready image
+    fn update_exposure(
+        &mut self,
+        device: &mut dyn ImagerDevice,
+    ) -> Result<Option<Arc<RawImage>>, String> {
+        if self.lifecycle.state() == ExposureState::Exposing && self.exposure_time_elapsed() {
+            self.lifecycle.transition(ExposureState::Reading, "Exposure time elapsed")?;
+        }
+
+        match device.image_ready() {
+            Err(error) => {
+                return self.fail(&format!("Image ready check failed: {}", error)).map(|_| None)
+            }
+            Ok(false) => return Ok(None),
+            Ok(true) => {}
+        }
+
+        debug!("Image ready to download");
+        self.lifecycle.transition(ExposureState::Downloading, "Image ready")?;
+        self.watchdog_retries = 0;
+        self.exposure_started = None;
+
+        let (mut params, context) = match self.current_exposure.take() {
+            None => return self.fail("Exposure parameters missing").map(|_| None),
+            Some(exposure) => exposure,
+        };
+
+        match device.download_image(&mut params) {
+            Err(error) => self.fail(&format!("Download failed: {}", error)).map(|_| None),
+            Ok(data) => {
+                debug!("Image downloaded");
+                self.lifecycle.transition(ExposureState::Idle, "Image downloaded")?;
+                Ok(Some(Arc::new(RawImage { params, data, context })))
+            }
+        }
+    }
+
+    fn cancel_exposure(&mut self, device: &mut dyn ImagerDevice) -> Result<(), String> {
+        if !self.lifecycle.is_active() {
+            return Ok(());
+        }
+
+        if let Err(error) = device.cancel_capture() {
+            return self.fail(&format!("Cancel failed: {}", error));
+        }
+
+        self.current_exposure = None;
+        self.exposure_started = None;
+        self.lifecycle.transition(ExposureState::Cancelled, "Cancelled by user")
+    }
+
+    /// Drop current exposure, mark it as failed and return the reason as error
+    fn fail(&mut self, reason: &str) -> Result<(), String> {
+        self.current_exposure = None;
+        self.exposure_started = None;
+        let _ = self.lifecycle.transition(ExposureState::Failed, reason);
+        Err(reason.to_owned())
+    }
+
+    fn exposure_time_elapsed(&self) -> bool {
+        match (self.exposure_started, self.current_exposure.as_ref()) {
+            (Some(started), Some((params, _))) => {
+                started.elapsed() >= Duration::from_secs_f64(params.time.max(0.0))
+            }
+            _ => false,
+        }
+    }
+
+    /// Cancel exposure which was not read out in time and retry it, reconnect the camera by
+    /// returning an error when retries did not help.
+    fn check_watchdog(&mut self, device: &mut dyn ImagerDevice) -> Result<(), String> {
+        let (started, deadline) = match (self.exposure_started, self.readout_deadline()) {
+            (Some(started), Some(deadline)) => (started, deadline),
+            _ => return Ok(()),
+        };
+
+        if started.elapsed() <= deadline {
+            return Ok(());
+        }
+
+        warn!("Exposure not read out within {:?}, cancelling", deadline);
+        self.watchdog_events.push(WatchdogEvent::Timeout);
+        let _ = device.cancel_capture();
+        let _ = self.fail("Readout timeout");
+
+        if self.watchdog_retries >= self.watchdog.max_retries {
+            self.watchdog_retries = 0;
+            self.watchdog_events.push(WatchdogEvent::Reconnect);
+            return Err(String::from("Exposure watchdog timeout, reconnecting camera"));
+        }
+
+        self.watchdog_retries += 1;
+        self.watchdog_events.push(WatchdogEvent::Retry);
+        self.start_exposure(device, "Watchdog retry")
+    }
+
+    fn readout_deadline(&self) -> Option<Duration> {
+        self.current_exposure.as_ref().map(|(params, _)| {
+            let time = match params.autoexp {
+                false => params.time,
+                true => params.time.max(self.image_params.max_exp as f64),
+            };
+
+            Duration::from_secs_f64(time.max(0.0)) + self.watchdog.readout_margin
+        })
     }
 
     fn update_exposure_conf(
@@ -154,22 +310,25 @@ impl ExposureController {
     }
 
     fn make_exposure_description(&mut self) -> ExposureParams {
-        let mut x = self.image_params.x.min((self.properties.width - 1) as u16) as usize;
-        let mut y = self.image_params.y.min((self.properties.height - 1) as u16) as usize;
-        let mut w = self.image_params.w.min(self.properties.width as u16) as usize;
-        let mut h = self.image_params.h.min(self.properties.height as u16) as usize;
+        let mut w = self.image_params.w as usize;
+        let mut h = self.image_params.h as usize;
         if w == 0 {
             w = self.properties.width;
         }
         if h == 0 {
             h = self.properties.height;
         }
-        if x + w > self.properties.width {
-            x = 0;
-        }
-        if y + h > self.properties.height {
-            y = 0;
-        }
+
+        // Area is validated by the camera controller, snapping only guards the readout
+        let requested = ExposureArea {
+            x: self.image_params.x as usize,
+            y: self.image_params.y as usize,
+            width: w,
+            height: h,
+        };
+        let (width, height) = (self.properties.width, self.properties.height);
+        let area = self.properties.roi_rules.snap(&requested, width, height);
+        let (x, y, w, h) = area.into_tuple();
 
         self.image_params.x = x as u16;
         self.image_params.y = y as u16;
@@ -179,12 +338,7 @@ impl ExposureController {
         ExposureParams {
             gain: self.camera_params.gain,
             time: self.camera_params.time,
-            area: ExposureArea {
-                x,
-                y,
-                width: w,
-                height: h,
-            },
+            area,
             autoexp: self.camera_params.autoexp,
             flipx: self.image_params.flipx,
             flipy: self.image_params.flipy,
@@ -195,3 +349,155 @@ impl ExposureController {
         }
     }
 }
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ccdi_imager_demo::DemoImagerDriver;
+    use ccdi_imager_interface::ImagerDriver;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::time::Duration;
+
+    use crate::{storage_channel, QueuePolicy, StorageQueueConfig, StorageReceiver};
+
+    struct TestSetup {
+        controller: ExposureController,
+        device: Box<dyn ImagerDevice>,
+        process_rx: Receiver<ProcessMessage>,
+        storage_rx: StorageReceiver,
+    }
+
+    fn setup() -> TestSetup {
+        setup_with_queue(StorageQueueConfig::default())
+    }
+
+    fn setup_with_queue(queue: StorageQueueConfig) -> TestSetup {
+        let mut driver = DemoImagerDriver::new();
+        let descriptor = driver.list_devices().unwrap().remove(0);
+        let area = ExposureArea { x: 0, y: 0, width: 0, height: 0 };
+        let (mut device, _) = driver.connect_device(&descriptor, &area).unwrap();
+        let properties = device.read_properties().unwrap().basic;
+        let (process_tx, process_rx) = channel();
+        let (storage_tx, storage_rx) = storage_channel(&queue);
+
+        let mut controller = ExposureController::new(
+            descriptor.name,
+            ImgSize::new(64, 64),
+            properties,
+            process_tx,
+            storage_tx,
+            OptExposureConfig::default(),
+            WatchdogConfig::default(),
+        );
+
+        controller.update_image_params(ImageParams { w: 64, h: 64, ..Default::default() });
+
+        let mut camera_params = CameraParams::new();
+        camera_params.time = 0.0;
+        controller.update_camera_params(camera_params);
+
+        TestSetup { controller, device, process_rx, storage_rx }
+    }
+
+    #[test]
+    fn exposure_is_downloaded() {
+        let mut test = setup();
+        let device = test.device.as_mut();
+
+        test.controller.exposure_command(device, ExposureCommand::Start).unwrap();
+        assert_eq!(test.controller.exposure_status().state, ExposureState::Exposing);
+        assert!(test.controller.exposure_active());
+
+        test.controller.periodic(device).unwrap();
+        let status = test.controller.exposure_status();
+        assert_eq!(status.state, ExposureState::Idle);
+        assert_eq!(status.reason, "Image downloaded");
+        assert!(!test.controller.exposure_active());
+        assert!(matches!(test.storage_rx.recv_timeout(Duration::ZERO), Ok(StorageMessage::ProcessImage(_))));
+        assert!(matches!(test.process_rx.try_recv(), Ok(ProcessMessage::ConvertRawImage(_))));
+    }
+
+    #[test]
+    fn cancel_ends_exposure() {
+        let mut test = setup();
+        let device = test.device.as_mut();
+
+        test.controller.exposure_command(device, ExposureCommand::Start).unwrap();
+        test.controller.exposure_command(device, ExposureCommand::Cancel).unwrap();
+        assert_eq!(test.controller.exposure_status().state, ExposureState::Cancelled);
+        assert!(!test.controller.exposure_active());
+
+        test.controller.periodic(device).unwrap();
+        assert_eq!(test.controller.exposure_status().state, ExposureState::Cancelled);
+        assert!(test.storage_rx.recv_timeout(Duration::ZERO).is_err());
+
+        test.controller.exposure_command(device, ExposureCommand::Start).unwrap();
+        assert_eq!(test.controller.exposure_status().state, ExposureState::Exposing);
+    }
+
+    #[test]
+    fn cancel_without_exposure_does_nothing() {
+        let mut test = setup();
+        let device = test.device.as_mut();
+
+        test.controller.exposure_command(device, ExposureCommand::Cancel).unwrap();
+        assert_eq!(test.controller.exposure_status().state, ExposureState::Idle);
+    }
+
+    #[test]
+    fn second_start_is_rejected() {
+        let mut test = setup();
+        let device = test.device.as_mut();
+
+        test.controller.exposure_command(device, ExposureCommand::Start).unwrap();
+        assert!(test.controller.exposure_command(device, ExposureCommand::Start).is_err());
+        assert_eq!(test.controller.exposure_status().state, ExposureState::Exposing);
+    }
+
+    #[test]
+    fn loop_starts_next_exposure() {
+        let mut test = setup();
+        let device = test.device.as_mut();
+
+        let mut camera_params = CameraParams::new();
+        camera_params.time = 0.0;
+        camera_params.loop_enabled = true;
+        test.controller.update_camera_params(camera_params);
+
+        test.controller.periodic(device).unwrap();
+        assert_eq!(test.controller.exposure_status().state, ExposureState::Exposing);
+
+        test.controller.periodic(device).unwrap();
+        let status = test.controller.exposure_status();
+        assert_eq!(status.state, ExposureState::Exposing);
+        assert_eq!(status.reason, "Exposure loop");
+        assert!(test.storage_rx.recv_timeout(Duration::ZERO).is_ok());
+    }
+
+    #[test]
+    fn full_queue_pauses_loop() {
+        let queue = StorageQueueConfig { capacity: 1, policy: QueuePolicy::Block };
+        let mut test = setup_with_queue(queue);
+        let device = test.device.as_mut();
+
+        let mut camera_params = CameraParams::new();
+        camera_params.time = 0.0;
+        camera_params.loop_enabled = true;
+        test.controller.update_camera_params(camera_params);
+
+        test.controller.periodic(device).unwrap();
+        test.controller.periodic(device).unwrap();
+        assert!(test.controller.exposure_active());
+        test.controller.periodic(device).unwrap();
+        assert!(!test.controller.exposure_active());
+
+        test.storage_rx.recv_timeout(Duration::ZERO).unwrap();
+        test.controller.periodic(device).unwrap();
+        assert!(!test.controller.exposure_active());
+        test.storage_rx.recv_timeout(Duration::ZERO).unwrap();
+        test.controller.periodic(device).unwrap();
+        assert!(test.controller.exposure_active());
+    }
+}
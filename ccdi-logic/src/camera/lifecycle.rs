@@ -0,0 +1,96 @@
+use ccdi_common::{ExposureState, ExposureStatus};
+use log::{debug, warn};
+
+// ============================================ PUBLIC =============================================
+
+/// Tracks the exposure state and allows only valid transitions between states
+pub struct ExposureLifecycle {
+    status: ExposureStatus,
+}
+
+impl ExposureLifecycle {
+    pub fn new() -> Self {
+        Self {
+            status: ExposureStatus {
+                state: ExposureState::Idle,
+                reason: String::from("Camera connected"),
+            },
+        }
+    }
+
+    pub fn transition(&mut self, state: ExposureState, reason: &str) -> Result<(), String> {
+        let current = self.status.state;
+
+        if !transition_allowed(current, state) {
+            warn!("Invalid exposure transition {:?} -> {:?} ({})", current, state, reason);
+            return Err(format!("Invalid exposure transition {:?} -> {:?}", current, state));
+        }
+
+        debug!("Exposure {:?} -> {:?}: {}", current, state, reason);
+        self.status = ExposureStatus { state, reason: reason.to_owned() };
+        Ok(())
+    }
+
+    pub fn state(&self) -> ExposureState {
+        self.status.state
+    }
+
+    pub fn status(&self) -> &ExposureStatus {
+        &self.status
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status.state.is_active()
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+fn transition_allowed(from: ExposureState, to: ExposureState) -> bool {
+    use ExposureState::*;
+
+    matches!(
+        (from, to),
+        (Idle | Cancelled | Failed, Exposing)
+            | (Exposing, Reading | Downloading)
+            | (Reading, Downloading)
+            | (Exposing | Reading, Cancelled)
+            | (Downloading, Idle)
+            | (_, Failed)
+    )
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ExposureState::*;
+
+    #[test]
+    fn valid_transitions() {
+        let mut lifecycle = ExposureLifecycle::new();
+        assert_eq!(lifecycle.state(), Idle);
+        assert!(lifecycle.transition(Exposing, "start").is_ok());
+        assert!(lifecycle.is_active());
+        assert!(lifecycle.transition(Reading, "time elapsed").is_ok());
+        assert!(lifecycle.transition(Downloading, "ready").is_ok());
+        assert!(lifecycle.transition(Idle, "done").is_ok());
+        assert!(!lifecycle.is_active());
+        assert_eq!(lifecycle.status().reason, "done");
+    }
+
+    #[test]
+    fn invalid_transitions_are_rejected() {
+        let mut lifecycle = ExposureLifecycle::new();
+        assert!(lifecycle.transition(Downloading, "ready").is_err());
+        assert!(lifecycle.transition(Cancelled, "cancel").is_err());
+        assert_eq!(lifecycle.state(), Idle);
+
+        lifecycle.transition(Exposing, "start").unwrap();
+        assert!(lifecycle.transition(Exposing, "start again").is_err());
+        lifecycle.transition(Downloading, "ready").unwrap();
+        assert!(lifecycle.transition(Cancelled, "cancel").is_err());
+        assert_eq!(lifecycle.status().reason, "ready");
+    }
+}
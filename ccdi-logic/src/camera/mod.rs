@@ -1,109 +1,193 @@
+mod acquisition;
 mod command;
 mod connected;
 mod exposure;
+mod lifecycle;
+mod presets;
 mod properties;
-
-use std::sync::{mpsc::Sender, Arc};
+mod shutdown;
+mod validation;
+
+use std::{
+    collections::HashMap,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
 
 use ccdi_common::{
-    CameraParamMessage, CameraParams, ClientMessage, ConnectionState, ExposureCommand,
-    ImageParamMessage, ImageParams, IoMessage, LogicStatus, OptExposureConfig, ProcessMessage,
-    StorageDetail, StorageMessage, StorageState, ViewState,
+    log_err, CameraParamMessage, CameraParams, ClientMessage, ConnectionState, Event,
+    EventSource, ExposureCommand, ImageParamMessage, ImageParams, IoMessage, LogicStatus,
+    PowerState, PowerStatus, PresetMessage, ProcessMessage, Severity, ShutdownStage, StateMessage,
+    StatusMode, StorageDetail, StorageLogRecord, StorageLogStatus, StorageMessage, StorageSettings,
+    StorageState, StorageTarget, ViewState, WatchdogStats,
 };
-use ccdi_imager_interface::{DeviceDescriptor, ExposureArea, ImagerDriver};
-use log::{debug, info};
+use ccdi_imager_interface::ExposureArea;
+use log::{debug, error, info, warn};
 
-use crate::ServiceConfig;
+use crate::{
+    events::EventLog, host::HostMonitor, load_presets, load_runtime_state, save_presets,
+    save_runtime_state, supervisor::WorkerMonitor, RuntimeState, ServiceConfig, SharedConfig,
+    StorageSender,
+};
 
-use self::{command::execute_command, connected::ConnectedCameraController};
+use self::{
+    acquisition::{
+        start_acquisition_thread, AcquisitionCommand, AcquisitionReport, AcquisitionSnapshot,
+    },
+    command::execute_command,
+    exposure::WatchdogEvent,
+    presets::PresetList,
+    shutdown::ShutdownSequence,
+    validation::{validate_camera_param, validate_image_param, validate_params, ParamLimits},
+};
 
 // ============================================ PUBLIC =============================================
 
+/// Keeps the camera state for the clients, camera itself is driven by the acquisition thread
 pub struct CameraController {
-    driver: Box<dyn ImagerDriver>,
-    state: State,
     detail: String,
-    connected: Option<ConnectedCameraController>,
     view: Option<ViewState>,
     image_params: ImageParams,
     camera_params: CameraParams,
-    process_tx: Sender<ProcessMessage>,
-    storage_tx: Sender<StorageMessage>,
+    storage_tx: StorageSender,
     storage_status: StorageState,
     config: Arc<ServiceConfig>,
     trigger_active: bool,
     storage_detail: StorageDetail,
-    turnning_off: bool,
-    optconfig: OptExposureConfig,
+    finished: bool,
+    host: HostMonitor,
+    throttle_warning: bool,
+    power_status: PowerStatus,
+    shutdown: Option<ShutdownSequence>,
+    watchdog: WatchdogStats,
+    acquisition_tx: Sender<AcquisitionCommand>,
+    acquisition_rx: Receiver<AcquisitionReport>,
+    acquisition: Option<JoinHandle<()>>,
+    snapshot: AcquisitionSnapshot,
+    workers: WorkerMonitor,
+    storage_settings: StorageSettings,
+    saved_state: Option<RuntimeState>,
+    /// ROI restored or set while disconnected is kept instead of the ROI reported on connection
+    roi_pending: bool,
+    /// Parameters restored or set while disconnected wait for the camera limits to be validated
+    params_unchecked: bool,
+    pending_io: Vec<IoMessage>,
+    presets: PresetList,
+    shared_config: SharedConfig,
+    config_version: usize,
+    events: EventLog,
+    /// Restart counts of workers already reported as events
+    worker_restarts: HashMap<String, usize>,
+    /// Dropped frames already reported and the time of the last report
+    dropped_frames: (usize, Option<Instant>),
 }
 
 impl CameraController {
     pub fn new(
-        driver: Box<dyn ImagerDriver>,
+        demo_mode: &str,
         process_tx: Sender<ProcessMessage>,
-        storage_tx: Sender<StorageMessage>,
-        config: Arc<ServiceConfig>,
-    ) -> Self {
-        let optconfig = config.exp.clone();
-        Self {
-            driver,
-            state: State::Error,
-            connected: None,
+        storage_tx: StorageSender,
+        shared_config: SharedConfig,
+        workers: WorkerMonitor,
+    ) -> Result<Self, String> {
+        let config_version = shared_config.version();
+        let config = shared_config.get();
+        let (acquisition_tx, command_rx) = channel();
+        let (report_tx, acquisition_rx) = channel();
+
+        let acquisition = start_acquisition_thread(
+            demo_mode.to_owned(),
+            shared_config.clone(),
+            process_tx,
+            storage_tx.clone(),
+            command_rx,
+            report_tx,
+            workers.clone(),
+        )?;
+
+        let mut controller = Self {
             detail: String::from("Started"),
             view: None,
-            image_params: ImageParams::new(
-                config.render_size,
-                ExposureArea {
-                    x: config.roi.x,
-                    y: config.roi.y,
-                    width: config.roi.width,
-                    height: config.roi.height,
-                },
-                optconfig.clone(),
-            ),
+            image_params: initial_image_params(&config),
             camera_params: CameraParams::new(),
-            process_tx,
             storage_tx,
             storage_status: StorageState::Unknown,
-            config,
             trigger_active: false,
             storage_detail: Default::default(),
-            turnning_off: false,
-            optconfig,
-        }
+            finished: false,
+            host: HostMonitor::new(),
+            throttle_warning: false,
+            power_status: Default::default(),
+            shutdown: None,
+            watchdog: Default::default(),
+            acquisition_tx,
+            acquisition_rx,
+            acquisition: Some(acquisition),
+            snapshot: Default::default(),
+            workers,
+            storage_settings: StorageSettings {
+                storage_name: String::from("default"),
+                cadence: config.savecadence,
+                counter: 0,
+                storage_enabled: false,
+                formats: config.formats.clone(),
+                compression: Some(config.fits.compression),
+                target: Default::default(),
+            },
+            saved_state: None,
+            roi_pending: false,
+            params_unchecked: false,
+            pending_io: Vec::new(),
+            presets: PresetList::new(log_err("Load presets", load_presets()).unwrap_or_default()),
+            events: EventLog::new(config.events.clone()),
+            worker_restarts: HashMap::new(),
+            dropped_frames: (0, None),
+            config,
+            shared_config,
+            config_version,
+        };
+
+        controller.restore_runtime_state();
+        Ok(controller)
     }
 
     pub fn periodic(&mut self) -> (Vec<ClientMessage>, Vec<IoMessage>) {
-        if self.turnning_off {
-            return (vec![], vec![]);
-        }
+        let mut messages = self.receive_reports();
+        self.apply_config_changes();
 
-        let old_state = self.state;
-
-        self.state = match self.state {
-            State::Error => self.handle_error_state(),
-            State::Connected => self.handle_connected_state(),
-        };
+        if self.host.periodic() {
+            self.check_throttling();
+        }
 
-        if self.state != old_state {
-            info!("Camera state {:?} -> {:?}", old_state, self.state);
+        if self.shutdown.is_some() {
+            self.handle_shutdown();
         }
 
-        let new_view = self.get_view();
+        self.check_workers();
 
-        let mut messages = vec![];
+        // Acquisition runs asynchronously, push the view whenever its state changes
+        let new_view = self.get_view();
 
-        if let Some(sview) = self.view.as_ref() {
-            if sview != &new_view {
-                messages.push(ClientMessage::View(Box::new(new_view)));
-            }
+        if self.view.as_ref() != Some(&new_view) {
+            self.view = Some(new_view.clone());
+            messages.push(ClientMessage::View(Box::new(new_view)));
         }
 
-        if let Some(ref mut camera) = self.connected {
-            messages.append(&mut camera.flush_messages());
+        self.save_runtime_state();
+
+        let events = self.events.take_new();
+
+        if !events.is_empty() {
+            messages.push(ClientMessage::Events(events));
         }
 
-        let states = vec![IoMessage::SetExposureActive(self.exposure_active())];
+        let mut states = std::mem::take(&mut self.pending_io);
+        states.push(IoMessage::SetExposureActive(self.exposure_active()));
+        states.push(IoMessage::SetStatus(self.status_mode()));
 
         (messages, states)
     }
@@ -118,26 +202,28 @@ impl CameraController {
             detail: self.detail.clone(),
             status: LogicStatus {
                 camera: self.connection_state(),
-                exposure: self
-                    .connected
-                    .as_ref()
-                    .map(|cam| cam.exposure_status())
-                    .unwrap_or(ConnectionState::Disconnected),
+                exposure: self.snapshot.exposure.clone(),
                 storage: self.storage_status.clone(),
                 trigger: into_state(self.trigger_active),
                 required: into_state(self.camera_params.trigger_required),
                 save: into_state(self.storage_detail.storage_enabled),
                 loop_enabled: into_state(self.camera_params.loop_enabled),
+                shutdown: self.shutdown.as_ref().map(|shutdown| shutdown.stage()),
             },
-            camera_properties: self.connected.as_ref().map(|cam| cam.get_properties()),
+            camera_properties: self.snapshot.properties.clone(),
             image_params: self.image_params.clone(),
             camera_params: self.camera_params.clone(),
             config: self.config.gui.clone(),
             storage_detail: self.storage_detail.clone(),
+            host: self.host.status().clone(),
+            power: self.power_status.clone(),
+            watchdog: self.watchdog.clone(),
+            workers: self.workers.statuses(),
+            presets: self.presets.presets().to_vec(),
         }
     }
 
-    pub fn update_image_params(&mut self, message: ImageParamMessage) {
+    pub fn update_image_params(&mut self, message: ImageParamMessage) -> Result<(), String> {
         match message {
             ImageParamMessage::SetRoi((x, y, w, h)) => {
                 debug!("New ROI: X {} Y {}, {} x {}", x, y, w, h);
@@ -145,6 +231,7 @@ impl CameraController {
                 self.image_params.y = y;
                 self.image_params.w = w;
                 self.image_params.h = h;
+                self.roi_pending |= self.snapshot.properties.is_none();
             }
             ImageParamMessage::SetFlipX(value) => self.image_params.flipx = value,
             ImageParamMessage::SetFlipY(value) => self.image_params.flipy = value,
@@ -152,14 +239,19 @@ impl CameraController {
             ImageParamMessage::SetPixelTgt(value) => self.image_params.pixel_tgt = value,
             ImageParamMessage::SetPixelTol(value) => self.image_params.pixel_tol = value,
         }
-        if let Some(camera) = self.connected.as_mut() {
-            camera.update_image_params(self.image_params.clone());
-        }
+
+        self.params_unchecked |= self.snapshot.properties.is_none();
+        self.send_image_params();
+        Ok(())
     }
 
-    pub fn update_camera_params(&mut self, message: CameraParamMessage) {
+    pub fn update_camera_params(&mut self, message: CameraParamMessage) -> Result<(), String> {
         use CameraParamMessage::*;
 
+        if self.shutdown.is_some() {
+            return Err(String::from("Shutdown in progress - camera parameters locked"));
+        }
+
         match message {
             EnableLoop(value) => self.camera_params.loop_enabled = value,
             SetGain(gain) => self.camera_params.gain = gain,
@@ -172,149 +264,740 @@ impl CameraController {
             }
         }
 
-        if let Some(camera) = self.connected.as_mut() {
-            camera.update_camera_params(self.camera_params.clone());
+        self.params_unchecked |= self.snapshot.properties.is_none();
+        self.send_camera_params();
+        Ok(())
+    }
+
+    /// Exposure is started asynchronously, its failures are reported as events
+    pub fn exposure_command(&mut self, command: ExposureCommand) -> Result<(), String> {
+        if self.shutdown.is_some() && command != ExposureCommand::Cancel {
+            return Err(String::from("Shutdown in progress - exposures locked"));
         }
+
+        self.send_command(AcquisitionCommand::Exposure(command));
+        Ok(())
     }
 
-    pub fn exposure_command(&mut self, command: ExposureCommand) {
-        match self.connected.as_mut() {
-            None => self.set_detail("Not connected - cannot handle exposure command"),
-            Some(connected) => match connected.exposure_command(command) {
-                Ok(_) => {}
-                Err(message) => self.set_detail(&format!("Exposure command failed: {}", message)),
-            },
+    pub fn preset_command(&mut self, message: PresetMessage) -> Result<(), String> {
+        use PresetMessage::*;
+
+        if self.shutdown.is_some() {
+            return Err(String::from("Shutdown in progress - presets locked"));
         }
+
+        let result = match message {
+            Create(name) => self
+                .presets
+                .create(&name, self.camera_params.clone(), self.image_params.clone())
+                .map(|_| format!("Preset '{}' saved", name)),
+            Rename((name, new_name)) => self
+                .presets
+                .rename(&name, &new_name)
+                .map(|_| format!("Preset '{}' renamed to '{}'", name, new_name)),
+            Delete(name) => self
+                .presets
+                .delete(&name)
+                .map(|_| format!("Preset '{}' deleted", name)),
+            Apply(name) => return self.apply_preset(&name),
+        };
+
+        let message = result.map_err(|error| format!("Preset not changed: {}", error))?;
+        log_err("Save presets", save_presets(self.presets.presets()));
+        self.event(Severity::Info, EventSource::Client, &message);
+        Ok(())
     }
 
     pub fn update_storage_status(&mut self, message: StorageState) {
+        match (&self.storage_status, &message) {
+            (StorageState::Error(old), StorageState::Error(new)) if old == new => {}
+            (_, StorageState::Error(error)) => {
+                let detail = format!("Storage error: {}", error);
+                self.event(Severity::Error, EventSource::Storage, &detail)
+            }
+            (StorageState::Full(_), StorageState::Full(_)) => {}
+            (_, StorageState::Full(capacity)) => {
+                let detail = format!(
+                    "Storage full, saving paused: {:.2}G free, minimum {:.2}G",
+                    capacity.free_gigabytes, self.config.disk.min_free_gigabytes
+                );
+                self.event(Severity::Error, EventSource::Storage, &detail)
+            }
+            (StorageState::Error(_), StorageState::Available(_)) => {
+                self.event(Severity::Info, EventSource::Storage, "Storage available again")
+            }
+            (StorageState::Full(_), StorageState::Available(_)) => {
+                let detail = "Free space recovered, saving resumed";
+                self.event(Severity::Info, EventSource::Storage, detail)
+            }
+            _ => {}
+        }
+
         self.storage_status = message;
     }
 
+    pub fn retention_deleted(&mut self, records: Vec<StorageLogRecord>) {
+        for record in records {
+            match record.status {
+                StorageLogStatus::Success => {
+                    let message = format!("Retention deleted {}", record.name);
+                    self.event(Severity::Info, EventSource::Storage, &message)
+                }
+                StorageLogStatus::Error(error) => {
+                    let message = format!("Retention could not delete {}: {}", record.name, error);
+                    self.event(Severity::Warning, EventSource::Storage, &message)
+                }
+            }
+        }
+    }
+
     pub fn update_storage_detail(&mut self, detail: StorageDetail) {
+        // Storage logs one record per output format of each saved frame
+        let saved = detail.counter.saturating_sub(self.storage_detail.counter);
+        let records = saved * detail.formats.len().max(1);
+
+        for record in detail.storage_log.iter().rev().take(records).rev() {
+            if let StorageLogStatus::Error(error) = &record.status {
+                let message = format!("Image not saved: {} {}", record.name, error);
+                self.event(Severity::Error, EventSource::Storage, &message);
+            }
+        }
+
+        let (reported, last_report) = self.dropped_frames;
+        let dropped = detail.queue.dropped.saturating_sub(reported);
+        let report_due = last_report.is_none_or(|time| time.elapsed() >= DROP_REPORT_INTERVAL);
+
+        if dropped > 0 && report_due {
+            let message = format!("Storage queue full, {} frames dropped", dropped);
+            self.event(Severity::Warning, EventSource::Storage, &message);
+            self.dropped_frames = (detail.queue.dropped, Some(Instant::now()));
+        }
+
+        if detail.storage_enabled != self.storage_detail.storage_enabled {
+            self.event(
+                Severity::Info,
+                EventSource::Storage,
+                match detail.storage_enabled {
+                    true => "Saving enabled",
+                    false => "Saving disabled",
+                },
+            );
+        }
+
+        self.report_drives(&detail);
+        let was_recording = self.storage_detail.recording.as_ref().is_some_and(|rec| rec.active);
+
+        match detail.recording.as_ref() {
+            Some(recording) if recording.active && !was_recording => {
+                self.event(Severity::Info, EventSource::Storage, "Video recording started")
+            }
+            Some(recording) if !recording.active && was_recording => match &recording.error {
+                Some(error) => self.event(
+                    Severity::Error,
+                    EventSource::Storage,
+                    &format!("Video recording stopped: {}", error),
+                ),
+                None => self.event(
+                    Severity::Info,
+                    EventSource::Storage,
+                    &format!(
+                        "Video saved: {} frames, {} dropped, {}",
+                        recording.frames, recording.dropped, recording.file
+                    ),
+                ),
+            },
+            _ => {}
+        }
+
+        self.storage_settings = StorageSettings {
+            storage_name: detail.storage_name.clone(),
+            cadence: detail.cadence,
+            counter: detail.counter,
+            storage_enabled: detail.storage_enabled,
+            formats: detail.formats.clone(),
+            compression: Some(detail.compression),
+            target: detail.target.clone(),
+        };
         self.storage_detail = detail;
     }
 
+    pub fn update_power_status(&mut self, status: PowerStatus) {
+        if status.state != self.power_status.state {
+            match status.state {
+                PowerState::Low => self.event(
+                    Severity::Warning,
+                    EventSource::Io,
+                    &format!("Supply voltage is low: {:.2} V", status.voltage.unwrap_or(0.0)),
+                ),
+                PowerState::Critical => self.start_shutdown(
+                    Severity::Error,
+                    EventSource::Io,
+                    "Supply voltage critical - stopping exposures and shutting down",
+                    true,
+                ),
+                PowerState::Normal | PowerState::Unknown => {}
+            }
+        }
+
+        self.power_status = status;
+    }
+
     pub fn update_trigger_status(&mut self, value: bool) {
         self.trigger_active = value;
+        self.send_command(AcquisitionCommand::UpdateTrigger(value));
+    }
 
-        if let Some(ref mut camera) = self.connected {
-            camera.update_trigger_status(value);
-        }
+    /// Start the shutdown sequence, turn off command is executed at its end if power_off is set
+    pub fn shutdown(&mut self, power_off: bool) {
+        // Power off is requested from the client, plain shutdown comes from a signal
+        let (source, detail) = match power_off {
+            true => (EventSource::Client, "Power off requested - stopping exposures"),
+            false => (EventSource::System, "Shutting down - stopping exposures"),
+        };
+
+        self.start_shutdown(Severity::Info, source, detail, power_off);
+    }
+
+    /// Shutdown sequence finished, camera is closed and the logic thread may exit
+    pub fn finished(&self) -> bool {
+        self.finished
     }
 
-    pub fn turn_off(&mut self) {
-        if let Some(ref mut camera) = self.connected {
-            camera.turn_off();
+    /// Check parameters sent from a client against the camera limits, returns the message with
+    /// values clamped to the limits and the reason of the change
+    pub fn validate_command(
+        &self,
+        message: StateMessage,
+    ) -> Result<(StateMessage, Option<String>), String> {
+        let limits = self.param_limits();
+
+        match message {
+            StateMessage::CameraParam(message) => validate_camera_param(message, &limits)
+                .map(|(message, reason)| (StateMessage::CameraParam(message), reason)),
+            StateMessage::ImageParam(message) => validate_image_param(message, &limits)
+                .map(|(message, reason)| (StateMessage::ImageParam(message), reason)),
+            StateMessage::Preset(PresetMessage::Apply(name)) => {
+                let reason = self.presets.get(&name).ok().and_then(|preset| {
+                    let (_, _, reasons) = self.validate_stored_params(
+                        (&preset.camera_params, &preset.image_params),
+                        (&self.camera_params, &self.image_params),
+                    );
+                    (!reasons.is_empty())
+                        .then(|| format!("Preset '{}' adjusted: {}", name, reasons.join("; ")))
+                });
+
+                Ok((StateMessage::Preset(PresetMessage::Apply(name)), reason))
+            }
+            other => Ok((other, None)),
         }
+    }
+
+    /// Add event to the log, the latest event is also shown as the status detail
+    pub fn event(&mut self, severity: Severity, source: EventSource, message: &str) {
+        self.detail = message.to_owned();
+        self.events.push(severity, source, message);
+    }
 
-        self.turnning_off = true;
-        info!("Abort requested, executing abort.");
-        execute_command(&self.config.turn_off_command);
-        std::process::abort();
+    /// All events kept in the log, sent to newly connected clients
+    pub fn events(&self) -> Vec<Event> {
+        self.events.all()
     }
 }
 
 // =========================================== PRIVATE =============================================
 
 impl CameraController {
+    fn report_drives(&mut self, detail: &StorageDetail) {
+        let mounted = |detail: &StorageDetail| {
+            detail.drives.iter().map(|drive| drive.mount_point.clone()).collect::<Vec<_>>()
+        };
+
+        let (old, new) = (mounted(&self.storage_detail), mounted(detail));
+
+        for mount_point in new.iter().filter(|mount_point| !old.contains(mount_point)) {
+            let message = format!("Removable drive mounted: {}", mount_point);
+            self.event(Severity::Info, EventSource::Storage, &message);
+        }
+
+        for mount_point in old.iter().filter(|mount_point| !new.contains(mount_point)) {
+            let message = format!("Removable drive removed: {}", mount_point);
+            self.event(Severity::Info, EventSource::Storage, &message);
+        }
+
+        let target = match &detail.target {
+            StorageTarget::Internal => String::from("internal storage"),
+            StorageTarget::Removable(mount_point) => mount_point.clone(),
+        };
+
+        match (self.storage_detail.fallback, detail.fallback) {
+            (false, true) => {
+                let message = format!("Drive {} missing, saving to internal storage", target);
+                self.event(Severity::Warning, EventSource::Storage, &message)
+            }
+            (true, false) => {
+                let message = format!("Saving to {}", target);
+                self.event(Severity::Info, EventSource::Storage, &message)
+            }
+            _ => {}
+        }
+    }
+
+    /// Limits of the connected camera, only camera independent limits apply while disconnected
+    fn param_limits(&self) -> ParamLimits {
+        ParamLimits::new(
+            &self.config,
+            self.snapshot.properties.as_ref().map(|properties| &properties.basic),
+        )
+    }
+
+    fn validate_stored_params(
+        &self,
+        stored: (&CameraParams, &ImageParams),
+        fallback: (&CameraParams, &ImageParams),
+    ) -> (CameraParams, ImageParams, Vec<String>) {
+        validate_params(stored, fallback, &self.param_limits())
+    }
+
     fn exposure_active(&self) -> bool {
-        let exposure_status = self
-            .connected
-            .as_ref()
-            .map(|cam| cam.exposure_status())
-            .unwrap_or(ConnectionState::Disconnected);
+        self.snapshot.exposure.state.is_active()
+    }
 
-        matches!(exposure_status, ConnectionState::Established)
+    fn send_command(&self, command: AcquisitionCommand) {
+        log_err("Send acquisition command", self.acquisition_tx.send(command));
     }
 
-    fn connection_state(&self) -> ConnectionState {
-        match self.state {
-            State::Error => ConnectionState::Connecting,
-            State::Connected => ConnectionState::Established,
-        }
+    fn send_camera_params(&self) {
+        self.send_command(AcquisitionCommand::UpdateCameraParams(self.camera_params.clone()));
+    }
+
+    fn send_image_params(&self) {
+        self.send_command(AcquisitionCommand::UpdateImageParams(self.image_params.clone()));
     }
 
-    fn set_detail(&mut self, detail: &str) {
-        self.detail = detail.to_owned();
+    /// Process reports of the acquisition thread, returns messages to be sent to clients
+    fn receive_reports(&mut self) -> Vec<ClientMessage> {
+        let mut messages = Vec::new();
+
+        while let Ok(report) = self.acquisition_rx.try_recv() {
+            match report {
+                AcquisitionReport::Started => {
+                    self.send_camera_params();
+                    self.send_image_params();
+                    self.send_command(AcquisitionCommand::UpdateTrigger(self.trigger_active));
+                }
+                AcquisitionReport::Snapshot(snapshot) => {
+                    if snapshot.connected != self.snapshot.connected {
+                        info!("Camera connected: {}", snapshot.connected);
+                    }
+                    self.snapshot = snapshot;
+
+                    if self.params_unchecked && self.snapshot.properties.is_some() {
+                        self.validate_unchecked_params();
+                    }
+                }
+                AcquisitionReport::Detail((severity, detail)) => {
+                    self.event(severity, EventSource::Camera, &detail)
+                }
+                AcquisitionReport::Roi(_) if self.roi_pending => {
+                    // Keep ROI restored from the runtime state or set by a client
+                    self.roi_pending = false;
+                    self.send_image_params();
+                }
+                AcquisitionReport::Roi(roi) => {
+                    self.image_params.w = roi.width as u16;
+                    self.image_params.h = roi.height as u16;
+                    self.image_params.x = roi.x as u16;
+                    self.image_params.y = roi.y as u16;
+                }
+                AcquisitionReport::Watchdog(event) => self.record_watchdog_event(event),
+                AcquisitionReport::ClientMessages(mut client) => messages.append(&mut client),
+                AcquisitionReport::Closed => {
+                    error!("Acquisition thread closed unexpectedly");
+                    self.snapshot = Default::default();
+                }
+            }
+        }
+
+        messages
     }
 
-    fn handle_error_state(&mut self) -> State {
-        if let Some(old_device) = self.connected.take() {
-            old_device.close();
-            self.set_detail("Closing old device");
+    fn apply_config_changes(&mut self) {
+        if let Some(config) = self.shared_config.changed(&mut self.config_version) {
+            if config.render_size != self.config.render_size {
+                self.image_params.render_size = config.render_size;
+                self.send_image_params();
+            }
+
+            self.events.update_config(config.events.clone());
+            self.config = config;
         }
+    }
 
-        match self.driver.list_devices() {
-            Err(_) => {
-                self.set_detail("Could not list devices");
-                State::Error
+    /// Apply parameters of the preset, running exposure loop is not started or stopped. Values
+    /// are validated against the connected camera, the changes are reported by validate_command.
+    fn apply_preset(&mut self, name: &str) -> Result<(), String> {
+        let preset = self
+            .presets
+            .get(name)
+            .map_err(|error| format!("Preset not applied: {}", error))?;
+
+        let (camera_params, image_params, _) = self.validate_stored_params(
+            (&preset.camera_params, &preset.image_params),
+            (&self.camera_params, &self.image_params),
+        );
+
+        self.camera_params = CameraParams {
+            loop_enabled: self.camera_params.loop_enabled,
+            ..camera_params
+        };
+
+        self.image_params = ImageParams {
+            render_size: self.config.render_size,
+            ..image_params
+        };
+
+        self.roi_pending |= self.snapshot.properties.is_none();
+        self.params_unchecked |= self.snapshot.properties.is_none();
+        self.pending_io.push(IoMessage::SetHeating(self.camera_params.heating_pwm as f32));
+        self.send_camera_params();
+        self.send_image_params();
+        self.event(Severity::Info, EventSource::Client, &format!("Preset '{}' applied", name));
+        Ok(())
+    }
+
+    fn restore_runtime_state(&mut self) {
+        let state = match load_runtime_state() {
+            Ok(state) => state,
+            Err(error) => {
+                info!("Runtime state not restored: {}", error);
+                return;
             }
-            Ok(devices) => match devices.as_slice() {
-                [] => {
-                    self.set_detail("No devices present in list");
-                    State::Error
-                }
-                [device_id, ..] => self.connect_and_init(device_id),
+        };
+
+        self.camera_params = state.camera_params.clone();
+        self.image_params = ImageParams {
+            render_size: self.config.render_size,
+            ..state.image_params.clone()
+        };
+        self.storage_settings = state.storage.clone();
+        self.roi_pending = true;
+        self.params_unchecked = true;
+
+        if !self.config.resume_series {
+            self.camera_params.loop_enabled = false;
+            self.storage_settings.storage_enabled = false;
+        }
+
+        log_err(
+            "Restore storage settings",
+            self.storage_tx.send(StorageMessage::RestoreSettings(self.storage_settings.clone())),
+        );
+
+        self.pending_io.push(IoMessage::SetHeating(self.camera_params.heating_pwm as f32));
+        self.saved_state = Some(state);
+
+        self.event(
+            Severity::Info,
+            EventSource::System,
+            match self.camera_params.loop_enabled || self.storage_settings.storage_enabled {
+                true => "Settings restored from previous run, series resumed",
+                false => "Settings restored from previous run",
             },
+        );
+    }
+
+    /// Parameters of the previous run or set while disconnected may not fit the camera connected
+    /// now, invalid values are replaced by defaults
+    fn validate_unchecked_params(&mut self) {
+        self.params_unchecked = false;
+        let defaults = (CameraParams::new(), initial_image_params(&self.config));
+
+        let (camera_params, image_params, reasons) = self.validate_stored_params(
+            (&self.camera_params, &self.image_params),
+            (&defaults.0, &defaults.1),
+        );
+
+        if reasons.is_empty() {
+            return;
+        }
+
+        self.camera_params = camera_params;
+        self.image_params = image_params;
+        self.pending_io.push(IoMessage::SetHeating(self.camera_params.heating_pwm as f32));
+        self.send_camera_params();
+        self.send_image_params();
+
+        let message = format!("Settings adjusted to the camera: {}", reasons.join("; "));
+        self.event(Severity::Warning, EventSource::System, &message);
+    }
+
+    /// Save parameters to the state file when they changed, state is kept frozen during shutdown
+    /// so that the series can be resumed after power is restored
+    fn save_runtime_state(&mut self) {
+        if self.shutdown.is_some() {
+            return;
+        }
+
+        let state = RuntimeState {
+            camera_params: self.camera_params.clone(),
+            image_params: self.image_params.clone(),
+            storage: self.storage_settings.clone(),
+        };
+
+        if self.saved_state.as_ref() != Some(&state)
+            && log_err("Save runtime state", save_runtime_state(&state)).is_some()
+        {
+            self.saved_state = Some(state);
         }
     }
 
-    fn connect_and_init(&mut self, id: &DeviceDescriptor) -> State {
-        match self.driver.connect_device(id, &self.config.roi) {
-            Err(_) => {
-                self.set_detail("Connect device failed");
-                State::Error
+    fn status_mode(&self) -> StatusMode {
+        let storage_problem = match &self.storage_status {
+            StorageState::Unknown => false,
+            StorageState::Error(_) | StorageState::Full(_) => true,
+            StorageState::Available(capacity) => {
+                capacity.free_gigabytes < self.config.io.low_storage_gigabytes
             }
-            Ok(device) => {
-                self.set_detail("Device connected, reading basic info");
-                let (device, roi) = device;
-
-                self.image_params.w = roi.width as u16;
-                self.image_params.h = roi.height as u16;
-                self.image_params.x = roi.x as u16;
-                self.image_params.y = roi.y as u16;
-
-                match ConnectedCameraController::new(
-                    device,
-                    self.config.render_size,
-                    self.process_tx.clone(),
-                    self.storage_tx.clone(),
-                    self.optconfig.clone(),
-                ) {
-                    Ok(connected) => {
-                        self.set_detail("Camera initialized");
-                        self.connected = Some(connected);
-                        State::Connected
+        };
+
+        if self.shutdown.is_some() {
+            StatusMode::ShuttingDown
+        } else if !self.snapshot.connected {
+            StatusMode::Disconnected
+        } else if storage_problem {
+            StatusMode::StorageError
+        } else if self.camera_params.loop_enabled && self.storage_detail.storage_enabled {
+            StatusMode::Saving
+        } else if self.camera_params.loop_enabled || self.exposure_active() {
+            StatusMode::Exposing
+        } else {
+            StatusMode::Idle
+        }
+    }
+
+    fn check_throttling(&mut self) {
+        let processing = self.camera_params.loop_enabled || self.exposure_active();
+        let throttling = self.host.status().throttling() && processing;
+
+        if throttling && !self.throttle_warning {
+            self.event(
+                Severity::Warning,
+                EventSource::System,
+                "CPU is throttling while images are being processed",
+            );
+        }
+
+        self.throttle_warning = throttling;
+    }
+
+    fn record_watchdog_event(&mut self, event: WatchdogEvent) {
+        let (severity, message) = match event {
+            WatchdogEvent::Timeout => {
+                self.watchdog.timeouts += 1;
+                (Severity::Warning, "Exposure watchdog: readout timed out")
+            }
+            WatchdogEvent::Retry => {
+                self.watchdog.retries += 1;
+                (Severity::Warning, "Exposure watchdog: exposure retried")
+            }
+            WatchdogEvent::Reconnect => {
+                self.watchdog.reconnects += 1;
+                (Severity::Error, "Exposure watchdog: camera reconnected")
+            }
+        };
+
+        self.event(severity, EventSource::Camera, message);
+    }
+
+    /// Report workers restarted since the last check
+    fn check_workers(&mut self) {
+        for worker in self.workers.statuses() {
+            let reported = self.worker_restarts.insert(worker.name.clone(), worker.restarts);
+
+            if worker.restarts > reported.unwrap_or(0) {
+                self.event(
+                    Severity::Error,
+                    EventSource::System,
+                    &format!(
+                        "Worker '{}' restarted: {}",
+                        worker.name,
+                        worker.last_error.as_deref().unwrap_or("unknown error")
+                    ),
+                );
+            }
+        }
+    }
+
+    fn start_shutdown(
+        &mut self,
+        severity: Severity,
+        source: EventSource,
+        detail: &str,
+        power_off: bool,
+    ) {
+        if self.shutdown.is_some() {
+            return;
+        }
+
+        self.event(severity, source, detail);
+        self.camera_params.loop_enabled = false;
+        self.send_camera_params();
+
+        self.shutdown = Some(ShutdownSequence::new(power_off));
+    }
+
+    fn handle_shutdown(&mut self) {
+        let (stage, elapsed) = match self.shutdown.as_ref() {
+            None => return,
+            Some(shutdown) => (shutdown.stage(), shutdown.stage_elapsed()),
+        };
+
+        let next_stage = match stage {
+            ShutdownStage::StopExposure => {
+                let timeout =
+                    Duration::from_secs_f64(self.camera_params.time.max(0.0)) + EXPOSURE_MARGIN;
+
+                match !self.exposure_active() || elapsed > timeout {
+                    false => None,
+                    true => {
+                        self.event(
+                            Severity::Info,
+                            EventSource::System,
+                            "Shutting down - finishing storage writes",
+                        );
+                        log_err(
+                            "Disable storage before shutdown",
+                            self.storage_tx.send(StorageMessage::DisableStore),
+                        );
+                        log_err(
+                            "Stop recording before shutdown",
+                            self.storage_tx.send(StorageMessage::StopRecording),
+                        );
+                        Some(ShutdownStage::FinishWrites)
                     }
-                    Err(message) => {
-                        self.set_detail(&format!("Init failed: {}", message));
-                        self.connected = None;
-                        State::Error
+                }
+            }
+            ShutdownStage::FinishWrites => {
+                let detail = &self.storage_detail;
+                let recording = detail.recording.as_ref().is_some_and(|rec| rec.active);
+
+                match !(detail.storage_enabled || recording)
+                    || elapsed > FINISH_WRITES_TIMEOUT
+                {
+                    false => None,
+                    true => {
+                        self.event(
+                            Severity::Info,
+                            EventSource::System,
+                            "Shutting down - warming up camera",
+                        );
+                        self.camera_params.temperature = self.config.power.warm_up_temperature;
+                        self.send_camera_params();
+                        Some(ShutdownStage::WarmUp)
                     }
                 }
             }
+            ShutdownStage::WarmUp => {
+                let warmed_up = self
+                    .snapshot
+                    .properties
+                    .as_ref()
+                    .map(|properties| {
+                        properties.basic.temperature as f64
+                            >= self.config.power.warm_up_temperature - WARM_UP_TOLERANCE
+                    })
+                    .unwrap_or(true);
+
+                match warmed_up || elapsed > self.config.power.warm_up_timeout {
+                    false => None,
+                    true => {
+                        self.event(
+                            Severity::Info,
+                            EventSource::System,
+                            "Shutting down - closing camera",
+                        );
+                        Some(ShutdownStage::CloseDevices)
+                    }
+                }
+            }
+            ShutdownStage::CloseDevices => {
+                if !self.finished {
+                    self.finish_shutdown();
+                }
+
+                None
+            }
+        };
+
+        if let (Some(next_stage), Some(shutdown)) = (next_stage, self.shutdown.as_mut()) {
+            shutdown.advance(next_stage);
         }
     }
 
-    fn handle_connected_state(&mut self) -> State {
-        if let Some(ref mut controller) = self.connected {
-            match controller.periodic(self.camera_params.temperature) {
-                Ok(_) => State::Connected,
-                Err(message) => {
-                    self.set_detail(&format!("Periodic task failed: {}", message));
-                    self.connected = None;
-                    State::Error
+    fn finish_shutdown(&mut self) {
+        self.close_acquisition();
+
+        if self.shutdown.as_ref().map(|shutdown| shutdown.power_off()).unwrap_or(false) {
+            info!("Executing turn off command.");
+            execute_command(&self.config.turn_off_command);
+        }
+
+        self.event(Severity::Info, EventSource::System, "Shut down");
+        self.finished = true;
+    }
+
+    /// Ask the acquisition thread to close the camera and wait until it finishes
+    fn close_acquisition(&mut self) {
+        self.send_command(AcquisitionCommand::Close);
+        let deadline = Instant::now() + CLOSE_TIMEOUT;
+
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+
+            match self.acquisition_rx.recv_timeout(timeout) {
+                Ok(AcquisitionReport::Closed) => break,
+                Ok(_) => {}
+                Err(_) => {
+                    warn!("Acquisition thread did not close in time");
+                    return;
                 }
             }
-        } else {
-            State::Error
+        }
+
+        if let Some(handle) = self.acquisition.take() {
+            log_err("Join acquisition thread", handle.join().map_err(|_| "Thread panicked"));
+        }
+    }
+
+    fn connection_state(&self) -> ConnectionState {
+        match self.snapshot.connected {
+            false => ConnectionState::Connecting,
+            true => ConnectionState::Established,
         }
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
-enum State {
-    Error,
-    Connected,
+/// Time to wait for a running exposure beyond its exposure time during shutdown
+const EXPOSURE_MARGIN: Duration = Duration::from_secs(30);
+/// Time to wait for the storage to write queued images during shutdown
+const FINISH_WRITES_TIMEOUT: Duration = Duration::from_secs(60);
+/// Camera is considered warm when it is at most this far below the warm up temperature
+const WARM_UP_TOLERANCE: f64 = 1.0;
+/// Time to wait for the acquisition thread to close the camera
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Dropped frames are summarized in one event per interval
+const DROP_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Image parameters with the ROI and autoexposure settings from the config
+fn initial_image_params(config: &ServiceConfig) -> ImageParams {
+    ImageParams::new(
+        config.render_size,
+        ExposureArea {
+            x: config.roi.x,
+            y: config.roi.y,
+            width: config.roi.width,
+            height: config.roi.height,
+        },
+        config.exp.clone(),
+    )
 }
@@ -0,0 +1,110 @@
+use ccdi_common::{CameraParams, ImageParams, Preset};
+
+// ============================================ PUBLIC =============================================
+
+/// Named parameter presets, saved to the config directory on every change
+pub struct PresetList {
+    presets: Vec<Preset>,
+}
+
+impl PresetList {
+    pub fn new(presets: Vec<Preset>) -> Self {
+        Self { presets }
+    }
+
+    pub fn presets(&self) -> &[Preset] {
+        &self.presets
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Preset, String> {
+        self.presets
+            .iter()
+            .find(|preset| preset.name == name)
+            .ok_or(format!("Preset '{}' not found", name))
+    }
+
+    /// Store parameters under the name, preset with the same name is replaced
+    pub fn create(
+        &mut self,
+        name: &str,
+        camera_params: CameraParams,
+        image_params: ImageParams,
+    ) -> Result<(), String> {
+        let preset = Preset { name: validate_name(name)?, camera_params, image_params };
+
+        match self.position(&preset.name) {
+            Some(index) => self.presets[index] = preset,
+            None => self.presets.push(preset),
+        }
+
+        Ok(())
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), String> {
+        let new_name = validate_name(new_name)?;
+        let index = self.position(name).ok_or(format!("Preset '{}' not found", name))?;
+
+        if name != new_name && self.position(&new_name).is_some() {
+            return Err(format!("Preset '{}' already exists", new_name));
+        }
+
+        self.presets[index].name = new_name;
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        let index = self.position(name).ok_or(format!("Preset '{}' not found", name))?;
+        self.presets.remove(index);
+        Ok(())
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+impl PresetList {
+    fn position(&self, name: &str) -> Option<usize> {
+        self.presets.iter().position(|preset| preset.name == name)
+    }
+}
+
+fn validate_name(name: &str) -> Result<String, String> {
+    match name.trim() {
+        "" => Err(String::from("Preset name must not be empty")),
+        name => Ok(name.to_owned()),
+    }
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create(list: &mut PresetList, name: &str, gain: u16) -> Result<(), String> {
+        let camera_params = CameraParams { gain, ..Default::default() };
+        list.create(name, camera_params, Default::default())
+    }
+
+    #[test]
+    fn create_rename_delete() {
+        let mut list = PresetList::new(Vec::new());
+        assert!(create(&mut list, "  ", 0).is_err());
+
+        create(&mut list, "focus", 100).unwrap();
+        create(&mut list, " deep ", 200).unwrap();
+        create(&mut list, "focus", 300).unwrap();
+        assert_eq!(list.presets().len(), 2);
+        assert_eq!(list.get("focus").unwrap().camera_params.gain, 300);
+        assert_eq!(list.get("deep").unwrap().camera_params.gain, 200);
+
+        assert!(list.rename("focus", "deep").is_err());
+        assert!(list.rename("flats", "dark").is_err());
+        list.rename("focus", "flats").unwrap();
+        assert!(list.get("focus").is_err());
+        assert_eq!(list.get("flats").unwrap().camera_params.gain, 300);
+
+        list.delete("deep").unwrap();
+        assert!(list.delete("deep").is_err());
+        assert_eq!(list.presets().len(), 1);
+    }
+}
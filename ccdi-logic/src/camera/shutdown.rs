@@ -0,0 +1,41 @@
+use std::time::{Duration, Instant};
+
+use ccdi_common::ShutdownStage;
+use log::info;
+
+// ============================================ PUBLIC =============================================
+
+pub struct ShutdownSequence {
+    stage: ShutdownStage,
+    stage_started: Instant,
+    power_off: bool,
+}
+
+impl ShutdownSequence {
+    /// Start the sequence, turn off command is executed at the end if power_off is set
+    pub fn new(power_off: bool) -> Self {
+        Self {
+            stage: ShutdownStage::StopExposure,
+            stage_started: Instant::now(),
+            power_off,
+        }
+    }
+
+    pub fn stage(&self) -> ShutdownStage {
+        self.stage
+    }
+
+    pub fn stage_elapsed(&self) -> Duration {
+        self.stage_started.elapsed()
+    }
+
+    pub fn power_off(&self) -> bool {
+        self.power_off
+    }
+
+    pub fn advance(&mut self, stage: ShutdownStage) {
+        info!("Shutdown stage {:?} -> {:?}", self.stage, stage);
+        self.stage = stage;
+        self.stage_started = Instant::now();
+    }
+}
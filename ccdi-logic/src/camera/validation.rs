@@ -0,0 +1,459 @@
+use ccdi_common::{CameraParamMessage, CameraParams, ImageParamMessage, ImageParams};
+use ccdi_imager_interface::{BasicProperties, ExposureArea, RoiRules};
+
+use crate::ServiceConfig;
+
+// ============================================ PUBLIC =============================================
+
+/// Limits of parameters set from clients, limits from the config are narrowed by the camera while
+/// it is connected
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParamLimits {
+    /// Sensor width and height
+    pub sensor: Option<(usize, usize)>,
+    pub max_gain: Option<u16>,
+    /// Exposure time range in seconds
+    pub min_exposure: f64,
+    pub max_exposure: f64,
+    /// Target temperature range in degrees Celsius
+    pub min_temperature: f64,
+    pub max_temperature: f64,
+    pub roi_rules: RoiRules,
+}
+
+impl ParamLimits {
+    pub fn new(config: &ServiceConfig, properties: Option<&BasicProperties>) -> Self {
+        let (min_exposure, max_exposure) = (
+            config.exp.min_exposure.as_secs_f64(),
+            config.exp.max_exposure.as_secs_f64(),
+        );
+
+        let min_exposure = properties
+            .and_then(|properties| properties.min_exposure)
+            .map_or(min_exposure, |camera| camera.max(min_exposure));
+
+        // Camera range outside of the config range is narrowed to the shortest exposure
+        let max_exposure = properties
+            .and_then(|properties| properties.max_exposure)
+            .map_or(max_exposure, |camera| camera.min(max_exposure))
+            .max(min_exposure);
+
+        Self {
+            sensor: properties.map(|properties| (properties.width, properties.height)),
+            max_gain: properties.and_then(|properties| properties.max_gain),
+            min_exposure,
+            max_exposure,
+            min_temperature: config.cooling.min_temperature,
+            max_temperature: config.cooling.max_temperature,
+            roi_rules: properties.map(|properties| properties.roi_rules).unwrap_or_default(),
+        }
+    }
+}
+
+/// Accepted message, possibly with a value clamped to the limits, and the reason of the change
+pub type Validated<T> = Result<(T, Option<String>), String>;
+
+pub fn validate_camera_param(
+    message: CameraParamMessage,
+    limits: &ParamLimits,
+) -> Validated<CameraParamMessage> {
+    use CameraParamMessage::*;
+
+    match message {
+        SetGain(gain) => match limits.max_gain {
+            Some(max) if gain > max => Ok((
+                SetGain(max),
+                Some(format!("Gain {} clamped to camera maximum {}", gain, max)),
+            )),
+            _ => Ok((SetGain(gain), None)),
+        },
+        SetTime(time) => {
+            finite("Exposure time", time)?;
+
+            if time <= 0.0 {
+                return Err(format!("Exposure time {} s must be positive", time));
+            }
+
+            let (min, max) = (limits.min_exposure, limits.max_exposure);
+
+            match time {
+                time if time < min => Ok((
+                    SetTime(min),
+                    Some(format!("Exposure time {} s clamped to minimum {} s", time, min)),
+                )),
+                time if time > max => Ok((
+                    SetTime(max),
+                    Some(format!("Exposure time {} s clamped to maximum {} s", time, max)),
+                )),
+                _ => Ok((SetTime(time), None)),
+            }
+        }
+        SetTemp(temperature) => {
+            finite("Temperature", temperature)?;
+
+            let (min, max) = (limits.min_temperature, limits.max_temperature);
+
+            match (min..=max).contains(&temperature) {
+                true => Ok((SetTemp(temperature), None)),
+                false => Err(format!(
+                    "Temperature {} C is out of range {} - {} C",
+                    temperature, min, max
+                )),
+            }
+        }
+        SetHeatingPwm(value) => {
+            finite("Heating PWM", value)?;
+            let clamped = value.clamp(0.0, 1.0);
+
+            Ok((
+                SetHeatingPwm(clamped),
+                (clamped != value)
+                    .then(|| format!("Heating PWM {} clamped to duty cycle {}", value, clamped)),
+            ))
+        }
+        EnableLoop(_) | SetTriggerRequired(_) | SetAutoExp(_) => Ok((message, None)),
+    }
+}
+
+pub fn validate_image_param(
+    message: ImageParamMessage,
+    limits: &ParamLimits,
+) -> Validated<ImageParamMessage> {
+    use ImageParamMessage::*;
+
+    match message {
+        SetPercentilePix(value) => fraction("Percentile pixel", value).map(|_| (message, None)),
+        SetPixelTgt(value) => fraction("Pixel target", value).map(|_| (message, None)),
+        SetPixelTol(value) => fraction("Pixel tolerance", value).map(|_| (message, None)),
+        SetRoi(roi) => validate_roi(roi, limits).map(|(roi, reason)| (SetRoi(roi), reason)),
+        SetFlipX(_) | SetFlipY(_) => Ok((message, None)),
+    }
+}
+
+/// Validate each field of stored parameters, e.g. of a preset or of the previous run. Rejected
+/// values are replaced by the fallback, returns the reasons of all clamped and rejected values.
+pub fn validate_params(
+    (camera, image): (&CameraParams, &ImageParams),
+    (fallback_camera, fallback_image): (&CameraParams, &ImageParams),
+    limits: &ParamLimits,
+) -> (CameraParams, ImageParams, Vec<String>) {
+    use CameraParamMessage::*;
+    use ImageParamMessage::*;
+
+    let mut reasons = Vec::new();
+    let mut camera_param = |message| checked(validate_camera_param(message, limits), &mut reasons);
+
+    let validated_camera = CameraParams {
+        gain: match camera_param(SetGain(camera.gain)) {
+            Some(SetGain(gain)) => gain,
+            _ => fallback_camera.gain,
+        },
+        time: match camera_param(SetTime(camera.time)) {
+            Some(SetTime(time)) => time,
+            _ => fallback_camera.time,
+        },
+        temperature: match camera_param(SetTemp(camera.temperature)) {
+            Some(SetTemp(temperature)) => temperature,
+            _ => fallback_camera.temperature,
+        },
+        heating_pwm: match camera_param(SetHeatingPwm(camera.heating_pwm)) {
+            Some(SetHeatingPwm(value)) => value,
+            _ => fallback_camera.heating_pwm,
+        },
+        ..camera.clone()
+    };
+
+    let mut image_param = |message| checked(validate_image_param(message, limits), &mut reasons);
+    let roi = (image.x, image.y, image.w, image.h);
+
+    let (x, y, w, h) = match image_param(SetRoi(roi)) {
+        Some(SetRoi(roi)) => roi,
+        _ => (fallback_image.x, fallback_image.y, fallback_image.w, fallback_image.h),
+    };
+
+    let validated_image = ImageParams {
+        percentile_pix: match image_param(SetPercentilePix(image.percentile_pix)) {
+            Some(SetPercentilePix(value)) => value,
+            _ => fallback_image.percentile_pix,
+        },
+        pixel_tgt: match image_param(SetPixelTgt(image.pixel_tgt)) {
+            Some(SetPixelTgt(value)) => value,
+            _ => fallback_image.pixel_tgt,
+        },
+        pixel_tol: match image_param(SetPixelTol(image.pixel_tol)) {
+            Some(SetPixelTol(value)) => value,
+            _ => fallback_image.pixel_tol,
+        },
+        x,
+        y,
+        w,
+        h,
+        ..image.clone()
+    };
+
+    (validated_camera, validated_image, reasons)
+}
+
+// =========================================== PRIVATE =============================================
+
+/// Accepted value, the reason of a change or rejection is appended to the reasons
+fn checked<T>(result: Validated<T>, reasons: &mut Vec<String>) -> Option<T> {
+    match result {
+        Ok((value, reason)) => {
+            reasons.extend(reason);
+            Some(value)
+        }
+        Err(error) => {
+            reasons.push(format!("{}, previous value kept", error));
+            None
+        }
+    }
+}
+
+fn finite(name: &str, value: f64) -> Result<(), String> {
+    match value.is_finite() {
+        true => Ok(()),
+        false => Err(format!("{} {} is not a number", name, value)),
+    }
+}
+
+fn fraction(name: &str, value: f32) -> Result<(), String> {
+    match (0.0..=1.0).contains(&value) {
+        true => Ok(()),
+        false => Err(format!("{} {} is out of range 0 - 1", name, value)),
+    }
+}
+
+type Roi = (u16, u16, u16, u16);
+
+/// Zero width and height select the full sensor, other areas are snapped to the camera rules
+fn validate_roi(roi: Roi, limits: &ParamLimits) -> Validated<Roi> {
+    let (x, y, width, height) = roi;
+
+    if (width == 0) != (height == 0) {
+        return Err(String::from(
+            "ROI width and height must be both zero (full sensor) or both set",
+        ));
+    }
+
+    match limits.sensor {
+        Some((sensor_width, sensor_height))
+            if x as usize + width as usize > sensor_width
+                || y as usize + height as usize > sensor_height =>
+        {
+            Err(format!(
+                "ROI {}x{} at {},{} does not fit the sensor {}x{}",
+                width, height, x, y, sensor_width, sensor_height
+            ))
+        }
+        Some((sensor_width, sensor_height)) if width > 0 => {
+            let area = ExposureArea {
+                x: x as usize,
+                y: y as usize,
+                width: width as usize,
+                height: height as usize,
+            };
+
+            let snapped = limits.roi_rules.snap(&area, sensor_width, sensor_height);
+            let applied = (
+                snapped.x as u16,
+                snapped.y as u16,
+                snapped.width as u16,
+                snapped.height as u16,
+            );
+
+            Ok((
+                applied,
+                (applied != roi).then(|| {
+                    format!(
+                        "ROI {}x{} at {},{} snapped to {}x{} at {},{}",
+                        width, height, x, y, applied.2, applied.3, applied.0, applied.1
+                    )
+                }),
+            ))
+        }
+        _ => Ok((roi, None)),
+    }
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use ccdi_common::ImgSize;
+
+    use super::*;
+
+    fn limits() -> ParamLimits {
+        ParamLimits {
+            sensor: Some((6000, 4000)),
+            max_gain: Some(450),
+            min_exposure: 0.001,
+            max_exposure: 2000.0,
+            min_temperature: -60.0,
+            max_temperature: 40.0,
+            roi_rules: RoiRules {
+                width_align: 8,
+                height_align: 2,
+                min_width: 16,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Limits from the default config while no camera is connected
+    fn disconnected() -> ParamLimits {
+        ParamLimits::new(&ServiceConfig::default(), None)
+    }
+
+    fn camera(message: CameraParamMessage) -> Validated<CameraParamMessage> {
+        validate_camera_param(message, &limits())
+    }
+
+    fn image(message: ImageParamMessage) -> Validated<ImageParamMessage> {
+        validate_image_param(message, &limits())
+    }
+
+    #[test]
+    fn enable_loop_is_accepted() {
+        assert_eq!(camera(CameraParamMessage::EnableLoop(true)).unwrap().1, None);
+    }
+
+    #[test]
+    fn gain_is_clamped_to_camera_maximum() {
+        assert_eq!(camera(CameraParamMessage::SetGain(200)).unwrap().1, None);
+        let (message, reason) = camera(CameraParamMessage::SetGain(1000)).unwrap();
+        assert_eq!(message, CameraParamMessage::SetGain(450));
+        assert!(reason.unwrap().contains("maximum 450"));
+
+        let unknown = validate_camera_param(CameraParamMessage::SetGain(1000), &disconnected());
+        assert_eq!(unknown.unwrap().0, CameraParamMessage::SetGain(1000));
+    }
+
+    #[test]
+    fn exposure_time_is_checked() {
+        assert!(camera(CameraParamMessage::SetTime(-1.0)).is_err());
+        assert!(camera(CameraParamMessage::SetTime(0.0)).is_err());
+        assert!(camera(CameraParamMessage::SetTime(f64::NAN)).is_err());
+        assert_eq!(camera(CameraParamMessage::SetTime(10.0)).unwrap().1, None);
+        assert_eq!(
+            camera(CameraParamMessage::SetTime(0.0001)).unwrap().0,
+            CameraParamMessage::SetTime(0.001)
+        );
+        assert_eq!(
+            camera(CameraParamMessage::SetTime(5000.0)).unwrap().0,
+            CameraParamMessage::SetTime(2000.0)
+        );
+    }
+
+    #[test]
+    fn temperature_is_checked() {
+        assert!(camera(CameraParamMessage::SetTemp(-10.0)).is_ok());
+        assert!(camera(CameraParamMessage::SetTemp(f64::NAN)).is_err());
+        assert!(camera(CameraParamMessage::SetTemp(-100.0)).is_err());
+    }
+
+    #[test]
+    fn config_limits_apply_while_camera_is_disconnected() {
+        let (message, reason) =
+            validate_camera_param(CameraParamMessage::SetTime(100.0), &disconnected()).unwrap();
+        assert_eq!(message, CameraParamMessage::SetTime(10.0));
+        assert!(reason.unwrap().contains("maximum 10 s"));
+
+        let temperature = CameraParamMessage::SetTemp(-70.0);
+        assert!(validate_camera_param(temperature, &disconnected()).is_err());
+
+        // Camera narrows the config range
+        let properties = BasicProperties {
+            width: 6000,
+            height: 4000,
+            temperature: 0.0,
+            exposure: 0.0,
+            roi: ExposureArea { x: 0, y: 0, width: 0, height: 0 },
+            max_gain: None,
+            min_exposure: Some(0.01),
+            max_exposure: Some(3600.0),
+            roi_rules: Default::default(),
+        };
+
+        let limits = ParamLimits::new(&ServiceConfig::default(), Some(&properties));
+        assert_eq!((limits.min_exposure, limits.max_exposure), (0.01, 10.0));
+    }
+
+    #[test]
+    fn heating_pwm_is_clamped_to_duty_cycle() {
+        assert_eq!(camera(CameraParamMessage::SetHeatingPwm(0.5)).unwrap().1, None);
+        assert_eq!(
+            camera(CameraParamMessage::SetHeatingPwm(20.0)).unwrap().0,
+            CameraParamMessage::SetHeatingPwm(1.0)
+        );
+        assert!(camera(CameraParamMessage::SetHeatingPwm(f64::INFINITY)).is_err());
+    }
+
+    #[test]
+    fn trigger_and_autoexposure_are_accepted() {
+        assert!(camera(CameraParamMessage::SetTriggerRequired(true)).is_ok());
+        assert!(camera(CameraParamMessage::SetAutoExp(false)).is_ok());
+    }
+
+    #[test]
+    fn autoexposure_fractions_are_checked() {
+        assert!(image(ImageParamMessage::SetPercentilePix(0.99)).is_ok());
+        assert!(image(ImageParamMessage::SetPercentilePix(1.5)).is_err());
+        assert!(image(ImageParamMessage::SetPixelTgt(f32::NAN)).is_err());
+        assert!(image(ImageParamMessage::SetPixelTol(-0.1)).is_err());
+    }
+
+    #[test]
+    fn roi_must_fit_the_sensor() {
+        assert!(image(ImageParamMessage::SetRoi((0, 0, 0, 0))).is_ok());
+        assert!(image(ImageParamMessage::SetRoi((100, 100, 1000, 1000))).is_ok());
+        assert!(image(ImageParamMessage::SetRoi((100, 0, 6000, 4000))).is_err());
+        assert!(image(ImageParamMessage::SetRoi((0, 0, 100, 0))).is_err());
+
+        let unknown =
+            validate_image_param(ImageParamMessage::SetRoi((100, 0, 6000, 4000)), &disconnected());
+        assert!(unknown.is_ok());
+    }
+
+    #[test]
+    fn roi_is_snapped_to_camera_rules() {
+        let aligned = image(ImageParamMessage::SetRoi((10, 11, 800, 600))).unwrap();
+        assert_eq!(aligned, (ImageParamMessage::SetRoi((10, 11, 800, 600)), None));
+
+        let (message, reason) = image(ImageParamMessage::SetRoi((10, 11, 805, 601))).unwrap();
+        assert_eq!(message, ImageParamMessage::SetRoi((10, 11, 800, 600)));
+        assert!(reason.unwrap().contains("snapped to 800x600"));
+
+        let small = image(ImageParamMessage::SetRoi((5990, 0, 3, 1))).unwrap().0;
+        assert_eq!(small, ImageParamMessage::SetRoi((5984, 0, 16, 2)));
+    }
+
+    #[test]
+    fn stored_params_are_clamped_or_replaced() {
+        let area = ExposureArea { x: 0, y: 0, width: 0, height: 0 };
+        let size = ImgSize::new(1024, 1024);
+        let fallback_image = ImageParams::new(size, area, Default::default());
+        let fallback_camera = CameraParams { temperature: -20.0, ..CameraParams::new() };
+        let camera = CameraParams { gain: 1000, temperature: -100.0, ..CameraParams::new() };
+        let image = ImageParams { w: 805, h: 601, pixel_tgt: 2.0, ..fallback_image.clone() };
+
+        let (camera, image, reasons) = validate_params(
+            (&camera, &image),
+            (&fallback_camera, &fallback_image),
+            &limits(),
+        );
+
+        assert_eq!(camera.gain, 450);
+        assert_eq!(camera.temperature, -20.0);
+        assert_eq!((image.w, image.h), (800, 600));
+        assert_eq!(image.pixel_tgt, fallback_image.pixel_tgt);
+        assert_eq!(reasons.len(), 4);
+    }
+
+    #[test]
+    fn flips_are_accepted() {
+        assert!(image(ImageParamMessage::SetFlipX(true)).is_ok());
+        assert!(image(ImageParamMessage::SetFlipY(false)).is_ok());
+    }
+}
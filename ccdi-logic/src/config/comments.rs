@@ -0,0 +1,118 @@
+// ============================================ PUBLIC =============================================
+
+/// Insert comments describing known keys into config serialized to YAML
+pub fn annotate_config(yaml: &str) -> String {
+    let mut output = String::from(HEADER);
+    let mut path: Vec<(usize, &str)> = Vec::new();
+
+    for line in yaml.lines() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if let Some((key, _)) = trimmed.split_once(':').filter(|_| !trimmed.starts_with('-')) {
+            while path.last().map(|(level, _)| *level >= indent).unwrap_or(false) {
+                path.pop();
+            }
+
+            path.push((indent, key));
+            let full_key = path.iter().map(|(_, key)| *key).collect::<Vec<_>>().join(".");
+
+            if let Some((_, comment)) = COMMENTS.iter().find(|(name, _)| *name == full_key) {
+                output.push_str(&format!("{}# {}\n", " ".repeat(indent), comment));
+            }
+        }
+
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    output
+}
+
+// =========================================== PRIVATE =============================================
+
+const HEADER: &str = "# ccdi service configuration, missing keys use the default values below\n";
+
+const COMMENTS: &[(&str, &str)] = &[
+    ("version", "Config format version, older files are migrated automatically"),
+    ("storage", "Root directory of saved images, used when no removable drive is selected"),
+    ("turn_off_command", "Command executed when the computer is powered off from the client"),
+    ("savecadence", "Default time between two saved images"),
+    ("render_size", "Size of the preview image sent to clients"),
+    ("roi", "Region of interest, zero width and height use the full sensor"),
+    ("exp", "Autoexposure settings"),
+    ("exp.percentile_pix", "Percentile of pixels used for autoexposure, 0 - 1"),
+    ("exp.pixel_tgt", "Target value of the percentile pixel relative to full scale, 0 - 1"),
+    ("exp.pixel_tol", "Tolerance of the target value, 0 - 1"),
+    ("exp.pixel_exclusion", "Number of brightest pixels ignored"),
+    ("exp.min_exposure", "Shortest exposure time allowed"),
+    ("exp.max_exposure", "Longest exposure time allowed"),
+    ("exp.max_bin", "Maximum binning, 1 disables binning"),
+    ("gui", "Buttons and histogram size of the web client"),
+    ("io", "Paths of GPIO inputs and outputs"),
+    ("io.status_patterns", "Status LED patterns, one character per 20 ms, '1' means on"),
+    ("io.low_storage_gigabytes", "Free space below which the storage is reported as nearly full"),
+    ("power", "Supply voltage monitoring, source kind is disabled, iio, hwmon or script"),
+    ("power.warning_voltage", "Voltage below which a warning is shown"),
+    ("power.shutdown_voltage", "Voltage below which the service shuts down"),
+    ("power.warm_up_temperature", "Camera temperature reached before power off"),
+    ("watchdog", "Recovery of exposures which were not read out in time"),
+    ("watchdog.readout_margin", "Time allowed for readout on top of the exposure time"),
+    ("watchdog.max_retries", "Exposure retries before the camera is reconnected"),
+    ("cooling", "Range of target temperatures accepted from clients, in degrees Celsius"),
+    ("resume_series", "Continue exposure loop and saving after restart"),
+    ("events", "Event log shown in the client"),
+    ("events.capacity", "Number of events kept in memory"),
+    ("events.persist", "Append events to events.log next to the config file"),
+    ("naming", "Names of saved frames inside the storage directory"),
+    (
+        "naming.template",
+        "Tokens: {date} {time} {timestamp} {seq} {seq:05} {gain} {exp} {temp} {bin} {filter} \
+         {frametype} {target}, the extension of each output format is appended",
+    ),
+    ("naming.frame_type", "Value of {frametype}, e.g. light, dark or flat"),
+    ("naming.filter", "Value of {filter}"),
+    ("formats", "Formats of saved frames: fits, tiff, png, xisf, sidecar (JSON headers)"),
+    ("fits", "Headers and compression of saved FITS files"),
+    ("fits.keywords", "Static keywords, e.g. OBSERVER, TELESCOP, SITELAT or FOCALLEN"),
+    ("fits.compression", "Tile compression: none, rice or gzip, float frames always use gzip"),
+    ("storage_queue", "Frames waiting to be written to the storage"),
+    ("storage_queue.capacity", "Maximum number of waiting frames"),
+    (
+        "storage_queue.policy",
+        "When full: block (pause exposure loop), drop_oldest or drop_newest",
+    ),
+    ("disk", "Free space guard and deletion of old data in the storage root"),
+    ("disk.min_free_gigabytes", "Saving pauses while free space is below this limit"),
+    ("disk.retention", "Oldest data is deleted first, every deletion is logged"),
+    (
+        "disk.retention.mode",
+        "Deleted unit: disabled, sessions (directories) or frames (image and video files)",
+    ),
+    ("disk.retention.max_age_days", "Data older than this is deleted, null keeps it"),
+    ("disk.retention.max_gigabytes", "Maximum size of the storage root, null means no limit"),
+    ("removable", "Removable drives offered as storage targets in the Series tab"),
+    (
+        "removable.mount_points",
+        "Mount points checked in addition to drives below /media and /run/media",
+    ),
+    ("removable.directory", "Directory on the drive used as storage root, must not be empty"),
+];
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_are_inserted_before_keys() {
+        let yaml = "version: 1\nexp:\n  pixel_tgt: 0.6\ngui:\n  buttons:\n  - text: a\n";
+        let annotated = annotate_config(yaml);
+
+        assert!(annotated.contains("# Config format version"));
+        assert!(annotated.contains("  # Target value of the percentile pixel"));
+        assert!(serde_yaml::from_str::<serde_yaml::Value>(&annotated).unwrap() ==
+            serde_yaml::from_str::<serde_yaml::Value>(yaml).unwrap());
+    }
+}
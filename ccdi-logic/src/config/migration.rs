@@ -0,0 +1,92 @@
+use serde_yaml::{Mapping, Value};
+
+// ============================================ PUBLIC =============================================
+
+/// Current config format version, increase it together with adding a migration
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Upgrade config document to the current version, returns the version it was migrated from
+pub fn migrate(document: &mut Value) -> Result<u32, String> {
+    let config = document
+        .as_mapping_mut()
+        .ok_or(String::from("config must be a mapping of keys to values"))?;
+
+    let version = match config.get("version") {
+        None => 0,
+        Some(value) => value
+            .as_u64()
+            .ok_or(String::from("version: must be a positive number"))? as u32,
+    };
+
+    if version > CONFIG_VERSION {
+        return Err(format!(
+            "version: {} is newer than supported version {}",
+            version, CONFIG_VERSION
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(config);
+    }
+
+    config.insert(Value::from("version"), Value::from(CONFIG_VERSION));
+    Ok(version)
+}
+
+// =========================================== PRIVATE =============================================
+
+/// Migration at index N upgrades config from version N to N + 1
+const MIGRATIONS: [fn(&mut Mapping); CONFIG_VERSION as usize] = [fix_min_exposure_spelling];
+
+/// Version 1 fixed spelling of exp.min_exopsure
+fn fix_min_exposure_spelling(config: &mut Mapping) {
+    if let Some(Value::Mapping(exp)) = config.get_mut("exp") {
+        rename_key(exp, "min_exopsure", "min_exposure");
+    }
+}
+
+fn rename_key(mapping: &mut Mapping, old: &str, new: &str) {
+    if !mapping.contains_key(new) {
+        if let Some(value) = mapping.remove(old) {
+            mapping.insert(Value::from(new), value);
+        }
+    }
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::config::parse_config;
+
+    use super::*;
+
+    #[test]
+    fn unversioned_config_is_migrated() {
+        let mut document: Value = serde_yaml::from_str(
+            "exp:\n  min_exopsure:\n    secs: 0\n    nanos: 2000000\n"
+        ).unwrap();
+
+        assert_eq!(migrate(&mut document).unwrap(), 0);
+        assert_eq!(document["version"], Value::from(CONFIG_VERSION));
+        assert!(document["exp"].get("min_exopsure").is_none());
+
+        let config = parse_config(&serde_yaml::to_string(&document).unwrap()).unwrap();
+        assert_eq!(config.exp.min_exposure, Duration::from_millis(2));
+        assert_eq!(config.storage, "~/storage/");
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let mut document: Value = serde_yaml::from_str("version: 1000\n").unwrap();
+        assert!(migrate(&mut document).unwrap_err().starts_with("version:"));
+    }
+
+    #[test]
+    fn repository_config_is_valid() {
+        let config = parse_config(include_str!("../../../config.yaml")).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+    }
+}
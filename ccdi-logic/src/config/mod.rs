@@ -1,15 +1,40 @@
-use std::{path::{Path, PathBuf}, sync::Arc, time::Duration};
+use std::{collections::BTreeMap, path::{Path, PathBuf}, sync::Arc, time::Duration};
 use ccdi_imager_interface::ExposureArea;
 use ccdi_common::ImgSize;
 use serde_derive::{Serialize, Deserialize};
 
-use ccdi_common::{to_string, GuiConfig, save_text_file, read_text_file, OptExposureConfig};
+use ccdi_common::{
+    to_string, GuiConfig, save_text_file, read_text_file, OptExposureConfig, OutputFormat,
+    StatusMode, FitsCompression
+};
 use directories::ProjectDirs;
+use log::info;
+
+pub use self::migration::CONFIG_VERSION;
+pub use self::presets::*;
+pub use self::runtime::*;
+pub use self::shared::*;
+pub use self::source::{env_overrides, init_config_source, parse_override, ConfigOverride};
+pub use self::validation::validate_roi;
+pub use self::watcher::*;
+
+mod comments;
+mod migration;
+mod presets;
+mod runtime;
+mod shared;
+mod source;
+mod validation;
+mod watcher;
 
 // ============================================ PUBLIC =============================================
 
+/// Missing fields are filled with defaults, renamed fields are handled by migrations
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ServiceConfig {
+    /// Format version, files without version are migrated from version 0
+    pub version: u32,
     pub storage: String,
     pub savecadence: Duration,
     pub turn_off_command: String,
@@ -18,11 +43,36 @@ pub struct ServiceConfig {
     pub exp: OptExposureConfig,
     pub gui: GuiConfig,
     pub io: IoConfig,
+    #[serde(default)]
+    pub power: PowerConfig,
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    #[serde(default)]
+    pub cooling: CoolingConfig,
+    /// Continue exposure loop and saving after restart if they were active before
+    #[serde(default)]
+    pub resume_series: bool,
+    #[serde(default)]
+    pub events: EventLogConfig,
+    #[serde(default)]
+    pub naming: NamingConfig,
+    #[serde(default)]
+    pub fits: FitsConfig,
+    /// Formats written for each saved frame until changed from the client
+    #[serde(default = "default_formats")]
+    pub formats: Vec<OutputFormat>,
+    #[serde(default)]
+    pub storage_queue: StorageQueueConfig,
+    #[serde(default)]
+    pub disk: DiskConfig,
+    #[serde(default)]
+    pub removable: RemovableConfig,
 }
 
 impl Default for ServiceConfig {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             storage: String::from("~/storage/"),
             savecadence: Duration::from_secs(10),
             render_size: ImgSize::new(1024, 1024),
@@ -30,17 +80,35 @@ impl Default for ServiceConfig {
             exp: Default::default(),
             gui: Default::default(),
             io: Default::default(),
+            power: Default::default(),
+            watchdog: Default::default(),
+            cooling: Default::default(),
+            resume_series: false,
+            events: Default::default(),
+            naming: Default::default(),
+            fits: Default::default(),
+            formats: default_formats(),
+            storage_queue: Default::default(),
+            disk: Default::default(),
+            removable: Default::default(),
             turn_off_command: String::new(),
         }
     }
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct IoConfig {
     pub trigger_input: String,
     pub exposure_status: String,
     pub heating_pwm: String,
     pub main_status: String,
+    /// Blink patterns of the main status LED
+    #[serde(default)]
+    pub status_patterns: StatusPatterns,
+    /// Free space in gigabytes below which the storage is reported as nearly full
+    #[serde(default = "default_low_storage_gigabytes")]
+    pub low_storage_gigabytes: f64,
 }
 
 impl Default for IoConfig {
@@ -49,24 +117,346 @@ impl Default for IoConfig {
             trigger_input: String::from("/sys/class/gpio/gpio17/value"),
             exposure_status: String::from("/sys/class/gpio/gpio2/value"),
             heating_pwm: String::from("/sys/class/gpio/gpio4/value"),
-            main_status: String::from("/sys/class/gpio/gpio3/value")
+            main_status: String::from("/sys/class/gpio/gpio3/value"),
+            status_patterns: Default::default(),
+            low_storage_gigabytes: default_low_storage_gigabytes(),
+        }
+    }
+}
+
+/// Status LED patterns, one character per IO tick (20 ms), '1' means LED on
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusPatterns {
+    pub disconnected: String,
+    pub idle: String,
+    pub exposing: String,
+    pub saving: String,
+    pub storage_error: String,
+    pub shutting_down: String,
+}
+
+impl StatusPatterns {
+    pub fn get(&self, mode: StatusMode) -> &str {
+        match mode {
+            StatusMode::Disconnected => &self.disconnected,
+            StatusMode::Idle => &self.idle,
+            StatusMode::Exposing => &self.exposing,
+            StatusMode::Saving => &self.saving,
+            StatusMode::StorageError => &self.storage_error,
+            StatusMode::ShuttingDown => &self.shutting_down,
+        }
+    }
+}
+
+impl Default for StatusPatterns {
+    fn default() -> Self {
+        Self {
+            disconnected: String::from("1111111111111111111111111000000000000000000000000"),
+            idle: String::from("1000000000000000000000000000000000000000000000000"),
+            exposing: String::from("1111100000000000000000000"),
+            saving: String::from("1000010000000000000000000"),
+            storage_error: String::from("10101010100000000000000000000000000000000000000000"),
+            shutting_down: String::from("1"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    /// Time allowed for readout on top of the exposure time
+    pub readout_margin: Duration,
+    /// Number of exposure retries before the camera is reconnected
+    pub max_retries: usize,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            readout_margin: Duration::from_secs(30),
+            max_retries: 2,
+        }
+    }
+}
+
+/// Temperatures which clients may set as the camera target
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoolingConfig {
+    pub min_temperature: f64,
+    pub max_temperature: f64,
+}
+
+impl Default for CoolingConfig {
+    fn default() -> Self {
+        Self {
+            min_temperature: -60.0,
+            max_temperature: 40.0,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerConfig {
+    pub source: PowerSource,
+    /// Interval between two voltage measurements
+    pub read_interval: Duration,
+    /// Time window used to compute the voltage trend
+    pub trend_window: Duration,
+    /// Voltage below which a warning is shown
+    pub warning_voltage: f64,
+    /// Voltage below which the service shuts down
+    pub shutdown_voltage: f64,
+    /// How long the voltage must stay below shutdown voltage before shutdown starts
+    pub shutdown_delay: Duration,
+    /// Camera temperature to reach before the power is turned off
+    pub warm_up_temperature: f64,
+    /// Maximum time to wait for the camera to warm up
+    pub warm_up_timeout: Duration,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            source: PowerSource::Disabled,
+            read_interval: Duration::from_secs(5),
+            trend_window: Duration::from_secs(600),
+            warning_voltage: 11.8,
+            shutdown_voltage: 11.2,
+            shutdown_delay: Duration::from_secs(30),
+            warm_up_temperature: 10.0,
+            warm_up_timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventLogConfig {
+    /// Number of events kept in memory and sent to newly connected clients
+    pub capacity: usize,
+    /// Append events to events.log next to the config file
+    pub persist: bool,
+}
+
+impl Default for EventLogConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 200,
+            persist: false,
+        }
+    }
+}
+
+/// Names of saved frames, relative to the storage directory selected in the client
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NamingConfig {
+    /// Template with tokens {date} {time} {timestamp} {seq} {seq:05} {gain} {exp} {temp} {bin}
+    /// {filter} {frametype} {target}, may contain subdirectories
+    pub template: String,
+    /// Value of the {frametype} token, e.g. light, dark or flat
+    pub frame_type: String,
+    /// Value of the {filter} token
+    pub filter: String,
+}
+
+impl Default for NamingConfig {
+    fn default() -> Self {
+        Self {
+            template: String::from("{target}_{timestamp}_{seq:05}.fits"),
+            frame_type: String::from("light"),
+            filter: String::from("none"),
+        }
+    }
+}
+
+/// Headers and compression of saved FITS files
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct FitsConfig {
+    /// Static keywords added to every file, e.g. OBSERVER, TELESCOP, SITELAT or FOCALLEN
+    pub keywords: BTreeMap<String, KeywordValue>,
+    /// Compression used until changed from the client
+    pub compression: FitsCompression,
+}
+
+/// Frames waiting to be written between the camera and the storage thread
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageQueueConfig {
+    /// Number of frames in the queue
+    pub capacity: usize,
+    /// What happens to new frames when the queue is full
+    pub policy: QueuePolicy,
+}
+
+impl Default for StorageQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 4,
+            policy: QueuePolicy::Block,
         }
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueuePolicy {
+    /// Next exposure of the loop is not started until a frame is written
+    Block,
+    /// Oldest waiting frame is discarded
+    DropOldest,
+    /// Incoming frame is discarded
+    DropNewest,
+}
+
+/// Free space guard and deletion of old data in the storage root
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskConfig {
+    /// Saving pauses while free space is below this limit
+    pub min_free_gigabytes: f64,
+    pub retention: RetentionConfig,
+}
+
+impl Default for DiskConfig {
+    fn default() -> Self {
+        Self {
+            min_free_gigabytes: 0.5,
+            retention: Default::default(),
+        }
+    }
+}
+
+/// Oldest data is deleted first until both limits are met
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub mode: RetentionMode,
+    /// Data older than this is deleted
+    pub max_age_days: Option<f64>,
+    /// Oldest data is deleted while the storage root is bigger
+    pub max_gigabytes: Option<f64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionMode {
+    #[default]
+    Disabled,
+    /// Whole directories selected in the client
+    Sessions,
+    /// Image and video files in any directory
+    Frames,
+}
+
+/// Removable drives offered as storage targets, drives below /media and /run/media are found
+/// automatically
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemovableConfig {
+    /// Additional mount points, e.g. /mnt/usb
+    pub mount_points: Vec<String>,
+    /// Directory on the drive used as storage root, not the drive itself because the retention
+    /// deletes data inside it
+    pub directory: String,
+}
+
+impl Default for RemovableConfig {
+    fn default() -> Self {
+        Self {
+            mount_points: Vec::new(),
+            directory: String::from("ccdi"),
+        }
+    }
+}
+
+/// Value of a FITS header keyword, numbers are written as numbers for downstream tools
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeywordValue {
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// Source of the supply voltage measurement
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PowerSource {
+    Disabled,
+    /// IIO ADC raw value, e.g. /sys/bus/iio/devices/iio:device0/in_voltage0_raw
+    Iio {
+        raw_path: String,
+        /// Millivolts per ADC step (value of in_voltageX_scale)
+        scale: f64,
+        /// Ratio of the external voltage divider
+        divider: f64,
+    },
+    /// Voltage in millivolts from hwmon, e.g. INA219 /sys/class/hwmon/hwmon0/in1_input
+    Hwmon {
+        path: String,
+    },
+    /// Shell command printing voltage in volts to stdout
+    Script {
+        command: String,
+    },
+}
+
 pub fn load_config_file() -> Result<Arc<ServiceConfig>, String> {
     let path = config_file_path()?;
 
-    let res = serde_yaml::from_str::<ServiceConfig>(&read_text_file(path.as_path())?)
+    let res = parse_config(&read_config_file()?)
         .map_err(|err| format!("Could not load config file {}: {}", path_as_string(&path), err))
         .map(Arc::new)?;
 
     Ok(res)
 }
 
+/// Text of the config file as it is on disk, without environment and command line overrides
+pub fn read_config_file() -> Result<String, String> {
+    read_text_file(config_file_path()?.as_path())
+}
+
+/// Parse config in YAML format, migrate it to the current version, apply environment and command
+/// line overrides and validate it
+pub fn parse_config(text: &str) -> Result<ServiceConfig, String> {
+    Ok(parse_config_layers(text)?.effective)
+}
+
+/// Parse config edited by the user, the file layer is saved and the effective config is applied
+pub fn parse_config_layers(text: &str) -> Result<ConfigLayers, String> {
+    parse_layers(text, source::overrides())
+}
+
+/// Config file content and the config with overrides applied on top of it
+pub struct ConfigLayers {
+    /// Migrated content of the config file, the only layer written back to disk
+    pub file: ServiceConfig,
+    /// Validated config used by the service
+    pub effective: ServiceConfig,
+}
+
+pub fn config_to_string(config: &ServiceConfig) -> Result<String, String> {
+    serde_yaml::to_string(config).map_err(to_string)
+}
+
+/// Save config with comments describing the fields, overrides must not be part of the config
+pub fn save_config_file(file: &ServiceConfig) -> Result<(), String> {
+    save_file_atomic(&config_file_text(file)?, &config_file_path()?)
+}
+
+pub fn event_log_path() -> Result<PathBuf, String> {
+    create_file_path("events.log")
+}
+
 pub fn create_default_config_file() -> Result<String, String> {
-    let config_json = serde_yaml::to_string(&<ServiceConfig as Default>::default())
-        .map_err(to_string)?;
+    let config_json = comments::annotate_config(
+        &config_to_string(&<ServiceConfig as Default>::default())?
+    );
 
     let path = default_file_path()?;
 
@@ -78,19 +468,62 @@ pub fn create_default_config_file() -> Result<String, String> {
 
 // =========================================== PRIVATE =============================================
 
+fn default_low_storage_gigabytes() -> f64 {
+    1.0
+}
+
+fn default_formats() -> Vec<OutputFormat> {
+    vec![OutputFormat::Fits]
+}
+
+fn parse_layers(text: &str, overrides: &[ConfigOverride]) -> Result<ConfigLayers, String> {
+    let mut document = serde_yaml::from_str::<serde_yaml::Value>(text).map_err(to_string)?;
+    let version = migration::migrate(&mut document)?;
+
+    if version != CONFIG_VERSION {
+        info!("Config migrated from version {} to {}", version, CONFIG_VERSION);
+    }
+
+    let file = deserialize(&document)?;
+    source::apply(&mut document, overrides)?;
+    let effective = deserialize(&document)?;
+    effective.validate()?;
+    Ok(ConfigLayers { file, effective })
+}
+
+/// Deserialize from text again, errors from values do not contain the key path
+fn deserialize(document: &serde_yaml::Value) -> Result<ServiceConfig, String> {
+    let text = serde_yaml::to_string(document).map_err(to_string)?;
+    serde_yaml::from_str::<ServiceConfig>(&text).map_err(to_string)
+}
+
+fn config_file_text(file: &ServiceConfig) -> Result<String, String> {
+    Ok(comments::annotate_config(&config_to_string(file)?))
+}
+
 fn path_as_string(path: &Path) -> String {
     path.to_string_lossy().to_string()
 }
 
+const CONFIG_FILE_NAME: &str = "config.yaml";
+
 fn config_file_path() -> Result<PathBuf, String> {
-    create_file_path("config.yaml")
+    match source::config_path() {
+        Some(path) => Ok(path.to_path_buf()),
+        None => create_file_path(CONFIG_FILE_NAME),
+    }
 }
 
 fn default_file_path() -> Result<PathBuf, String> {
     create_file_path("default.yaml")
 }
 
+/// Files are stored next to the config file given on the command line or in the config directory
 fn create_file_path(file_name: &str) -> Result<PathBuf, String> {
+    if let Some(directory) = source::config_path().and_then(Path::parent) {
+        return Ok(directory.join(file_name));
+    }
+
     Ok(
         ProjectDirs::from("", "",  "ccdi")
             .ok_or(String::from("Could not determine config directory path"))?
@@ -99,3 +532,33 @@ fn create_file_path(file_name: &str) -> Result<PathBuf, String> {
             .join(file_name)
     )
 }
+
+/// Save to a temporary file first so that a power loss does not leave a truncated file
+fn save_file_atomic(content: &str, path: &Path) -> Result<(), String> {
+    let temporary = path.with_extension("tmp");
+    save_text_file(content, &temporary)?;
+    std::fs::rename(&temporary, path).map_err(to_string)
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_are_applied_but_not_saved() {
+        let overrides = [ConfigOverride {
+            origin: String::from("CCDI_STORAGE"),
+            key: String::from("storage"),
+            value: String::from("/mnt/override"),
+        }];
+
+        let layers = parse_layers("storage: /mnt/file\n", &overrides).unwrap();
+        assert_eq!(layers.effective.storage, "/mnt/override");
+
+        let saved = config_file_text(&layers.file).unwrap();
+        assert!(saved.contains("/mnt/file"));
+        assert!(!saved.contains("/mnt/override"));
+    }
+}
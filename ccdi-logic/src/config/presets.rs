@@ -0,0 +1,26 @@
+use ccdi_common::{read_text_file, to_string, Preset};
+
+use super::{create_file_path, save_file_atomic};
+
+// ============================================ PUBLIC =============================================
+
+/// Load presets from the config directory, missing file means no presets
+pub fn load_presets() -> Result<Vec<Preset>, String> {
+    let path = create_file_path(PRESETS_FILE_NAME)?;
+
+    match path.exists() {
+        false => Ok(Vec::new()),
+        true => serde_yaml::from_str::<Vec<Preset>>(&read_text_file(&path)?).map_err(to_string),
+    }
+}
+
+pub fn save_presets(presets: &[Preset]) -> Result<(), String> {
+    save_file_atomic(
+        &serde_yaml::to_string(presets).map_err(to_string)?,
+        &create_file_path(PRESETS_FILE_NAME)?,
+    )
+}
+
+// =========================================== PRIVATE =============================================
+
+const PRESETS_FILE_NAME: &str = "presets.yaml";
@@ -0,0 +1,30 @@
+use ccdi_common::{read_text_file, to_string, CameraParams, ImageParams, StorageSettings};
+use serde_derive::{Deserialize, Serialize};
+
+use super::{create_file_path, save_file_atomic};
+
+// ============================================ PUBLIC =============================================
+
+/// Settings changed at runtime, saved next to the config file and restored at startup
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RuntimeState {
+    pub camera_params: CameraParams,
+    pub image_params: ImageParams,
+    pub storage: StorageSettings,
+}
+
+pub fn load_runtime_state() -> Result<RuntimeState, String> {
+    let path = create_file_path(STATE_FILE_NAME)?;
+    serde_yaml::from_str::<RuntimeState>(&read_text_file(&path)?).map_err(to_string)
+}
+
+pub fn save_runtime_state(state: &RuntimeState) -> Result<(), String> {
+    save_file_atomic(
+        &serde_yaml::to_string(state).map_err(to_string)?,
+        &create_file_path(STATE_FILE_NAME)?,
+    )
+}
+
+// =========================================== PRIVATE =============================================
+
+const STATE_FILE_NAME: &str = "state.yaml";
@@ -0,0 +1,61 @@
+use std::sync::{Arc, Mutex};
+
+use super::ServiceConfig;
+
+// ============================================ PUBLIC =============================================
+
+/// Config shared by all threads, replaced when edited from the client or when the file changes
+#[derive(Clone)]
+pub struct SharedConfig {
+    current: Arc<Mutex<VersionedConfig>>,
+}
+
+impl SharedConfig {
+    pub fn new(config: Arc<ServiceConfig>) -> Self {
+        Self {
+            current: Arc::new(Mutex::new(VersionedConfig { version: 0, config })),
+        }
+    }
+
+    pub fn get(&self) -> Arc<ServiceConfig> {
+        self.lock(|current| current.config.clone())
+    }
+
+    pub fn version(&self) -> usize {
+        self.lock(|current| current.version)
+    }
+
+    pub fn set(&self, config: Arc<ServiceConfig>) {
+        self.lock(|current| {
+            current.version += 1;
+            current.config = config;
+        })
+    }
+
+    /// Return the config if it was replaced since the version, the version is updated
+    pub fn changed(&self, version: &mut usize) -> Option<Arc<ServiceConfig>> {
+        self.lock(|current| match current.version == *version {
+            true => None,
+            false => {
+                *version = current.version;
+                Some(current.config.clone())
+            }
+        })
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+struct VersionedConfig {
+    version: usize,
+    config: Arc<ServiceConfig>,
+}
+
+impl SharedConfig {
+    fn lock<T>(&self, action: impl FnOnce(&mut VersionedConfig) -> T) -> T {
+        match self.current.lock() {
+            Ok(mut current) => action(&mut current),
+            Err(poisoned) => action(&mut poisoned.into_inner()),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use ccdi_common::to_string;
+use serde_yaml::{Mapping, Value};
+
+use super::ServiceConfig;
+
+// ============================================ PUBLIC =============================================
+
+/// Single config key set from the environment or the command line
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConfigOverride {
+    /// Environment variable or command line option which set the value
+    pub origin: String,
+    /// Key path separated by dots, e.g. exp.pixel_tgt
+    pub key: String,
+    /// Value in YAML syntax
+    pub value: String,
+}
+
+/// Set config file location and overrides applied on every load of the config file. Must be
+/// called once before the config is loaded, overrides later in the list take precedence.
+pub fn init_config_source(
+    path: Option<PathBuf>,
+    overrides: Vec<ConfigOverride>,
+) -> Result<(), String> {
+    SOURCE
+        .set(ConfigSource { path, overrides })
+        .map_err(|_| String::from("Config source already initialized"))
+}
+
+/// Overrides from CCDI_* environment variables, nested keys are separated by double underscore,
+/// e.g. CCDI_EXP__PIXEL_TGT=0.5 sets exp.pixel_tgt
+pub fn env_overrides() -> Vec<ConfigOverride> {
+    let mut overrides = std::env::vars()
+        .filter_map(|(name, value)| {
+            let key = name.strip_prefix(ENV_PREFIX)?.to_lowercase().replace("__", ".");
+            Some(ConfigOverride { origin: name, key, value })
+        })
+        .collect::<Vec<_>>();
+
+    // Environment order is not defined, keep the result stable
+    overrides.sort_by(|a, b| a.origin.cmp(&b.origin));
+    overrides
+}
+
+/// Parse command line override in the form key=value, e.g. exp.pixel_tgt=0.5
+pub fn parse_override(text: &str) -> Result<ConfigOverride, String> {
+    let (key, value) = text
+        .split_once('=')
+        .ok_or(format!("Config override '{}' must have the form key=value", text))?;
+
+    Ok(ConfigOverride {
+        origin: format!("--set {}", text),
+        key: key.trim().to_owned(),
+        value: value.to_owned(),
+    })
+}
+
+// =========================================== PRIVATE =============================================
+
+const ENV_PREFIX: &str = "CCDI_";
+
+static SOURCE: OnceLock<ConfigSource> = OnceLock::new();
+
+struct ConfigSource {
+    path: Option<PathBuf>,
+    overrides: Vec<ConfigOverride>,
+}
+
+/// Config file path given on the command line
+pub(super) fn config_path() -> Option<&'static Path> {
+    SOURCE.get().and_then(|source| source.path.as_deref())
+}
+
+/// Overrides set by init_config_source
+pub(super) fn overrides() -> &'static [ConfigOverride] {
+    SOURCE.get().map(|source| source.overrides.as_slice()).unwrap_or_default()
+}
+
+pub(super) fn apply(document: &mut Value, overrides: &[ConfigOverride]) -> Result<(), String> {
+    let defaults = serde_yaml::to_value(ServiceConfig::default()).map_err(to_string)?;
+
+    for item in overrides {
+        let path = item.key.split('.').collect::<Vec<_>>();
+        let (name, parents) = path.split_last().ok_or(String::from("Empty config key"))?;
+
+        // Keys of tagged enums like power.source differ by variant, any key is accepted there
+        let known = [&defaults, &*document].iter().any(|root| {
+            lookup(root, &path).is_some()
+                || lookup(root, parents).map(|parent| parent.get("kind").is_some()).unwrap_or(false)
+        });
+
+        if !known {
+            return Err(format!("{}: unknown config key {}", item.origin, item.key));
+        }
+
+        let mut target = &mut *document;
+
+        for parent in parents {
+            let mapping = target
+                .as_mapping_mut()
+                .ok_or(format!("{}: {} is not a mapping", item.origin, parent))?;
+
+            target = mapping
+                .entry(Value::from(*parent))
+                .or_insert_with(|| Value::Mapping(Mapping::new()));
+        }
+
+        target
+            .as_mapping_mut()
+            .ok_or(format!("{}: parent of {} is not a mapping", item.origin, item.key))?
+            .insert(Value::from(*name), parse_value(&item.value));
+    }
+
+    Ok(())
+}
+
+fn lookup<'a>(root: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().try_fold(root, |value, key| value.get(key))
+}
+
+/// Values are YAML, plain text which is not valid YAML is used as a string
+fn parse_value(text: &str) -> Value {
+    match serde_yaml::from_str::<Value>(text) {
+        Ok(Value::Null) | Err(_) => Value::from(text),
+        Ok(value) => value,
+    }
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(key: &str, value: &str) -> ConfigOverride {
+        ConfigOverride { origin: String::from("test"), key: key.to_owned(), value: value.to_owned() }
+    }
+
+    #[test]
+    fn overrides_replace_values() {
+        let mut document = serde_yaml::from_str::<Value>("exp:\n  pixel_tgt: 0.6\n").unwrap();
+
+        let overrides = [
+            item("exp.pixel_tgt", "0.4"),
+            item("exp.pixel_tgt", "0.5"),
+            item("storage", "/mnt/data"),
+            item("watchdog.max_retries", "5"),
+            item("turn_off_command", ""),
+        ];
+
+        apply(&mut document, &overrides).unwrap();
+        assert_eq!(document["exp"]["pixel_tgt"], Value::from(0.5));
+        assert_eq!(document["storage"], Value::from("/mnt/data"));
+        assert_eq!(document["watchdog"]["max_retries"], Value::from(5));
+        assert_eq!(document["turn_off_command"], Value::from(""));
+
+        assert!(apply(&mut document, &[item("exp.pixel_target", "1")]).is_err());
+        assert!(apply(&mut document, &[item("unknown", "1")]).is_err());
+        assert!(parse_override("exp.pixel_tgt").is_err());
+        assert_eq!(parse_override("storage=/a=b").unwrap().value, "/a=b");
+    }
+}
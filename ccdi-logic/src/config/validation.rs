@@ -0,0 +1,208 @@
+use std::{
+    path::{Component, Path},
+    time::Duration,
+};
+
+use ccdi_imager_interface::ExposureArea;
+
+use crate::storage::{validate_keyword, validate_template};
+
+use super::{PowerSource, ServiceConfig};
+
+// ============================================ PUBLIC =============================================
+
+impl ServiceConfig {
+    /// Check values which deserialize fine but cannot be used, all errors name the offending key
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Validation::default();
+        let exp = &self.exp;
+        let power = &self.power;
+
+        errors.check(!self.storage.trim().is_empty(), "storage", "must not be empty");
+        errors.check(self.render_size.x > 0, "render_size.x", "must not be zero");
+        errors.check(self.render_size.y > 0, "render_size.y", "must not be zero");
+
+        errors.check(
+            (self.roi.width == 0) == (self.roi.height == 0),
+            "roi",
+            "width and height must be both zero (full sensor) or both set",
+        );
+
+        errors.range("exp.percentile_pix", exp.percentile_pix, 0.0, 1.0);
+        errors.range("exp.pixel_tgt", exp.pixel_tgt, 0.0, 1.0);
+        errors.range("exp.pixel_tol", exp.pixel_tol, 0.0, 1.0);
+        errors.check(exp.max_bin > 0, "exp.max_bin", "must be at least 1");
+        errors.positive("exp.max_exposure", exp.max_exposure);
+
+        errors.check(
+            exp.min_exposure <= exp.max_exposure,
+            "exp.min_exposure",
+            "must not be longer than exp.max_exposure",
+        );
+
+        errors.check(
+            self.cooling.min_temperature <= self.cooling.max_temperature,
+            "cooling.min_temperature",
+            "must not be higher than cooling.max_temperature",
+        );
+
+        errors.check(self.gui.histogram_width > 0, "gui.histogram_width", "must not be zero");
+        errors.check(self.gui.histogram_height > 0, "gui.histogram_height", "must not be zero");
+
+        errors.check(
+            self.io.low_storage_gigabytes >= 0.0,
+            "io.low_storage_gigabytes",
+            "must not be negative",
+        );
+
+        let patterns = &self.io.status_patterns;
+
+        for (key, pattern) in [
+            ("disconnected", &patterns.disconnected),
+            ("idle", &patterns.idle),
+            ("exposing", &patterns.exposing),
+            ("saving", &patterns.saving),
+            ("storage_error", &patterns.storage_error),
+            ("shutting_down", &patterns.shutting_down),
+        ] {
+            errors.check(
+                pattern.chars().any(|c| c == '0' || c == '1'),
+                &format!("io.status_patterns.{}", key),
+                "must contain at least one '0' or '1'",
+            );
+        }
+
+        if power.source != PowerSource::Disabled {
+            errors.positive("power.read_interval", power.read_interval);
+            errors.check(
+                power.shutdown_voltage < power.warning_voltage,
+                "power.shutdown_voltage",
+                "must be lower than power.warning_voltage",
+            );
+        }
+
+        errors.check(self.events.capacity > 0, "events.capacity", "must not be zero");
+
+        if let Err(error) = validate_template(&self.naming) {
+            errors.check(false, "naming.template", &error);
+        }
+
+        errors.check(!self.formats.is_empty(), "formats", "must contain at least one format");
+
+        errors.check(self.storage_queue.capacity > 0, "storage_queue.capacity", "must not be zero");
+        errors.check(
+            self.disk.min_free_gigabytes >= 0.0,
+            "disk.min_free_gigabytes",
+            "must not be negative",
+        );
+
+        let retention = &self.disk.retention;
+        let limits = [
+            ("disk.retention.max_age_days", retention.max_age_days),
+            ("disk.retention.max_gigabytes", retention.max_gigabytes),
+        ];
+
+        for (key, limit) in limits {
+            errors.check(limit.is_none_or(|limit| limit > 0.0), key, "must be positive");
+        }
+
+        for mount_point in &self.removable.mount_points {
+            errors.check(
+                mount_point.starts_with('/'),
+                "removable.mount_points",
+                &format!("'{}' must be an absolute path", mount_point),
+            );
+        }
+
+        // Retention deletes data in the storage root, it must not be the whole drive
+        let directory = Path::new(&self.removable.directory);
+        errors.check(
+            directory.components().next().is_some()
+                && directory.components().all(|part| matches!(part, Component::Normal(_))),
+            "removable.directory",
+            "must be a non-empty path relative to the drive",
+        );
+
+        for key in self.fits.keywords.keys() {
+            if let Err(error) = validate_keyword(key) {
+                errors.check(false, &format!("fits.keywords.{}", key), &error);
+            }
+        }
+
+        errors.result()
+    }
+}
+
+/// Check that ROI from the config fits the sensor reported by the camera
+pub fn validate_roi(roi: &ExposureArea, width: usize, height: usize) -> Result<(), String> {
+    match roi.x + roi.width <= width && roi.y + roi.height <= height {
+        true => Ok(()),
+        false => Err(format!(
+            "roi: {}x{} at {},{} is bigger than the sensor {}x{}",
+            roi.width, roi.height, roi.x, roi.y, width, height
+        )),
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+#[derive(Default)]
+struct Validation {
+    errors: Vec<String>,
+}
+
+impl Validation {
+    fn check(&mut self, valid: bool, key: &str, message: &str) {
+        if !valid {
+            self.errors.push(format!("{}: {}", key, message));
+        }
+    }
+
+    fn range(&mut self, key: &str, value: f32, min: f32, max: f32) {
+        self.check(
+            (min..=max).contains(&value),
+            key,
+            &format!("{} is out of range {} - {}", value, min, max),
+        );
+    }
+
+    fn positive(&mut self, key: &str, value: Duration) {
+        self.check(!value.is_zero(), key, "must not be zero");
+    }
+
+    fn result(self) -> Result<(), String> {
+        match self.errors.is_empty() {
+            true => Ok(()),
+            false => Err(self.errors.join(", ")),
+        }
+    }
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_name_offending_keys() {
+        assert!(ServiceConfig::default().validate().is_ok());
+
+        let mut config = ServiceConfig::default();
+        config.exp.pixel_tgt = 1.5;
+        config.roi.width = 100;
+
+        let error = config.validate().unwrap_err();
+        assert!(error.contains("exp.pixel_tgt: 1.5 is out of range"));
+        assert!(error.contains("roi: width and height"));
+
+        for directory in ["", ".", "/ccdi", "../ccdi"] {
+            let mut config = ServiceConfig::default();
+            config.removable.directory = directory.to_owned();
+            assert!(config.validate().unwrap_err().contains("removable.directory"));
+        }
+
+        let roi = ExposureArea { x: 100, y: 0, width: 6000, height: 4000 };
+        assert!(validate_roi(&roi, 6000, 4000).unwrap_err().starts_with("roi:"));
+    }
+}
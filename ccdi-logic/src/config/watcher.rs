@@ -0,0 +1,62 @@
+use std::{
+    fs,
+    time::{Duration, Instant, SystemTime},
+};
+
+use ccdi_common::{to_string, Severity};
+
+use super::{config_file_path, load_config_file, SharedConfig};
+
+// ============================================ PUBLIC =============================================
+
+/// Polls modification time of config.yaml and reloads the config after manual edits
+pub struct ConfigWatcher {
+    config: SharedConfig,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new(config: SharedConfig) -> Self {
+        Self {
+            config,
+            modified: modified_time().ok(),
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Reload the config file if it was modified, returns description of the result
+    pub fn periodic(&mut self) -> Option<(Severity, String)> {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return None;
+        }
+
+        self.last_check = Instant::now();
+        let modified = modified_time().ok();
+
+        if modified == self.modified {
+            return None;
+        }
+
+        self.modified = modified;
+
+        match load_config_file() {
+            Ok(config) if config == self.config.get() => None,
+            Ok(config) => {
+                self.config.set(config);
+                Some((Severity::Info, String::from("Config file changed - reloaded")))
+            }
+            Err(error) => {
+                Some((Severity::Error, format!("Config file not reloaded: {}", error)))
+            }
+        }
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+fn modified_time() -> Result<SystemTime, String> {
+    fs::metadata(config_file_path()?).and_then(|metadata| metadata.modified()).map_err(to_string)
+}
@@ -0,0 +1,120 @@
+use std::{collections::VecDeque, fs::OpenOptions, io::Write, time::SystemTime};
+
+use ccdi_common::{format_utc_time, log_err, to_string, Event, EventSource, Severity};
+use log::{error, info, warn};
+
+use crate::{event_log_path, EventLogConfig};
+
+// ============================================ PUBLIC =============================================
+
+/// Ring buffer of the latest events, new events are pushed to clients incrementally
+pub struct EventLog {
+    events: VecDeque<Event>,
+    config: EventLogConfig,
+    next_id: u64,
+    /// Events with lower id were already sent to clients
+    sent_id: u64,
+}
+
+impl EventLog {
+    pub fn new(config: EventLogConfig) -> Self {
+        Self {
+            events: VecDeque::new(),
+            config,
+            next_id: 1,
+            sent_id: 1,
+        }
+    }
+
+    pub fn update_config(&mut self, config: EventLogConfig) {
+        self.config = config;
+        self.trim();
+    }
+
+    pub fn push(&mut self, severity: Severity, source: EventSource, message: &str) {
+        match severity {
+            Severity::Info => info!("{:?}: {}", source, message),
+            Severity::Warning => warn!("{:?}: {}", source, message),
+            Severity::Error => error!("{:?}: {}", source, message),
+        }
+
+        let event = Event {
+            id: self.next_id,
+            time: SystemTime::now(),
+            severity,
+            source,
+            message: message.to_owned(),
+        };
+
+        if self.config.persist {
+            log_err("Persist event", append_to_file(&event));
+        }
+
+        self.next_id += 1;
+        self.events.push_back(event);
+        self.trim();
+    }
+
+    /// All events kept in the buffer, sent to newly connected clients
+    pub fn all(&self) -> Vec<Event> {
+        self.events.iter().cloned().collect()
+    }
+
+    /// Events added since the last call
+    pub fn take_new(&mut self) -> Vec<Event> {
+        let sent_id = self.sent_id;
+        self.sent_id = self.next_id;
+        self.events.iter().filter(|event| event.id >= sent_id).cloned().collect()
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+impl EventLog {
+    fn trim(&mut self) {
+        while self.events.len() > self.config.capacity.max(1) {
+            self.events.pop_front();
+        }
+    }
+}
+
+fn append_to_file(event: &Event) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(event_log_path()?)
+        .map_err(to_string)?;
+
+    writeln!(
+        file,
+        "{} {:?} {:?}: {}",
+        format_utc_time(event.time, "%Y-%m-%d %H:%M:%S"),
+        event.severity,
+        event.source,
+        event.message
+    )
+    .map_err(to_string)
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_events_are_dropped() {
+        let mut log = EventLog::new(EventLogConfig { capacity: 2, persist: false });
+
+        log.push(Severity::Info, EventSource::Client, "first");
+        assert_eq!(log.take_new().len(), 1);
+
+        log.push(Severity::Warning, EventSource::Storage, "second");
+        log.push(Severity::Error, EventSource::Camera, "third");
+
+        let new = log.take_new();
+        assert_eq!(new.iter().map(|event| event.id).collect::<Vec<_>>(), vec![2, 3]);
+        assert!(log.take_new().is_empty());
+        assert_eq!(log.all().first().map(|event| event.message.as_str()), Some("second"));
+    }
+}
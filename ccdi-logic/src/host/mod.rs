@@ -0,0 +1,137 @@
+use std::{path::Path, time::{Duration, Instant}};
+
+use ccdi_common::{read_text_file, HostStatus};
+
+// ============================================ PUBLIC =============================================
+
+/// Periodically reads temperature, throttling, load, memory and uptime of the host computer
+pub struct HostMonitor {
+    status: HostStatus,
+    last_read: Option<Instant>,
+}
+
+impl HostMonitor {
+    pub fn new() -> Self {
+        Self {
+            status: HostStatus::default(),
+            last_read: None,
+        }
+    }
+
+    /// Refresh host status if the read interval elapsed, returns true if status changed
+    pub fn periodic(&mut self) -> bool {
+        if let Some(last_read) = self.last_read {
+            if last_read.elapsed() < READ_INTERVAL {
+                return false;
+            }
+        }
+
+        self.last_read = Some(Instant::now());
+        let status = read_host_status();
+        let changed = status != self.status;
+        self.status = status;
+        changed
+    }
+
+    pub fn status(&self) -> &HostStatus {
+        &self.status
+    }
+}
+
+impl Default for HostMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+const READ_INTERVAL: Duration = Duration::from_secs(2);
+const THERMAL_ZONE_PATH: &str = "/sys/class/thermal/thermal_zone0/temp";
+const THROTTLED_PATH: &str = "/sys/devices/platform/soc/soc:firmware/get_throttled";
+const LOADAVG_PATH: &str = "/proc/loadavg";
+const MEMINFO_PATH: &str = "/proc/meminfo";
+const UPTIME_PATH: &str = "/proc/uptime";
+
+fn read_host_status() -> HostStatus {
+    HostStatus {
+        cpu_temperature: read_file(THERMAL_ZONE_PATH).and_then(|text| parse_temperature(&text)),
+        throttle_flags: read_file(THROTTLED_PATH).and_then(|text| parse_throttled(&text)),
+        load_average: read_file(LOADAVG_PATH).and_then(|text| parse_loadavg(&text)),
+        memory_total_megabytes: read_file(MEMINFO_PATH)
+            .and_then(|text| parse_meminfo_megabytes(&text, "MemTotal")),
+        memory_available_megabytes: read_file(MEMINFO_PATH)
+            .and_then(|text| parse_meminfo_megabytes(&text, "MemAvailable")),
+        uptime: read_file(UPTIME_PATH).and_then(|text| parse_uptime(&text)),
+    }
+}
+
+fn read_file(path: &str) -> Option<String> {
+    read_text_file(Path::new(path)).ok()
+}
+
+/// Thermal zone reports temperature in millidegrees celsius
+fn parse_temperature(text: &str) -> Option<f32> {
+    text.trim().parse::<f32>().ok().map(|value| value / 1000.0)
+}
+
+/// Accepts both sysfs format "50005" and vcgencmd format "throttled=0x50005"
+fn parse_throttled(text: &str) -> Option<u32> {
+    let value = text.trim();
+    let value = value.strip_prefix("throttled=").unwrap_or(value);
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    u32::from_str_radix(value, 16).ok()
+}
+
+fn parse_loadavg(text: &str) -> Option<[f32; 3]> {
+    let mut tokens = text.split_whitespace().map(|token| token.parse::<f32>().ok());
+    Some([tokens.next()??, tokens.next()??, tokens.next()??])
+}
+
+fn parse_meminfo_megabytes(text: &str, key: &str) -> Option<u64> {
+    text.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| *name == key)
+        .and_then(|(_, value)| value.split_whitespace().next())
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(|kilobytes| kilobytes / 1024)
+}
+
+fn parse_uptime(text: &str) -> Option<Duration> {
+    text.split_whitespace()
+        .next()
+        .and_then(|value| value.parse::<f64>().ok())
+        .map(Duration::from_secs_f64)
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    const TEST_MEMINFO: &str = indoc! {"
+        MemTotal:        3885520 kB
+        MemFree:          203628 kB
+        MemAvailable:    2611220 kB
+    "};
+
+    #[test]
+    fn parse_proc_files() {
+        assert_eq!(parse_meminfo_megabytes(TEST_MEMINFO, "MemTotal"), Some(3794));
+        assert_eq!(parse_meminfo_megabytes(TEST_MEMINFO, "MemAvailable"), Some(2550));
+        assert_eq!(parse_meminfo_megabytes(TEST_MEMINFO, "SwapTotal"), None);
+        assert_eq!(parse_loadavg("0.52 0.58 0.59 1/389 12345\n"), Some([0.52, 0.58, 0.59]));
+        assert_eq!(parse_uptime("350735.47 234388.90\n"), Some(Duration::from_secs_f64(350735.47)));
+        assert_eq!(parse_temperature("48312\n"), Some(48.312));
+    }
+
+    #[test]
+    fn parse_throttled_flags() {
+        assert_eq!(parse_throttled("throttled=0x50005\n"), Some(0x50005));
+        assert_eq!(parse_throttled("50005\n"), Some(0x50005));
+        assert_eq!(parse_throttled("0\n"), Some(0));
+        assert_eq!(parse_throttled("garbage"), None);
+    }
+}
@@ -1,4 +1,3 @@
-
 use std::path::{PathBuf, Path};
 
 use ccdi_common::append_to_file;
@@ -9,6 +8,7 @@ pub struct ProgrammableOutput {
     path: PathBuf,
     pattern: Vec<bool>,
     position: usize,
+    last_value: Option<bool>,
 }
 
 pub fn write_output(path: &Path, value: bool) -> Result<(), String> {
@@ -29,9 +29,13 @@ pub fn pattern_pwm(value: f32) -> Vec<bool> {
         .collect()
 }
 
-pub fn status_healthy() -> Vec<bool> {
-    vec![0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]
-        .into_iter().map(|value| value > 0).collect()
+/// Parse pattern written as a string of '0' and '1', other characters are skipped
+pub fn parse_pattern(pattern: &str) -> Vec<bool> {
+    pattern
+        .chars()
+        .filter(|c| *c == '0' || *c == '1')
+        .map(|c| c == '1')
+        .collect()
 }
 
 impl ProgrammableOutput {
@@ -40,14 +44,21 @@ impl ProgrammableOutput {
             path: PathBuf::from(path.to_owned()),
             pattern: vec![false],
             position: 0,
+            last_value: None,
         }
     }
 
+    pub fn set_path(&mut self, path: &str) {
+        self.path = PathBuf::from(path.to_owned());
+        self.last_value = None;
+    }
+
     pub fn set_pattern(&mut self, pattern: Vec<bool>) {
         self.pattern = pattern;
+        self.position = 0;
     }
 
-    #[allow(unused)]
+    /// Advance pattern by one step, the output is written only when its value changes
     pub fn iterate(&mut self) -> Result<(), String> {
         self.position += 1;
 
@@ -57,8 +68,11 @@ impl ProgrammableOutput {
 
         match self.pattern.get(self.position) {
             None => Ok(()),
-            Some(value) => write_output(&self.path, *value),
+            Some(value) if self.last_value == Some(*value) => Ok(()),
+            Some(value) => {
+                self.last_value = Some(*value);
+                write_output(&self.path, *value)
+            }
         }
     }
 }
-
@@ -1,13 +1,15 @@
 use std::path::{PathBuf, Path};
 
-use ccdi_common::{IoMessage, StateMessage, read_text_file};
+use ccdi_common::{IoMessage, StateMessage, StatusMode, read_text_file};
 use log::{debug, warn, info};
 
-use crate::IoConfig;
+use crate::{IoConfig, PowerConfig, StatusPatterns};
 
-use self::led_output::{write_output, ProgrammableOutput, pattern_pwm, status_healthy};
+use self::led_output::{write_output, ProgrammableOutput, pattern_pwm, parse_pattern};
+use self::power::PowerMonitor;
 
 mod led_output;
+mod power;
 
 // ============================================ PUBLIC =============================================
 
@@ -19,12 +21,16 @@ pub struct IoManager {
     exposure_status_path: PathBuf,
     heating_pwm: ProgrammableOutput,
     main_status: ProgrammableOutput,
+    status_patterns: StatusPatterns,
+    status_mode: StatusMode,
+    power: PowerMonitor,
 }
 
 impl IoManager {
-    pub fn new(config: &IoConfig) -> Self {
+    pub fn new(config: &IoConfig, power: &PowerConfig) -> Self {
+        let status_mode = StatusMode::Disconnected;
         let mut main_status = ProgrammableOutput::new(&config.main_status);
-        main_status.set_pattern(status_healthy());
+        main_status.set_pattern(parse_pattern(config.status_patterns.get(status_mode)));
 
         Self {
             last_trigger_value: None,
@@ -32,9 +38,23 @@ impl IoManager {
             exposure_status_path: PathBuf::from(config.exposure_status.clone()),
             heating_pwm: ProgrammableOutput::new(&config.heating_pwm),
             main_status,
+            status_patterns: config.status_patterns.clone(),
+            status_mode,
+            power: PowerMonitor::new(power),
         }
     }
 
+    /// Apply edited config, outputs are written to the new paths from the next change
+    pub fn update_config(&mut self, config: &IoConfig, power: &PowerConfig) {
+        self.trigger_input_path = PathBuf::from(config.trigger_input.clone());
+        self.exposure_status_path = PathBuf::from(config.exposure_status.clone());
+        self.heating_pwm.set_path(&config.heating_pwm);
+        self.main_status.set_path(&config.main_status);
+        self.status_patterns = config.status_patterns.clone();
+        self.main_status.set_pattern(parse_pattern(self.status_patterns.get(self.status_mode)));
+        self.power.update_config(power);
+    }
+
     pub fn process(&mut self, message: IoMessage) -> Result<Vec<StateMessage>, String> {
         match message {
             IoMessage::SetHeating(value) => {
@@ -44,8 +64,12 @@ impl IoManager {
             IoMessage::SetExposureActive(value) => {
                 let _ = write_output(&self.exposure_status_path, value);
             },
-            IoMessage::SetStatus(_) => {
-                self.main_status.set_pattern(status_healthy())
+            IoMessage::SetStatus(mode) => {
+                if mode != self.status_mode {
+                    debug!("Status mode {:?} -> {:?}", self.status_mode, mode);
+                    self.status_mode = mode;
+                    self.main_status.set_pattern(parse_pattern(self.status_patterns.get(mode)));
+                }
             },
         }
 
@@ -54,7 +78,7 @@ impl IoManager {
 
     pub fn periodic_tasks(&mut self) -> Result<Vec<StateMessage>, String> {
         // let _ = log_err("Set PWM", self.heating_pwm.iterate());
-        // let _ = log_err("Set Status", self.main_status.iterate());
+        let _ = self.main_status.iterate();
 
         // let prev_input = self.last_trigger_value;
         // let actual_input = read_input(&self.trigger_input_path);
@@ -74,7 +98,10 @@ impl IoManager {
         // };
 
         // Ok(output)
-        Ok(Vec::<StateMessage>::new())
+        Ok(match self.power.periodic() {
+            Some(status) => vec![StateMessage::UpdatePowerStatus(status)],
+            None => Vec::new(),
+        })
     }
 }
 
@@ -0,0 +1,287 @@
+use std::{
+    collections::VecDeque,
+    path::Path,
+    process::{Command, Stdio},
+    sync::mpsc::{channel, Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use ccdi_common::{read_text_file, to_string, PowerState, PowerStatus};
+use log::{info, warn};
+
+use crate::{PowerConfig, PowerSource};
+
+// ============================================ PUBLIC =============================================
+
+/// Measures supply voltage and decides whether it is safe to continue
+pub struct PowerMonitor {
+    config: PowerConfig,
+    samples: VecDeque<(Instant, f64)>,
+    last_read: Option<Instant>,
+    below_shutdown_since: Option<Instant>,
+    status: PowerStatus,
+    /// Result of the voltage script running on its own thread
+    script: Option<Receiver<Result<f64, String>>>,
+}
+
+impl PowerMonitor {
+    pub fn new(config: &PowerConfig) -> Self {
+        Self {
+            config: config.clone(),
+            samples: VecDeque::new(),
+            last_read: None,
+            below_shutdown_since: None,
+            status: PowerStatus::default(),
+            script: None,
+        }
+    }
+
+    /// Use new thresholds, collected samples are dropped when the voltage source changes
+    pub fn update_config(&mut self, config: &PowerConfig) {
+        if config.source != self.config.source {
+            self.samples.clear();
+            self.last_read = None;
+            self.below_shutdown_since = None;
+            self.status = PowerStatus::default();
+            self.script = None;
+        }
+
+        self.config = config.clone();
+    }
+
+    /// Read voltage if the read interval elapsed, returns new status if it was measured. The
+    /// script is not waited for, its result is returned by a later call.
+    pub fn periodic(&mut self) -> Option<PowerStatus> {
+        if self.config.source == PowerSource::Disabled {
+            return None;
+        }
+
+        if let Some(script) = self.script.take() {
+            return match script.try_recv() {
+                Ok(voltage) => Some(self.update_status(voltage)),
+                Err(TryRecvError::Empty) => {
+                    self.script = Some(script);
+                    None
+                }
+                Err(TryRecvError::Disconnected) => {
+                    Some(self.update_status(Err(String::from("Voltage script thread failed"))))
+                }
+            };
+        }
+
+        if let Some(last_read) = self.last_read {
+            if last_read.elapsed() < self.config.read_interval {
+                return None;
+            }
+        }
+
+        self.last_read = Some(Instant::now());
+
+        match &self.config.source {
+            PowerSource::Script { command } => {
+                self.script = Some(start_script(command.clone()));
+                None
+            }
+            source => {
+                let voltage = read_voltage(source);
+                Some(self.update_status(voltage))
+            }
+        }
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+impl PowerMonitor {
+    fn update_status(&mut self, voltage: Result<f64, String>) -> PowerStatus {
+        let now = Instant::now();
+
+        let voltage = match voltage {
+            Ok(voltage) => Some(voltage),
+            Err(error) => {
+                warn!("Could not read supply voltage: {}", error);
+                None
+            }
+        };
+
+        if let Some(voltage) = voltage {
+            self.samples.push_back((now, voltage));
+        }
+
+        while let Some((time, _)) = self.samples.front() {
+            match now.duration_since(*time) > self.config.trend_window {
+                true => self.samples.pop_front(),
+                false => break,
+            };
+        }
+
+        let state = self.evaluate_state(voltage, now);
+
+        if state != self.status.state {
+            info!("Power state {:?} -> {:?} ({:?} V)", self.status.state, state, voltage);
+        }
+
+        self.status = PowerStatus {
+            voltage,
+            trend: voltage_trend(&self.samples),
+            state,
+        };
+
+        self.status.clone()
+    }
+
+    fn evaluate_state(&mut self, voltage: Option<f64>, now: Instant) -> PowerState {
+        let voltage = match voltage {
+            None => return PowerState::Unknown,
+            Some(voltage) => voltage,
+        };
+
+        if voltage >= self.config.shutdown_voltage {
+            self.below_shutdown_since = None;
+        } else if self.below_shutdown_since.is_none() {
+            self.below_shutdown_since = Some(now);
+        }
+
+        match self.below_shutdown_since {
+            Some(since) if now.duration_since(since) >= self.config.shutdown_delay => {
+                PowerState::Critical
+            }
+            _ if voltage < self.config.warning_voltage => PowerState::Low,
+            _ => PowerState::Normal,
+        }
+    }
+}
+
+const SCRIPT_TIMEOUT: Duration = Duration::from_secs(2);
+const SCRIPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn read_voltage(source: &PowerSource) -> Result<f64, String> {
+    match source {
+        PowerSource::Disabled => Err(String::from("Power monitoring disabled")),
+        PowerSource::Iio { raw_path, scale, divider } => {
+            Ok(read_number(&read_text_file(Path::new(raw_path))?)? * scale / 1000.0 * divider)
+        }
+        PowerSource::Hwmon { path } => {
+            Ok(read_number(&read_text_file(Path::new(path))?)? / 1000.0)
+        }
+        PowerSource::Script { command } => run_script(command),
+    }
+}
+
+/// Run the script on its own thread so that LEDs and trigger keep working while it runs
+fn start_script(command: String) -> Receiver<Result<f64, String>> {
+    let (sender, receiver) = channel();
+
+    thread::spawn(move || {
+        let _ = sender.send(run_script(&command));
+    });
+
+    receiver
+}
+
+/// A hung script is killed, otherwise no other voltage reading is started
+fn run_script(command: &str) -> Result<f64, String> {
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(to_string)?;
+
+    let deadline = Instant::now() + SCRIPT_TIMEOUT;
+
+    while child.try_wait().map_err(to_string)?.is_none() {
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("Voltage script did not finish in {:?}", SCRIPT_TIMEOUT));
+        }
+
+        thread::sleep(SCRIPT_POLL_INTERVAL);
+    }
+
+    let output = child.wait_with_output().map_err(to_string)?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Voltage script failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    read_number(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn read_number(text: &str) -> Result<f64, String> {
+    text.split_whitespace()
+        .next()
+        .ok_or(String::from("Empty voltage value"))?
+        .parse::<f64>()
+        .map_err(to_string)
+}
+
+/// Least squares slope of the samples in volts per hour
+fn voltage_trend(samples: &VecDeque<(Instant, f64)>) -> Option<f64> {
+    let (start, _) = samples.front()?;
+
+    if samples.len() < 2 {
+        return None;
+    }
+
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|(time, voltage)| (time.duration_since(*start).as_secs_f64() / 3600.0, *voltage))
+        .collect();
+
+    let count = points.len() as f64;
+    let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / count;
+    let mean_v = points.iter().map(|(_, v)| v).sum::<f64>() / count;
+    let covariance: f64 = points.iter().map(|(t, v)| (t - mean_t) * (v - mean_v)).sum();
+    let variance: f64 = points.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+
+    match variance > 0.0 {
+        true => Some(covariance / variance),
+        false => None,
+    }
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_status_is_checked() {
+        assert_eq!(run_script("echo 12.5"), Ok(12.5));
+
+        let error = run_script("echo 12.5; echo broken >&2; exit 1").unwrap_err();
+        assert!(error.contains("broken"));
+    }
+
+    #[test]
+    fn script_does_not_block_periodic_tasks() {
+        let mut monitor = PowerMonitor::new(&PowerConfig {
+            source: PowerSource::Script { command: String::from("sleep 0.1; echo 12.5") },
+            ..Default::default()
+        });
+
+        let started = Instant::now();
+        assert_eq!(monitor.periodic(), None);
+        assert_eq!(monitor.periodic(), None);
+        assert!(started.elapsed() < Duration::from_millis(100));
+
+        while started.elapsed() < SCRIPT_TIMEOUT {
+            if let Some(status) = monitor.periodic() {
+                assert_eq!(status.voltage, Some(12.5));
+                return;
+            }
+
+            thread::sleep(SCRIPT_POLL_INTERVAL);
+        }
+
+        panic!("Voltage not read");
+    }
+}
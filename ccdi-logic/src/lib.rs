@@ -4,6 +4,11 @@ mod camera;
 mod config;
 mod storage;
 mod io;
+mod host;
+mod events;
+mod supervisor;
 
 pub use thread::*;
-pub use config::*;
\ No newline at end of file
+pub use config::*;
+pub use storage::{storage_channel, StorageReceiver, StorageSender};
+pub use supervisor::WorkerMonitor;
\ No newline at end of file
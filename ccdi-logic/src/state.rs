@@ -1,8 +1,14 @@
 use std::sync::{mpsc::Sender, Arc};
 
-use ccdi_common::{ClientMessage, IoMessage, ProcessMessage, StateMessage, StorageMessage};
+use ccdi_common::{
+    ClientCommand, ClientMessage, CommandResult, ConfigMessage, EventSource, IoMessage,
+    ProcessMessage, Severity, StateMessage, StorageMessage,
+};
 
-use crate::{camera::CameraController, ServiceConfig};
+use crate::{
+    camera::CameraController, parse_config_layers, read_config_file, save_config_file,
+    storage::validate_storage_command, supervisor::WorkerMonitor, ConfigWatcher, SharedConfig, StorageSender,
+};
 use log::info;
 
 // ============================================ PUBLIC =============================================
@@ -11,37 +17,30 @@ pub struct BackendState {
     camera: CameraController,
     /// Last image sent to clients
     image: Option<Arc<Vec<u8>>>,
+    config: SharedConfig,
+    watcher: ConfigWatcher,
 }
 
 impl BackendState {
     pub fn new(
         demo_mode: &str,
         process_tx: Sender<ProcessMessage>,
-        storage_tx: Sender<StorageMessage>,
-        config: Arc<ServiceConfig>,
-    ) -> Self {
-        Self {
+        storage_tx: StorageSender,
+        config: SharedConfig,
+        workers: WorkerMonitor,
+    ) -> Result<Self, String> {
+        Ok(Self {
             camera: CameraController::new(
-                match demo_mode {
-                    #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
-                    "asi" => {
-                        let mut drv = ccdi_imager_asicam::ASICameraDriver::new();
-                        drv.update_opt_config(config.exp.get_optimum_exp_config().unwrap());
-                        Box::new(drv)
-                    },
-                    "fli" => {
-                        let mut drv = ccdi_imager_fli::FLICameraDriver::new();
-                        drv.update_opt_config(config.exp.get_optimum_exp_config().unwrap());
-                        Box::new(drv)
-                    }
-                    _ => Box::new(ccdi_imager_demo::DemoImagerDriver::new()),
-                },
+                demo_mode,
                 process_tx,
                 storage_tx,
-                config,
-            ),
+                config.clone(),
+                workers,
+            )?,
             image: None,
-        }
+            watcher: ConfigWatcher::new(config.clone()),
+            config,
+        })
     }
 
     // This is where messages are processed by the server. ~Mit
@@ -50,6 +49,7 @@ impl BackendState {
         use StateMessage::*;
 
         Ok(match message {
+            Command(command) => self.process_command(command),
             ClientInformation(info) => {
                 let (kind, msg) = info;
                 // Do something with the information.
@@ -66,7 +66,7 @@ impl BackendState {
                     _ => None,
                 };
 
-                self.camera.update_camera_params(message);
+                self.camera.update_camera_params(message)?;
 
                 match heating {
                     None => self.return_view(),
@@ -82,21 +82,22 @@ impl BackendState {
             ImageParam(message) => {
                 log::debug!("ImageParam message: {:?}", message);
 
-                self.camera.update_image_params(message);
-                // TODO: Do something with the exact type of image param message
-
+                self.camera.update_image_params(message)?;
                 BackendResult::empty()
             }
             ExposureMessage(command) => {
-                self.camera.exposure_command(command);
+                self.camera.exposure_command(command)?;
                 self.return_view()
             }
             ClientConnected => {
                 let view_msg = ClientMessage::View(Box::new(self.camera.get_view()));
+                let events_msg = ClientMessage::Events(self.camera.events());
 
                 BackendResult::client(match self.image.as_ref() {
-                    None => vec![view_msg],
-                    Some(image) => vec![view_msg, ClientMessage::PngImage(image.clone())],
+                    None => vec![view_msg, events_msg],
+                    Some(image) => {
+                        vec![view_msg, events_msg, ClientMessage::PngImage(image.clone())]
+                    }
                 })
             }
             UpdateStorageState(storage_state) => {
@@ -109,27 +110,69 @@ impl BackendState {
                 let (client, io) = self.camera.periodic();
                 BackendResult::client_io(client, io)
             }
-            StorageMessage(message) => BackendResult {
-                client_messages: Vec::new(),
-                storage_messages: vec![message],
-                io_messages: Vec::new(),
-            },
+            StorageMessage(message) => {
+                validate_storage_command(&message)?;
+
+                BackendResult {
+                    client_messages: Vec::new(),
+                    storage_messages: vec![message],
+                    io_messages: Vec::new(),
+                }
+            }
+            RetentionDeleted(records) => {
+                self.camera.retention_deleted(records);
+                self.return_view()
+            }
             UpdateStorageDetail(detail) => {
-                self.camera.update_storage_detail(detail);
+                self.camera.update_storage_detail(*detail);
                 self.return_view()
             }
+            UpdatePowerStatus(status) => {
+                self.camera.update_power_status(status);
+                self.return_view()
+            }
+            Preset(message) => {
+                self.camera.preset_command(message)?;
+                self.return_view()
+            }
+            Config(ConfigMessage::Load) => {
+                BackendResult::client(vec![ClientMessage::ConfigText(read_config_file()?)])
+            }
+            Config(ConfigMessage::Save(text)) => {
+                self.save_config(&text)
+                    .map_err(|error| format!("Config not saved: {}", error))?;
+
+                self.camera.event(Severity::Info, EventSource::Client, "Config saved and applied");
+
+                let mut result = self.return_view();
+                result.client_messages.push(ClientMessage::ConfigText(read_config_file()?));
+                result
+            }
             PowerOff => {
-                self.camera.turn_off();
-                BackendResult::empty()
+                self.camera.shutdown(true);
+                self.return_view()
+            }
+            Shutdown => {
+                self.camera.shutdown(false);
+                self.return_view()
             }
         })
     }
 
     /// Called periodically to perform any tasks needed and return messages for clients
     pub fn periodic(&mut self) -> Result<BackendResult, String> {
+        if let Some((severity, detail)) = self.watcher.periodic() {
+            self.camera.event(severity, EventSource::System, &detail);
+        }
+
         let (client, io) = self.camera.periodic();
         Ok(BackendResult::client_io(client, io))
     }
+
+    /// Shutdown sequence finished, the logic thread should exit
+    pub fn finished(&self) -> bool {
+        self.camera.finished()
+    }
 }
 
 pub struct BackendResult {
@@ -167,6 +210,44 @@ impl BackendResult {
 // =========================================== PRIVATE =============================================
 
 impl BackendState {
+    /// Validate and process message sent from a client and append the result of the command for
+    /// the client, rejected and adjusted commands are also recorded in the event log
+    fn process_command(&mut self, command: ClientCommand) -> BackendResult {
+        let processed = client_message(*command.message)
+            .and_then(|message| self.camera.validate_command(message))
+            .and_then(|(message, reason)| Ok((self.process(message)?, reason)));
+
+        let (mut response, result) = match processed {
+            Ok((response, reason)) => {
+                if let Some(reason) = reason.as_ref() {
+                    self.camera.event(Severity::Warning, EventSource::Client, reason);
+                }
+                (response, Ok(reason))
+            }
+            Err(error) => {
+                self.camera.event(Severity::Warning, EventSource::Client, &error);
+                (self.return_view(), Err(error))
+            }
+        };
+
+        response.client_messages.push(ClientMessage::CommandResult(CommandResult {
+            id: command.id,
+            connection: command.connection,
+            result,
+        }));
+
+        response
+    }
+
+    /// Validate config edited in the client with overrides applied, save only the file layer so
+    /// that environment and command line overrides keep their precedence
+    fn save_config(&mut self, text: &str) -> Result<(), String> {
+        let layers = parse_config_layers(text)?;
+        save_config_file(&layers.file)?;
+        self.config.set(Arc::new(layers.effective));
+        Ok(())
+    }
+
     fn return_view(&self) -> BackendResult {
         BackendResult {
             client_messages: vec![ClientMessage::View(Box::new(self.camera.get_view()))],
@@ -175,3 +256,36 @@ impl BackendState {
         }
     }
 }
+
+/// Messages a client may send inside a command, the rest is sent only by the service threads
+fn client_message(message: StateMessage) -> Result<StateMessage, String> {
+    use StateMessage::*;
+
+    match message {
+        ClientInformation(_) | ExposureMessage(_) | ImageParam(_) | CameraParam(_)
+        | ClientConnected | StorageMessage(_) | Preset(_) | Config(_) | PowerOff => Ok(message),
+        Command(_) | ImageDisplayed(_) | UpdateStorageState(_) | TriggerValueChanged(_)
+        | UpdateStorageDetail(_) | RetentionDeleted(_) | UpdatePowerStatus(_) | Shutdown => {
+            Err(format!("Message not allowed from a client: {:?}", message))
+        }
+    }
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use ccdi_common::{CameraParamMessage, ClientCommand};
+
+    use super::*;
+
+    #[test]
+    fn internal_messages_are_refused_from_clients() {
+        let param = StateMessage::CameraParam(CameraParamMessage::SetGain(100));
+        assert_eq!(client_message(param.clone()), Ok(param.clone()));
+        assert!(client_message(StateMessage::Shutdown).is_err());
+
+        let nested = ClientCommand { id: 1, connection: 0, message: Box::new(param) };
+        assert!(client_message(StateMessage::Command(nested)).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::Path;
+
+use cameraunit::{DynamicSerialImage, Primitive, SerialImageBuffer};
+use ccdi_common::{to_string, FitsCompression, RawImage};
+use fitsio::{
+    images::{ImageDescription, ImageType, WriteImage},
+    FitsFile,
+};
+
+use crate::KeywordValue;
+
+use super::header::HeaderCard;
+
+// ============================================ PUBLIC =============================================
+
+/// Write image to a new FITS file, color images are stored as a cube of red, green and blue.
+/// Compressed images are stored in the first extension after an empty primary HDU.
+pub fn write_fits_file(
+    image: &RawImage,
+    path: &Path,
+    cards: &[HeaderCard],
+    compression: FitsCompression,
+) -> Result<(), String> {
+    let float = matches!(image.data, DynamicSerialImage::F32(_));
+    let file = FitsTarget { path, compression: compression_spec(compression, float) };
+
+    match &image.data {
+        DynamicSerialImage::U8(buffer) => {
+            write_buffer(buffer, ImageType::UnsignedByte, file, cards)
+        }
+        DynamicSerialImage::U16(buffer) => {
+            write_buffer(buffer, ImageType::UnsignedShort, file, cards)
+        }
+        DynamicSerialImage::F32(buffer) => write_buffer(buffer, ImageType::Float, file, cards),
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+struct FitsTarget<'a> {
+    path: &'a Path,
+    /// Compression in cfitsio extended file name syntax
+    compression: Option<&'static str>,
+}
+
+/// Rice quantizes float images, they are compressed by gzip without quantization instead
+fn compression_spec(compression: FitsCompression, float: bool) -> Option<&'static str> {
+    match (compression, float) {
+        (FitsCompression::None, _) => None,
+        (_, true) => Some("G; q 0"),
+        (FitsCompression::Rice, false) => Some("R"),
+        (FitsCompression::Gzip, false) => Some("G"),
+    }
+}
+
+fn write_buffer<T: Primitive + WriteImage>(
+    buffer: &SerialImageBuffer<T>,
+    data_type: ImageType,
+    target: FitsTarget,
+    cards: &[HeaderCard],
+) -> Result<(), String> {
+    let (width, height) = (buffer.width(), buffer.height());
+
+    let (dimensions, data) = match (buffer.get_luma(), buffer.get_red()) {
+        (Some(luma), _) => (vec![height, width], luma.clone()),
+        (None, Some(red)) => {
+            let mut data = red.clone();
+            data.extend_from_slice(buffer.get_green().ok_or("Green channel missing")?);
+            data.extend_from_slice(buffer.get_blue().ok_or("Blue channel missing")?);
+            (vec![3, height, width], data)
+        }
+        (None, None) => return Err(String::from("Image without channels")),
+    };
+
+    let description = ImageDescription { data_type, dimensions: &dimensions };
+
+    let (mut file, hdu) = match target.compression {
+        None => {
+            let mut file = FitsFile::create(target.path)
+                .with_custom_primary(&description)
+                .open()
+                .map_err(to_string)?;
+            let hdu = file.primary_hdu().map_err(to_string)?;
+            (file, hdu)
+        }
+        Some(compression) => {
+            let path = target.path.to_str().ok_or("File path is not valid UTF-8")?;
+            let mut file = FitsFile::create(format!("{}[compress {}]", path, compression))
+                .open()
+                .map_err(to_string)?;
+            let hdu = file.create_image("COMPRESSED_IMAGE", &description).map_err(to_string)?;
+            (file, hdu)
+        }
+    };
+
+    hdu.write_image(&mut file, &data).map_err(to_string)?;
+
+    for card in cards {
+        let comment = card.comment.as_str();
+        let key = card.key.as_str();
+
+        match &card.value {
+            KeywordValue::Integer(value) => hdu.write_key(&mut file, key, (*value, comment)),
+            KeywordValue::Float(value) => hdu.write_key(&mut file, key, (*value, comment)),
+            KeywordValue::Text(value) => hdu.write_key(&mut file, key, (value.as_str(), comment)),
+        }
+        .map_err(|error| format!("Header {}: {}", key, error))?;
+    }
+
+    Ok(())
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::storage::tests::test_image;
+
+    use super::*;
+
+    #[test]
+    fn compressed_files_read_back_losslessly() {
+        let dir = std::env::temp_dir().join(format!("ccdi-fits-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let pixels = (0..64 * 32).map(|value| (value % 512) as u16).collect::<Vec<_>>();
+        let image = test_image(DynamicSerialImage::from_vec_u16(64, 32, pixels.clone()).unwrap());
+
+        for compression in [FitsCompression::Rice, FitsCompression::Gzip] {
+            let path = dir.join(format!("{:?}.fits", compression));
+            write_fits_file(&image, &path, &[], compression).unwrap();
+
+            let mut file = FitsFile::open(&path).unwrap();
+            let hdu = file.hdu(1).unwrap();
+            assert_eq!(hdu.read_image::<Vec<u16>>(&mut file).unwrap(), pixels);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
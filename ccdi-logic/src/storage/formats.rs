@@ -0,0 +1,414 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use cameraunit::{DynamicSerialImage, Primitive, SerialImageBuffer};
+use ccdi_common::{format_utc_time, to_string, FitsCompression, OutputFormat, RawImage};
+use tiff::{
+    encoder::{colortype, TiffEncoder},
+    tags::Tag,
+};
+
+use crate::KeywordValue;
+
+use super::{fits::write_fits_file, header::HeaderCard};
+
+// ============================================ PUBLIC =============================================
+
+/// Write image in the format to a new file, metadata is kept as far as the format allows
+pub fn write_image_file(
+    format: OutputFormat,
+    image: &RawImage,
+    path: &Path,
+    cards: &[HeaderCard],
+    compression: FitsCompression,
+) -> Result<(), String> {
+    match format {
+        OutputFormat::Fits => write_fits_file(image, path, cards, compression),
+        OutputFormat::Tiff => write_tiff_file(image, path, cards),
+        OutputFormat::Png => write_png_file(image, path, cards),
+        OutputFormat::Xisf => write_xisf_file(image, path, cards),
+        OutputFormat::Sidecar => write_sidecar_file(path, cards),
+    }
+}
+
+/// Gray plane or red, green and blue planes of the image
+pub fn channel_planes<T: Primitive>(buffer: &SerialImageBuffer<T>) -> Result<Vec<&Vec<T>>, String> {
+    match (buffer.get_luma(), buffer.get_red(), buffer.get_green(), buffer.get_blue()) {
+        (Some(luma), _, _, _) => Ok(vec![luma]),
+        (None, Some(red), Some(green), Some(blue)) => Ok(vec![red, green, blue]),
+        _ => Err(String::from("Image without gray or RGB channels")),
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+/// Image converted to 16 bits, one plane for gray images, red, green and blue planes for color
+struct Planes16 {
+    width: usize,
+    height: usize,
+    planes: Vec<Vec<u16>>,
+}
+
+impl Planes16 {
+    fn new(data: &DynamicSerialImage) -> Result<Self, String> {
+        let (width, height) = (data.width(), data.height());
+
+        let planes = match data {
+            DynamicSerialImage::U8(buffer) => convert_planes(buffer, |value| value as u16 * 257)?,
+            DynamicSerialImage::U16(buffer) => convert_planes(buffer, |value| value)?,
+            DynamicSerialImage::F32(buffer) => convert_planes(buffer, |value| {
+                (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+            })?,
+        };
+
+        Ok(Self { width, height, planes })
+    }
+
+    fn is_color(&self) -> bool {
+        self.planes.len() == 3
+    }
+
+    /// Samples of all planes interleaved pixel by pixel
+    fn interleaved(&self) -> Vec<u16> {
+        match self.planes.as_slice() {
+            [gray] => gray.clone(),
+            planes => (0..self.width * self.height)
+                .flat_map(|index| planes.iter().map(move |plane| plane[index]))
+                .collect(),
+        }
+    }
+}
+
+fn convert_planes<T: Primitive>(
+    buffer: &SerialImageBuffer<T>,
+    convert: impl Fn(T) -> u16,
+) -> Result<Vec<Vec<u16>>, String> {
+    Ok(channel_planes(buffer)?
+        .into_iter()
+        .map(|plane| plane.iter().map(|value| convert(*value)).collect())
+        .collect())
+}
+
+fn create_file(path: &Path) -> Result<BufWriter<File>, String> {
+    File::create(path).map(BufWriter::new).map_err(to_string)
+}
+
+/// Header cards as text for formats which can only keep a description
+fn cards_text(cards: &[HeaderCard]) -> String {
+    cards
+        .iter()
+        .map(|card| format!("{} = {}", card.key, value_text(&card.value)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn value_text(value: &KeywordValue) -> String {
+    match value {
+        KeywordValue::Integer(value) => value.to_string(),
+        KeywordValue::Float(value) => value.to_string(),
+        KeywordValue::Text(value) => value.clone(),
+    }
+}
+
+fn find_text<'a>(cards: &'a [HeaderCard], key: &str) -> &'a str {
+    cards
+        .iter()
+        .find(|card| card.key == key)
+        .and_then(|card| match &card.value {
+            KeywordValue::Text(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .unwrap_or("")
+}
+
+fn write_tiff_file(image: &RawImage, path: &Path, cards: &[HeaderCard]) -> Result<(), String> {
+    let planes = Planes16::new(&image.data)?;
+    let (width, height) = (planes.width as u32, planes.height as u32);
+    let data = planes.interleaved();
+    let date = format_utc_time(image.context.start, "%Y:%m:%d %H:%M:%S");
+    let description = cards_text(cards);
+    let mut encoder = TiffEncoder::new(create_file(path)?).map_err(to_string)?;
+
+    macro_rules! write_tiff {
+        ($color:ty) => {{
+            let mut tiff = encoder.new_image::<$color>(width, height).map_err(to_string)?;
+            let directory = tiff.encoder();
+            directory.write_tag(Tag::ImageDescription, description.as_str()).map_err(to_string)?;
+            directory.write_tag(Tag::Software, "ccdi").map_err(to_string)?;
+            directory.write_tag(Tag::Model, find_text(cards, "INSTRUME")).map_err(to_string)?;
+            directory.write_tag(Tag::DateTime, date.as_str()).map_err(to_string)?;
+            tiff.write_data(&data).map_err(to_string)
+        }};
+    }
+
+    match planes.is_color() {
+        false => write_tiff!(colortype::Gray16),
+        true => write_tiff!(colortype::RGB16),
+    }
+}
+
+fn write_png_file(image: &RawImage, path: &Path, cards: &[HeaderCard]) -> Result<(), String> {
+    let planes = Planes16::new(&image.data)?;
+    let mut encoder =
+        png::Encoder::new(create_file(path)?, planes.width as u32, planes.height as u32);
+
+    encoder.set_color(match planes.is_color() {
+        false => png::ColorType::Grayscale,
+        true => png::ColorType::Rgb,
+    });
+    encoder.set_depth(png::BitDepth::Sixteen);
+    encoder.add_text_chunk(String::from("Software"), String::from("ccdi")).map_err(to_string)?;
+
+    for card in cards {
+        encoder.add_text_chunk(card.key.clone(), value_text(&card.value)).map_err(to_string)?;
+    }
+
+    // PNG samples are big endian
+    let samples = planes.interleaved();
+    let bytes: Vec<u8> = samples.iter().flat_map(|value| value.to_be_bytes()).collect();
+    let mut writer = encoder.write_header().map_err(to_string)?;
+    writer.write_image_data(&bytes).map_err(to_string)?;
+    writer.finish().map_err(to_string)
+}
+
+/// Monolithic XISF 1.0 file: signature, XML header and a single attached image block
+fn write_xisf_file(image: &RawImage, path: &Path, cards: &[HeaderCard]) -> Result<(), String> {
+    let (sample_format, planes, data) = match &image.data {
+        DynamicSerialImage::U8(buffer) => xisf_data(buffer, "UInt8", |value| [value])?,
+        DynamicSerialImage::U16(buffer) => xisf_data(buffer, "UInt16", u16::to_le_bytes)?,
+        DynamicSerialImage::F32(buffer) => xisf_data(buffer, "Float32", f32::to_le_bytes)?,
+    };
+
+    let header = |offset: usize| {
+        xisf_header(image, sample_format, planes, (offset, data.len()), cards)
+    };
+
+    // The header contains the data offset, data starts at a block boundary after the header
+    let offset = (XISF_PREFIX + header(0).len() + 32).next_multiple_of(XISF_BLOCK);
+    let header = header(offset);
+
+    let mut file = create_file(path)?;
+    file.write_all(b"XISF0100").map_err(to_string)?;
+    file.write_all(&(header.len() as u32).to_le_bytes()).map_err(to_string)?;
+    file.write_all(&[0; 4]).map_err(to_string)?;
+    file.write_all(header.as_bytes()).map_err(to_string)?;
+    file.write_all(&vec![0; offset - XISF_PREFIX - header.len()]).map_err(to_string)?;
+    file.write_all(&data).map_err(to_string)?;
+    file.flush().map_err(to_string)
+}
+
+/// Signature, header length and reserved field
+const XISF_PREFIX: usize = 16;
+const XISF_BLOCK: usize = 4096;
+
+/// Sample format, number of planes and little endian samples stored plane by plane
+fn xisf_data<T: Primitive, B: IntoIterator<Item = u8>>(
+    buffer: &SerialImageBuffer<T>,
+    sample_format: &'static str,
+    bytes: impl Fn(T) -> B,
+) -> Result<(&'static str, usize, Vec<u8>), String> {
+    let planes = channel_planes(buffer)?;
+    let data = planes.iter().flat_map(|plane| plane.iter().flat_map(|value| bytes(*value)));
+    Ok((sample_format, planes.len(), data.collect()))
+}
+
+fn xisf_header(
+    image: &RawImage,
+    sample_format: &str,
+    planes: usize,
+    (offset, size): (usize, usize),
+    cards: &[HeaderCard],
+) -> String {
+    let keywords = cards
+        .iter()
+        .map(|card| {
+            let value = match &card.value {
+                KeywordValue::Text(text) => format!("'{}'", text),
+                value => value_text(value),
+            };
+
+            format!(
+                "<FITSKeyword name=\"{}\" value=\"{}\" comment=\"{}\"/>",
+                xml_escape(&card.key),
+                xml_escape(&value),
+                xml_escape(&card.comment)
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>",
+            "<xisf version=\"1.0\" xmlns=\"http://www.pixinsight.com/xisf\">",
+            "<Image geometry=\"{}:{}:{}\" sampleFormat=\"{}\"{} colorSpace=\"{}\" ",
+            "location=\"attachment:{}:{}\">{}</Image>",
+            "<Metadata><Property id=\"XISF:CreatorApplication\" type=\"String\">ccdi</Property>",
+            "</Metadata></xisf>"
+        ),
+        image.data.width(),
+        image.data.height(),
+        planes,
+        sample_format,
+        if sample_format == "Float32" { " bounds=\"0:1\"" } else { "" },
+        if planes == 3 { "RGB" } else { "Gray" },
+        offset,
+        size,
+        keywords
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_sidecar_file(path: &Path, cards: &[HeaderCard]) -> Result<(), String> {
+    let mut file = create_file(path)?;
+    serde_json::to_writer_pretty(&mut file, cards).map_err(to_string)?;
+    file.flush().map_err(to_string)
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::BufReader, path::PathBuf};
+
+    use tiff::decoder::{Decoder, DecodingResult};
+
+    use crate::storage::tests::test_image;
+
+    use super::*;
+
+    const WIDTH: usize = 5;
+    const HEIGHT: usize = 3;
+
+    fn image(samples: Vec<u16>) -> RawImage {
+        test_image(DynamicSerialImage::from_vec_u16(WIDTH, HEIGHT, samples).unwrap())
+    }
+
+    /// Gray samples and interleaved RGB samples covering the whole 16-bit range
+    fn test_images() -> Vec<(RawImage, Vec<u16>)> {
+        let gray = (0..WIDTH * HEIGHT).map(|index| (index * 4099) as u16).collect::<Vec<_>>();
+        let rgb = (0..WIDTH * HEIGHT * 3).map(|index| (index * 1409) as u16).collect::<Vec<_>>();
+        vec![(image(gray.clone()), gray), (image(rgb.clone()), rgb)]
+    }
+
+    fn cards() -> Vec<HeaderCard> {
+        vec![HeaderCard {
+            key: String::from("OBSERVER"),
+            value: KeywordValue::Text(String::from("<Tom & Jerry>")),
+            comment: String::from("Observer name"),
+        }]
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ccdi-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn xml_values_are_escaped() {
+        assert_eq!(xml_escape("a<b & \"c\">"), "a&lt;b &amp; &quot;c&quot;&gt;");
+    }
+
+    #[test]
+    fn tiff_files_read_back() {
+        let dir = temp_dir("tiff");
+
+        for (image, samples) in test_images() {
+            let path = dir.join("frame.tiff");
+            write_tiff_file(&image, &path, &cards()).unwrap();
+
+            let mut decoder = Decoder::new(File::open(&path).unwrap()).unwrap();
+            assert_eq!(decoder.dimensions().unwrap(), (WIDTH as u32, HEIGHT as u32));
+
+            match decoder.read_image().unwrap() {
+                DecodingResult::U16(decoded) => assert_eq!(decoded, samples),
+                _ => panic!("TIFF samples are not 16-bit"),
+            }
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn png_files_read_back() {
+        let dir = temp_dir("png");
+
+        for (image, samples) in test_images() {
+            let path = dir.join("frame.png");
+            write_png_file(&image, &path, &cards()).unwrap();
+
+            let decoder = png::Decoder::new(BufReader::new(File::open(&path).unwrap()));
+            let mut reader = decoder.read_info().unwrap();
+            let mut buffer = vec![0; reader.output_buffer_size().unwrap()];
+            let info = reader.next_frame(&mut buffer).unwrap();
+            assert_eq!((info.width, info.height), (WIDTH as u32, HEIGHT as u32));
+            assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+
+            let decoded = buffer[..info.buffer_size()]
+                .chunks(2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+                .collect::<Vec<_>>();
+            assert_eq!(decoded, samples);
+
+            let text = &reader.info().uncompressed_latin1_text;
+            assert!(text.iter().any(|chunk| chunk.keyword == "OBSERVER"));
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn xisf_attachment_points_at_data() {
+        let dir = temp_dir("xisf");
+
+        for (image, samples) in test_images() {
+            let path = dir.join("frame.xisf");
+            write_xisf_file(&image, &path, &cards()).unwrap();
+            let bytes = fs::read(&path).unwrap();
+            assert_eq!(&bytes[..8], b"XISF0100");
+
+            let length = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+            let header = std::str::from_utf8(&bytes[XISF_PREFIX..XISF_PREFIX + length]).unwrap();
+            assert!(header.starts_with("<?xml") && header.ends_with("</xisf>"));
+            assert!(header.contains("&lt;Tom &amp; Jerry&gt;"));
+
+            let location = header.split("location=\"attachment:").nth(1).unwrap();
+            let (offset, rest) = location.split_once(':').unwrap();
+            let size = rest.split('"').next().unwrap();
+            let (offset, size): (usize, usize) = (offset.parse().unwrap(), size.parse().unwrap());
+            assert_eq!(offset % XISF_BLOCK, 0);
+            assert_eq!(offset + size, bytes.len());
+
+            // XISF stores planes one after another, the test samples are interleaved
+            let planes = samples.len() / (WIDTH * HEIGHT);
+            let expected = (0..planes)
+                .flat_map(|plane| samples.iter().skip(plane).step_by(planes))
+                .flat_map(|value| value.to_le_bytes())
+                .collect::<Vec<_>>();
+            assert_eq!(&bytes[offset..], expected.as_slice());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sidecar_contains_cards() {
+        let dir = temp_dir("sidecar");
+        let path = dir.join("frame.json");
+        write_sidecar_file(&path, &cards()).unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(json[0]["key"], "OBSERVER");
+        assert_eq!(json[0]["value"], "<Tom & Jerry>");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
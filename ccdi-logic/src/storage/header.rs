@@ -0,0 +1,132 @@
+use std::time::{Duration, SystemTime};
+
+use ccdi_common::{format_utc_time, RawImage};
+use serde_derive::Serialize;
+
+use crate::{FitsConfig, KeywordValue};
+
+// ============================================ PUBLIC =============================================
+
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct HeaderCard {
+    pub key: String,
+    pub value: KeywordValue,
+    pub comment: String,
+}
+
+/// Standard keywords filled from the service state followed by static keywords from config
+pub fn header_cards(image: &RawImage, frame_type: &str, config: &FitsConfig) -> Vec<HeaderCard> {
+    use KeywordValue::*;
+
+    let params = &image.params;
+    let context = &image.context;
+    let metadata = image.data.get_metadata();
+    let binning = metadata
+        .as_ref()
+        .map(|meta| (meta.bin_x.max(1), meta.bin_y.max(1)))
+        .unwrap_or((1, 1));
+    let end = context.start + Duration::from_secs_f64(params.time.max(0.0));
+
+    let mut cards = vec![
+        card("INSTRUME", Text(context.instrument.clone()), "Camera which took the frame"),
+        card("SWCREATE", Text(String::from("ccdi")), "Software which saved the file"),
+        card("IMAGETYP", Text(image_type(frame_type)), "Type of the frame"),
+        card("DATE-OBS", Text(iso_time(context.start)), "UTC start of the exposure"),
+        card("DATE-END", Text(iso_time(end)), "UTC end of the exposure"),
+        card("EXPTIME", Float(params.time), "Exposure time in seconds"),
+        card("GAIN", Integer(params.gain as i64), "Camera gain"),
+        card("SET-TEMP", Float(context.target_temperature), "Requested sensor temperature in C"),
+        card("XBINNING", Integer(binning.0 as i64), "Horizontal binning"),
+        card("YBINNING", Integer(binning.1 as i64), "Vertical binning"),
+        card("XORGSUBF", Integer(params.area.x as i64), "ROI start in sensor pixels"),
+        card("YORGSUBF", Integer(params.area.y as i64), "ROI start in sensor pixels"),
+        card("FLIPX", Integer(params.flipx as i64), "Image flipped horizontally"),
+        card("FLIPY", Integer(params.flipy as i64), "Image flipped vertically"),
+        card("HEATDUTY", Float(context.heating_pwm), "Heater duty cycle 0 - 1"),
+    ];
+
+    if let Some(meta) = metadata.as_ref() {
+        cards.push(card("CCD-TEMP", Float(meta.temperature as f64), "Sensor temperature in C"));
+    }
+
+    cards.extend(config.keywords.iter().map(|(key, value)| HeaderCard {
+        key: key.clone(),
+        value: value.clone(),
+        comment: String::new(),
+    }));
+
+    cards
+}
+
+/// Static keywords must be valid FITS keywords and must not replace keywords written by ccdi
+pub fn validate_keyword(key: &str) -> Result<(), String> {
+    let valid_chars = key
+        .chars()
+        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-' || c == '_');
+
+    if key.is_empty() || key.len() > 8 || !valid_chars {
+        Err(String::from("must be 1 - 8 characters A-Z, 0-9, - or _"))
+    } else if RESERVED.contains(&key) {
+        Err(String::from("is written by the service"))
+    } else {
+        Ok(())
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+const RESERVED: &[&str] = &[
+    "SIMPLE", "BITPIX", "NAXIS", "NAXIS1", "NAXIS2", "NAXIS3", "EXTEND", "BZERO", "BSCALE", "END",
+    "INSTRUME", "SWCREATE", "IMAGETYP", "DATE-OBS", "DATE-END", "EXPTIME", "GAIN", "SET-TEMP",
+    "CCD-TEMP", "XBINNING", "YBINNING", "XORGSUBF", "YORGSUBF", "FLIPX", "FLIPY", "HEATDUTY",
+];
+
+fn card(key: &str, value: KeywordValue, comment: &str) -> HeaderCard {
+    HeaderCard {
+        key: key.to_owned(),
+        value,
+        comment: comment.to_owned(),
+    }
+}
+
+/// Frame types in the form understood by Siril and PixInsight
+fn image_type(frame_type: &str) -> String {
+    match frame_type.to_lowercase().as_str() {
+        "light" => String::from("Light Frame"),
+        "dark" => String::from("Dark Frame"),
+        "flat" => String::from("Flat Field"),
+        "bias" => String::from("Bias Frame"),
+        _ => frame_type.to_owned(),
+    }
+}
+
+/// UTC time with milliseconds, e.g. 2024-03-01T21:15:07.250
+fn iso_time(time: SystemTime) -> String {
+    format_utc_time(time, "%Y-%m-%dT%H:%M:%S%.3f")
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    #[test]
+    fn time_and_frame_type_are_formatted() {
+        let time = UNIX_EPOCH + Duration::from_millis(1709327707250);
+        assert_eq!(iso_time(time), "2024-03-01T21:15:07.250");
+        assert_eq!(image_type("dark"), "Dark Frame");
+        assert_eq!(image_type("custom"), "custom");
+    }
+
+    #[test]
+    fn static_keywords_are_checked() {
+        assert!(validate_keyword("OBSERVER").is_ok());
+        assert!(validate_keyword("FOCALLEN").is_ok());
+        assert!(validate_keyword("observer").is_err());
+        assert!(validate_keyword("TELESCOPE").is_err());
+        assert!(validate_keyword("EXPTIME").is_err());
+    }
+}
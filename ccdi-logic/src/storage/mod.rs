@@ -1,23 +1,47 @@
 use std::{
     collections::VecDeque,
-    path::PathBuf,
-    process::Command,
+    mem::discriminant,
+    path::{Path, PathBuf},
     sync::Arc,
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
 use ccdi_common::{
-    to_string, RawImage, StateMessage, StorageCapacity, StorageDetail, StorageLogRecord,
-    StorageLogStatus, StorageMessage, StorageState,
+    to_string, FitsCompression, OutputFormat, QueueStatus, RawImage, RemovableDrive,
+    StateMessage, StorageCapacity, StorageDetail, StorageLogRecord, StorageLogStatus,
+    StorageMessage, StorageState, StorageTarget,
 };
+use cameraunit::DynamicSerialImage;
 use log::debug;
+use nix::sys::statvfs::statvfs;
 use simple_expand_tilde::expand_tilde;
 
-use crate::ServiceConfig;
+use crate::{KeywordValue, RetentionMode, ServiceConfig};
 
-use self::save::save_fits_file;
+use self::{
+    header::{header_cards, HeaderCard},
+    naming::{render_file_name, FrameInfo},
+    recording::Recording,
+    removable::scan_drives,
+    retention::apply_retention,
+    save::{create_free_file, save_frame},
+    ser::{SerInfo, SerWriter},
+};
+
+pub use header::validate_keyword;
+pub use naming::validate_template;
+pub use queue::{storage_channel, StorageReceiver, StorageSender};
 
+mod fits;
+mod formats;
+mod header;
+mod naming;
+mod queue;
+mod recording;
+mod removable;
+mod retention;
 mod save;
+mod ser;
 
 // ============================================ PUBLIC =============================================
 
@@ -30,11 +54,24 @@ pub struct Storage {
     storage_name: String,
     storage_active: bool,
     details: VecDeque<StorageLogRecord>,
+    formats: Vec<OutputFormat>,
+    compression: FitsCompression,
+    /// Current or last finished video
+    recording: Option<Recording>,
+    queue: QueueStatus,
+    /// Bytes and write time of the last frames
+    writes: VecDeque<(u64, Duration)>,
+    last_retention: Option<Instant>,
+    target: StorageTarget,
+    /// Removable drives found by the last scan
+    drives: Vec<RemovableDrive>,
 }
 
 impl Storage {
     pub fn new(config: Arc<ServiceConfig>) -> Self {
         let dur = config.savecadence;
+        let formats = config.formats.clone();
+        let compression = config.fits.compression;
         Self {
             config,
             savecadence: dur,
@@ -44,10 +81,37 @@ impl Storage {
             storage_name: String::from("default"),
             storage_active: false,
             details: VecDeque::new(),
+            formats,
+            compression,
+            recording: None,
+            queue: QueueStatus::default(),
+            writes: VecDeque::new(),
+            last_retention: None,
+            target: StorageTarget::Internal,
+            drives: Vec::new(),
+        }
+    }
+
+    /// Apply edited config, new storage root is used for the next saved image
+    pub fn update_config(&mut self, config: Arc<ServiceConfig>) {
+        self.config = config;
+    }
+
+    /// Depth, capacity and dropped frames of the queue feeding this storage, frames dropped since
+    /// the last update are also dropped from the active recording
+    pub fn update_queue_status(&mut self, status: QueueStatus) {
+        let dropped = status.dropped.saturating_sub(self.queue.dropped);
+
+        if let Some(recording) = self.recording.as_mut() {
+            recording.queue_dropped(dropped);
         }
+
+        self.queue = QueueStatus { throughput: self.queue.throughput, ..status };
     }
 
     pub fn process(&mut self, message: StorageMessage) -> Result<Vec<StateMessage>, String> {
+        let mut messages = Vec::new();
+
         match message {
             StorageMessage::SetDirectory(name) => {
                 self.storage_name = name;
@@ -62,18 +126,67 @@ impl Storage {
                 debug!("Storage enabled");
                 self.storage_active = true;
             }
+            StorageMessage::RestoreSettings(settings) => {
+                debug!("Storage settings restored: {:?}", settings);
+                self.storage_name = settings.storage_name;
+                self.savecadence = settings.cadence;
+                self.counter = settings.counter;
+                self.storage_active = settings.storage_enabled;
+
+                if !settings.formats.is_empty() {
+                    self.formats = settings.formats;
+                }
+
+                if let Some(compression) = settings.compression {
+                    self.compression = compression;
+                }
+
+                self.target = settings.target;
+                self.drives = scan_drives(&self.config.removable);
+            }
+            StorageMessage::SetFormats(formats) => {
+                debug!("Storage formats set to {:?}", formats);
+                self.formats = OutputFormat::ALL
+                    .into_iter()
+                    .filter(|format| formats.contains(format))
+                    .collect();
+            }
             StorageMessage::UpdateCadence(dur) => {
                 debug!("Storage cadence updated to {:?}", dur);
                 self.savecadence = dur;
             }
+            StorageMessage::SetCompression(compression) => {
+                debug!("FITS compression set to {:?}", compression);
+                self.compression = compression;
+            }
+            StorageMessage::StartRecording(limits) => {
+                debug!("Recording started with limits {:?}", limits);
+                self.stop_recording(None);
+                self.recording = Some(Recording::new(limits));
+            }
+            StorageMessage::StopRecording => self.stop_recording(None),
+            StorageMessage::SetTarget(target) => {
+                debug!("Storage target set to {:?}", target);
+                self.target = target;
+                self.drives = scan_drives(&self.config.removable);
+                messages.extend(self.refresh_storage_state());
+            }
             StorageMessage::ProcessImage(image) => {
-                if self.storage_active {
+                // Drives and free space are checked by the periodic tasks, not for each frame
+                let writing = self.is_recording() || self.storage_active;
+
+                if writing && matches!(self.last_storage_state, StorageState::Full(_)) {
+                    // Frames are skipped until the retention or the user frees space
+                    self.stop_recording(Some(String::from("Storage full")));
+                } else if self.is_recording() {
+                    self.record_frame(&image);
+                } else if self.storage_active {
                     if self.last_save.is_none() {
                         self.last_save = Some(SystemTime::now());
                     } else {
                         let elapsed = self.last_save.unwrap().elapsed().unwrap();
                         if elapsed < self.savecadence {
-                            return Ok(vec![]);
+                            return Ok(messages);
                         }
                         self.last_save = Some(SystemTime::now());
                     }
@@ -82,35 +195,93 @@ impl Storage {
             }
         }
 
-        Ok(vec![StateMessage::UpdateStorageDetail(self.get_details())])
+        messages.push(StateMessage::UpdateStorageDetail(Box::new(self.get_details())));
+        Ok(messages)
     }
 
     pub fn periodic_tasks(&mut self) -> Result<Vec<StateMessage>, String> {
-        // Need to construct absolute path before performing df command in check_storage.
-        if let Some(file_name) = self.current_dir() {
-            let path = PathBuf::from(file_name);
-            let prefix = path.parent().ok_or("Invalid path parent".to_string())?;
-            let prefix = if prefix.starts_with("~") {
-                expand_tilde(prefix).ok_or("Could not un-tilde".to_string())?
-            } else {
-                prefix.to_owned()
-            };
+        // Duration limit must stop the recording even when frames stop arriving
+        let mut messages = Vec::new();
 
-            std::fs::create_dir_all(&prefix).map_err(to_string)?;
-            let storage_state = check_storage(prefix.to_str().ok_or("Err")?);
-            // let storage_state = check_storage(&self.storage_name);
-            // info!("Storage name: {:?}", self.storage_name);
-            // info!("Prefix (abs path): {:?}", prefix);
-            self.last_storage_state = storage_state.clone();
-            return Ok(vec![StateMessage::UpdateStorageState(storage_state)]);
-        };
+        if self.recording.as_ref().is_some_and(|recording| recording.limit_reached()) {
+            self.stop_recording(None);
+            messages.push(StateMessage::UpdateStorageDetail(Box::new(self.get_details())));
+        }
+
+        let retention_due = self
+            .last_retention
+            .is_none_or(|time| time.elapsed() >= RETENTION_INTERVAL);
+
+        if self.config.disk.retention.mode != RetentionMode::Disabled && retention_due {
+            self.last_retention = Some(Instant::now());
+            let deleted = self.apply_retention()?;
+
+            if !deleted.is_empty() {
+                messages.push(StateMessage::RetentionDeleted(deleted));
+            }
+        }
+
+        let drives = scan_drives(&self.config.removable);
+
+        if drives != self.drives {
+            self.drives = drives;
+            messages.push(StateMessage::UpdateStorageDetail(Box::new(self.get_details())));
+        }
+
+        self.refresh_storage_state();
+        messages.push(StateMessage::UpdateStorageState(self.last_storage_state.clone()));
+        Ok(messages)
+    }
+}
+
+/// Check storage message sent from a client before it is passed to the storage thread
+pub fn validate_storage_command(message: &StorageMessage) -> Result<(), String> {
+    match message {
+        StorageMessage::SetDirectory(name) => {
+            let name = name.trim();
 
-        Ok(vec![])
+            if name.is_empty() {
+                Err(String::from("Directory name must not be empty"))
+            } else if name.contains(['/', '\\']) || name == "." || name == ".." {
+                Err(format!("Directory name '{}' must be a single directory, not a path", name))
+            } else {
+                Ok(())
+            }
+        }
+        StorageMessage::UpdateCadence(cadence) if cadence.is_zero() => {
+            Err(String::from("Save cadence must not be zero"))
+        }
+        StorageMessage::SetFormats(formats) if formats.is_empty() => {
+            Err(String::from("At least one output format must be selected"))
+        }
+        StorageMessage::StartRecording(limits) if limits.frames == Some(0) => {
+            Err(String::from("Recording frame limit must not be zero"))
+        }
+        StorageMessage::StartRecording(limits) if limits.duration == Some(Duration::ZERO) => {
+            Err(String::from("Recording duration limit must not be zero"))
+        }
+        StorageMessage::SetTarget(StorageTarget::Removable(mount_point))
+            if !mount_point.starts_with('/') =>
+        {
+            Err(format!("Drive mount point '{}' must be an absolute path", mount_point))
+        }
+        StorageMessage::ProcessImage(_) | StorageMessage::RestoreSettings(_) => {
+            Err(String::from("Storage message is not a client command"))
+        }
+        _ => Ok(()),
     }
 }
 
 // =========================================== PRIVATE =============================================
 
+/// Number of frames kept in the storage log
+const LOG_CAPACITY: usize = 20;
+/// Number of frames averaged in the write throughput
+const THROUGHPUT_FRAMES: usize = 10;
+/// Time between two scans of the storage root by the retention policy
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60);
+const GIGABYTE: f64 = 1024.0 * 1024.0 * 1024.0;
+
 impl Storage {
     fn get_details(&self) -> StorageDetail {
         StorageDetail {
@@ -120,109 +291,314 @@ impl Storage {
             storage_log: self.details.iter().cloned().collect(),
             storage_enabled: self.storage_active,
             state: self.last_storage_state.clone(),
+            formats: self.formats.clone(),
+            compression: self.compression,
+            queue: self.queue,
+            target: self.target.clone(),
+            drives: self.drives.clone(),
+            fallback: self.active_drive().is_none() && self.target != StorageTarget::Internal,
+            recording: self.recording.as_ref().map(|recording| Box::new(recording.status())),
         }
     }
 
-    // This is where the directory prefix from the config file (i.e. ~/storage/) and the self.storage_name entered in the GUI (i.e. testdir) are concatenated.
-    // Any leading ~ is expanded only once within save_fits_file().
-    fn current_dir(&self) -> Option<String> {
-        PathBuf::from(&self.config.storage)
-            .join(PathBuf::from(self.storage_name.clone()))
-            .to_str()
-            .map(|path| path.to_owned())
+    /// Directory selected in the client inside the storage root
+    fn current_dir(&self) -> Result<PathBuf, String> {
+        Ok(self.storage_root()?.join(&self.storage_name))
     }
 
-    fn current_file_name(&self) -> Option<String> {
-        self.current_dir()
-            .map(|dir| format!("{}/{:05}.fits", dir, self.counter))
+    /// Selected drive if it is mounted and readable
+    fn active_drive(&self) -> Option<&RemovableDrive> {
+        let mount_point = match &self.target {
+            StorageTarget::Internal => return None,
+            StorageTarget::Removable(mount_point) => mount_point,
+        };
+
+        self.drives.iter().find(|drive| {
+            drive.mount_point == *mount_point && !matches!(drive.capacity, StorageState::Error(_))
+        })
+    }
+
+    /// Directory on the selected drive, storage root from the config with the home directory
+    /// expanded when no drive is selected or the selected drive is missing
+    fn storage_root(&self) -> Result<PathBuf, String> {
+        match self.active_drive() {
+            Some(drive) => Ok(Path::new(&drive.mount_point).join(&self.config.removable.directory)),
+            None => expand_tilde(&self.config.storage)
+                .ok_or(String::from("Could not expand home directory")),
+        }
+    }
+
+    /// File was written to the internal storage instead of the selected drive
+    fn is_fallback(&self, path: &Path) -> bool {
+        match &self.target {
+            StorageTarget::Internal => false,
+            StorageTarget::Removable(mount_point) => !path.starts_with(mount_point),
+        }
+    }
+
+    /// Query free space, returns the new state when it changes between available, full or error
+    fn refresh_storage_state(&mut self) -> Option<StateMessage> {
+        let min_free = self.config.disk.min_free_gigabytes;
+        let state = self
+            .storage_root()
+            .and_then(|root| std::fs::create_dir_all(&root).map(|_| root).map_err(to_string))
+            .map(|root| check_storage(&root, min_free))
+            .unwrap_or_else(StorageState::Error);
+
+        let changed = discriminant(&state) != discriminant(&self.last_storage_state);
+        self.last_storage_state = state.clone();
+        changed.then_some(StateMessage::UpdateStorageState(state))
+    }
+
+    /// Current session and the video being recorded are never deleted
+    fn apply_retention(&self) -> Result<Vec<StorageLogRecord>, String> {
+        let root = self.storage_root()?;
+        let mut keep = vec![self.current_dir()?];
+        let recording = self.recording.as_ref().filter(|recording| recording.is_active());
+
+        if let Some(file) = recording.and_then(|recording| expand_tilde(recording.status().file)) {
+            keep.push(file);
+        }
+
+        let deleted = apply_retention(&root, &self.config.disk.retention, &keep, SystemTime::now());
+        Ok(deleted)
+    }
+
+    /// Path from the naming template without extension, files get a suffix if the path exists
+    fn current_file_path(&self, image: &RawImage) -> Result<PathBuf, String> {
+        let frame = FrameInfo::from_image(image, &self.storage_name, self.counter);
+        let name = render_file_name(&self.config.naming, &frame)?;
+        Ok(self.current_dir()?.join(name))
+    }
+
+    fn header_cards(&self, image: &RawImage) -> Vec<HeaderCard> {
+        header_cards(image, &self.config.naming.frame_type, &self.config.fits)
     }
 
     fn handle_image(&mut self, image: Arc<RawImage>) {
-        let result = match self.current_file_name() {
-            None => file_name_err(),
-            Some(file_name) => match save_fits_file(&image, &file_name) {
-                Ok(file_name) => ok_record(file_name.to_string_lossy().to_string()),
-                Err(error) => StorageLogRecord {
-                    name: file_name,
-                    status: StorageLogStatus::Error(error),
-                },
-            },
-        };
+        let cards = self.header_cards(&image);
+        let started = Instant::now();
+        let mut records = self.save_image(&image, &cards);
 
+        // Drive removed since the last periodic scan, the frame is saved to the internal storage
+        if records.iter().any(|record| record.status != StorageLogStatus::Success)
+            && self.drive_removed()
+        {
+            records = self.save_image(&image, &cards);
+        }
+
+        let written = records
+            .iter()
+            .filter(|record| record.status == StorageLogStatus::Success)
+            .filter_map(|record| std::fs::metadata(&record.name).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        self.record_write(written, started.elapsed());
         self.counter += 1;
-        self.details.push_back(result);
+        self.details.extend(records);
 
-        while self.details.len() > 20 {
+        while self.details.len() > LOG_CAPACITY * self.formats.len().max(1) {
             self.details.pop_front();
         }
     }
+
+    /// One record per format, the camera reports errors of the last counter * formats records
+    fn save_image(&self, image: &RawImage, cards: &[HeaderCard]) -> Vec<StorageLogRecord> {
+        let for_each_format = |record: StorageLogRecord| vec![record; self.formats.len()];
+
+        match self.current_file_path(image) {
+            Err(error) => for_each_format(file_name_err(error)),
+            Ok(path) => match save_frame(image, &path, &self.formats, cards, self.compression) {
+                Err(error) => for_each_format(error_record(&path, error)),
+                Ok(files) => files
+                    .into_iter()
+                    .map(|(path, result)| match result {
+                        Ok(()) => StorageLogRecord {
+                            compression_ratio: self.compression_ratio(image, &path),
+                            fallback: self.is_fallback(&path),
+                            ..ok_record(path.to_string_lossy().to_string())
+                        },
+                        Err(error) => error_record(&path, error),
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    /// Rescan drives after a write error, true if the selected drive was in use and is gone now
+    fn drive_removed(&mut self) -> bool {
+        if self.active_drive().is_none() {
+            return false;
+        }
+
+        self.drives = scan_drives(&self.config.removable);
+        self.active_drive().is_none()
+    }
+
+    /// Uncompressed image data size divided by the size of a compressed FITS file
+    fn compression_ratio(&self, image: &RawImage, path: &Path) -> Option<f64> {
+        let fits = path.extension().is_some_and(|extension| extension == "fits");
+
+        if self.compression == FitsCompression::None || !fits {
+            return None;
+        }
+
+        let file_bytes = std::fs::metadata(path).ok()?.len();
+        Some(data_bytes(image) as f64 / file_bytes.max(1) as f64)
+    }
+
+    fn record_write(&mut self, bytes: u64, elapsed: Duration) {
+        self.writes.push_back((bytes, elapsed));
+
+        while self.writes.len() > THROUGHPUT_FRAMES {
+            self.writes.pop_front();
+        }
+
+        let bytes: u64 = self.writes.iter().map(|(bytes, _)| bytes).sum();
+        let secs: f64 = self.writes.iter().map(|(_, elapsed)| elapsed.as_secs_f64()).sum();
+        self.queue.throughput = match secs > 0.0 {
+            true => bytes as f64 / secs / 1e6,
+            false => 0.0,
+        };
+    }
+
+    fn is_recording(&self) -> bool {
+        self.recording.as_ref().is_some_and(Recording::is_active)
+    }
+
+    fn record_frame(&mut self, image: &RawImage) {
+        let started = Instant::now();
+        let writer = match self.recording.as_ref().is_some_and(Recording::needs_writer) {
+            true => Some(self.create_video(image)),
+            false => None,
+        };
+
+        let recording = match self.recording.as_mut() {
+            Some(recording) => recording,
+            None => return,
+        };
+
+        let result = match writer {
+            Some(Err(error)) => Err(error),
+            Some(Ok(writer)) => {
+                recording.start_writer(writer);
+                recording.append(image)
+            }
+            None => recording.append(image),
+        };
+
+        let limit_reached = recording.limit_reached();
+        self.record_write(data_bytes(image) as u64, started.elapsed());
+
+        match result {
+            Err(error) => {
+                // Updates the fallback state, the video is not continued on another drive
+                self.drive_removed();
+                self.stop_recording(Some(error))
+            }
+            Ok(()) if limit_reached => self.stop_recording(None),
+            Ok(()) => {}
+        }
+    }
+
+    fn create_video(&self, image: &RawImage) -> Result<SerWriter, String> {
+        let (file, path) = create_free_file(&self.current_file_path(image)?, "ser")?;
+        let keyword = |key: &str| match self.config.fits.keywords.get(key) {
+            Some(KeywordValue::Text(text)) => text.clone(),
+            _ => String::new(),
+        };
+
+        let info = SerInfo {
+            observer: keyword("OBSERVER"),
+            instrument: image.context.instrument.clone(),
+            telescope: keyword("TELESCOP"),
+        };
+
+        SerWriter::create(file, path, &image.data, image.context.start, &info)
+    }
+
+    /// Finish the active recording and log the video file
+    fn stop_recording(&mut self, error: Option<String>) {
+        let recording = match self.recording.as_mut() {
+            Some(recording) if recording.is_active() => recording,
+            _ => return,
+        };
+
+        recording.finish(error);
+        let status = recording.status();
+        debug!("Recording finished: {:?}", status);
+
+        if !status.file.is_empty() {
+            let fallback = self.is_fallback(Path::new(&status.file));
+            self.details.push_back(StorageLogRecord {
+                name: status.file,
+                status: match status.error {
+                    None => StorageLogStatus::Success,
+                    Some(error) => StorageLogStatus::Error(error),
+                },
+                compression_ratio: None,
+                fallback,
+            });
+        }
+    }
+}
+
+/// Size of the samples of all channels
+fn data_bytes(image: &RawImage) -> usize {
+    let (sample_bytes, planes) = match &image.data {
+        DynamicSerialImage::U8(buffer) => (1, buffer.get_luma().map_or(3, |_| 1)),
+        DynamicSerialImage::U16(buffer) => (2, buffer.get_luma().map_or(3, |_| 1)),
+        DynamicSerialImage::F32(buffer) => (4, buffer.get_luma().map_or(3, |_| 1)),
+    };
+
+    image.data.width() * image.data.height() * planes * sample_bytes
 }
 
 fn ok_record(name: String) -> StorageLogRecord {
     StorageLogRecord {
         name,
         status: StorageLogStatus::Success,
+        compression_ratio: None,
+        fallback: false,
     }
 }
 
-fn file_name_err() -> StorageLogRecord {
+fn error_record(path: &Path, error: String) -> StorageLogRecord {
     StorageLogRecord {
-        name: String::from("Could not assemble file name"),
-        status: StorageLogStatus::Error(String::new()),
+        name: path.to_string_lossy().to_string(),
+        status: StorageLogStatus::Error(error),
+        compression_ratio: None,
+        fallback: false,
     }
 }
 
-fn check_storage(path: &str) -> StorageState {
-    // Probable cause: path is not being prepended by the expanded "~/storage" from the config file. ~Mit
-    match Command::new("df").args([path]).output() {
-        Ok(output) => match output.status.code() {
-            Some(0) => match String::from_utf8(output.stdout) {
-                Ok(stdout) => match parse_free_space(&stdout) {
-                    Ok(details) => StorageState::Available(details),
-                    Err(error) => StorageState::Error(error),
-                },
-                Err(error) => {
-                    StorageState::Error(format!("Could not parse stdout as utf8: {:?}", error))
-                }
-            },
-            Some(code) => StorageState::Error(format!(
-                "Storage check returned error code: {:?} {:?}",
-                code,
-                String::from_utf8_lossy(&output.stderr)
-            )),
-            status => StorageState::Error(format!(
-                "Storage check did not return successfully: {:?}",
-                status
-            )),
-        },
-        Err(error) => StorageState::Error(format!("Storage check call failed: {:?}", error)),
+fn file_name_err(error: String) -> StorageLogRecord {
+    StorageLogRecord {
+        name: String::from("Could not assemble file name"),
+        status: StorageLogStatus::Error(error),
+        compression_ratio: None,
+        fallback: false,
     }
 }
 
-fn parse_free_space(stdout: &str) -> Result<StorageCapacity, String> {
-    let line = stdout
-        .lines()
-        .nth(1)
-        .ok_or("df output second line missing")?;
-    let total_gigabytes = kb_to_gb(parse_nth_token(line, 1)?);
-    let free_gigabytes = kb_to_gb(parse_nth_token(line, 3)?);
-    Ok(StorageCapacity {
-        total_gigabytes,
-        free_gigabytes,
-    })
-}
-
-fn parse_nth_token(line: &str, index: usize) -> Result<f64, String> {
-    let token = line
-        .split_whitespace()
-        .nth(index)
-        .ok_or(format!("{}th token not present in '{}'", index, line))?;
-
-    token.parse::<f64>().map_err(to_string)
-}
+/// Free space available to the service, below the minimum the storage is reported as full
+fn check_storage(path: &Path, min_free_gigabytes: f64) -> StorageState {
+    match statvfs(path) {
+        Err(error) => StorageState::Error(format!("Storage check failed: {}", error)),
+        Ok(stat) => {
+            let block = stat.fragment_size() as f64;
+            let capacity = StorageCapacity {
+                total_gigabytes: stat.blocks() as f64 * block / GIGABYTE,
+                free_gigabytes: stat.blocks_available() as f64 * block / GIGABYTE,
+            };
 
-fn kb_to_gb(kilobytes: f64) -> f64 {
-    kilobytes / 1024.0 / 1024.0
+            match capacity.free_gigabytes < min_free_gigabytes {
+                true => StorageState::Full(capacity),
+                false => StorageState::Available(capacity),
+            }
+        }
+    }
 }
 
 // ============================================= TEST ==============================================
@@ -230,17 +606,59 @@ fn kb_to_gb(kilobytes: f64) -> f64 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use indoc::indoc;
+    use ccdi_common::{FrameContext, RecordingLimits};
+    use ccdi_imager_interface::{ExposureArea, ExposureParams};
+
+    /// Frame of the image data, shared by the tests of the storage modules
+    pub fn test_image(data: DynamicSerialImage) -> RawImage {
+        RawImage {
+            params: ExposureParams {
+                gain: 0,
+                time: 1.0,
+                area: ExposureArea { x: 0, y: 0, width: data.width(), height: data.height() },
+                autoexp: false,
+                flipx: false,
+                flipy: false,
+                percentile_pix: 0.5,
+                pixel_tgt: 0.5,
+                pixel_tol: 0.1,
+                save: true,
+            },
+            data,
+            context: FrameContext {
+                instrument: String::from("test"),
+                start: SystemTime::now(),
+                target_temperature: 0.0,
+                heating_pwm: 0.0,
+            },
+        }
+    }
 
-    const TEST_DF_OUTPUT: &str = indoc! {"
-        Filesystem           1K-blocks  Used      Available  Use% Mounted on
-        /dev/mapper/luks-a6e 1967861712 111750632 1756075448   6% /media/x/759
-    "};
+    #[test]
+    fn free_space_is_checked() {
+        let root = std::env::temp_dir();
+
+        match check_storage(&root, 0.0) {
+            StorageState::Available(capacity) => assert!(capacity.total_gigabytes > 0.0),
+            state => panic!("Unexpected state {:?}", state),
+        }
+
+        assert!(matches!(check_storage(&root, f64::MAX), StorageState::Full(_)));
+        assert!(matches!(check_storage(&root.join("missing"), 0.0), StorageState::Error(_)));
+    }
 
     #[test]
-    fn parse_df_output() {
-        let details = parse_free_space(TEST_DF_OUTPUT).expect("Parse details failed");
-        assert_eq!(details.total_gigabytes, 1967861712.0 / 1024.0 / 1024.0);
-        assert_eq!(details.free_gigabytes, 1756075448.0 / 1024.0 / 1024.0);
+    fn client_storage_commands_are_validated() {
+        let directory = |name: &str| StorageMessage::SetDirectory(name.to_owned());
+
+        assert!(validate_storage_command(&directory("m31")).is_ok());
+        assert!(validate_storage_command(&directory(" ")).is_err());
+        assert!(validate_storage_command(&directory("../etc")).is_err());
+        assert!(validate_storage_command(&directory("..")).is_err());
+        assert!(validate_storage_command(&StorageMessage::UpdateCadence(Duration::ZERO)).is_err());
+        assert!(validate_storage_command(&StorageMessage::SetFormats(vec![])).is_err());
+        let frames = RecordingLimits { frames: Some(0), duration: None };
+        assert!(validate_storage_command(&StorageMessage::StartRecording(frames)).is_err());
+        assert!(validate_storage_command(&StorageMessage::EnableStore).is_ok());
     }
 }
@@ -0,0 +1,220 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ccdi_common::{format_utc_time, RawImage};
+
+use crate::NamingConfig;
+
+// ============================================ PUBLIC =============================================
+
+/// Values of a saved frame available to the file name template
+#[derive(Clone, PartialEq, Debug)]
+pub struct FrameInfo {
+    /// Storage directory name entered in the client
+    pub target: String,
+    pub sequence: usize,
+    pub time: SystemTime,
+    pub gain: u16,
+    /// Exposure time in seconds
+    pub exposure: f64,
+    /// Sensor temperature in degrees celsius, if reported by the camera
+    pub temperature: Option<f32>,
+    pub binning: (u32, u32),
+}
+
+impl FrameInfo {
+    pub fn from_image(image: &RawImage, target: &str, sequence: usize) -> Self {
+        let metadata = image.data.get_metadata();
+
+        Self {
+            target: target.to_owned(),
+            sequence,
+            time: image.context.start,
+            gain: image.params.gain,
+            exposure: image.params.time,
+            temperature: metadata.as_ref().map(|meta| meta.temperature),
+            binning: metadata
+                .as_ref()
+                .map(|meta| (meta.bin_x.max(1), meta.bin_y.max(1)))
+                .unwrap_or((1, 1)),
+        }
+    }
+}
+
+/// File path relative to the storage directory without extension, each output format adds its
+/// own. An image extension at the end of the template is dropped.
+pub fn render_file_name(config: &NamingConfig, frame: &FrameInfo) -> Result<PathBuf, String> {
+    let rendered = render(&config.template, |name, width| {
+        token_value(name, width, config, frame)
+    })?;
+
+    let stem = IMAGE_EXTENSIONS
+        .iter()
+        .find_map(|extension| rendered.strip_suffix(extension))
+        .unwrap_or(&rendered);
+    let path = PathBuf::from(stem);
+
+    match stem.ends_with('/') || stem.is_empty() || !is_relative(&path) {
+        false => Ok(path),
+        true => Err(format!(
+            "File name {:?} must be a relative path inside the storage directory",
+            path
+        )),
+    }
+}
+
+/// Check that the template uses only known tokens and renders to a relative path
+pub fn validate_template(config: &NamingConfig) -> Result<(), String> {
+    let frame = FrameInfo {
+        target: String::from("target"),
+        sequence: 1,
+        time: UNIX_EPOCH,
+        gain: 100,
+        exposure: 1.0,
+        temperature: Some(-10.0),
+        binning: (1, 1),
+    };
+
+    render_file_name(config, &frame).map(|_| ())
+}
+
+// =========================================== PRIVATE =============================================
+
+const IMAGE_EXTENSIONS: &[&str] = &[".fits", ".fit", ".tiff", ".tif", ".png", ".xisf"];
+
+/// Replace {token} and {token:0N} in the template with values from the closure
+fn render(
+    template: &str,
+    value: impl Fn(&str, Option<usize>) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Unclosed '{{' in file name template '{}'", template))?;
+
+        let token = &rest[start + 1..start + end];
+        let (name, width) = match token.split_once(':') {
+            None => (token, None),
+            Some((name, width)) => (
+                name,
+                Some(width.parse().map_err(|_| format!("Invalid width in {{{}}}", token))?),
+            ),
+        };
+
+        output.push_str(&value(name, width)?);
+        rest = &rest[start + end + 1..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn token_value(
+    name: &str,
+    width: Option<usize>,
+    config: &NamingConfig,
+    frame: &FrameInfo,
+) -> Result<String, String> {
+    if width.is_some() && name != "seq" {
+        return Err(format!("Only {{seq}} accepts width, not {{{}}}", name));
+    }
+
+    let value = match name {
+        "date" => format_utc_time(frame.time, "%Y-%m-%d"),
+        "time" => format_utc_time(frame.time, "%H%M%S"),
+        "timestamp" => format_utc_time(frame.time, "%Y%m%d_%H%M%S"),
+        "seq" => format!("{:0width$}", frame.sequence, width = width.unwrap_or(0)),
+        "gain" => frame.gain.to_string(),
+        "exp" => ((frame.exposure * 1e6).round() / 1e6).to_string(),
+        "temp" => frame
+            .temperature
+            .map(|temperature| format!("{:.1}", temperature))
+            .unwrap_or_else(|| String::from("na")),
+        "bin" => format!("{}x{}", frame.binning.0, frame.binning.1),
+        "filter" => config.filter.clone(),
+        "frametype" => config.frame_type.clone(),
+        "target" => frame.target.clone(),
+        _ => return Err(format!("Unknown token {{{}}} in file name template", name)),
+    };
+
+    Ok(sanitize(&value))
+}
+
+/// Values must not create directories or characters unsafe on removable media
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || "-_.+".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+fn is_relative(path: &Path) -> bool {
+    path.components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn frame() -> FrameInfo {
+        FrameInfo {
+            target: String::from("M31"),
+            sequence: 7,
+            time: UNIX_EPOCH + Duration::from_secs(1709327707),
+            gain: 120,
+            exposure: 2.5,
+            temperature: Some(-9.96),
+            binning: (2, 2),
+        }
+    }
+
+    fn config(template: &str) -> NamingConfig {
+        NamingConfig {
+            template: template.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tokens_are_replaced() {
+        let name = render_file_name(
+            &config("{date}/{target}_{frametype}_{exp}s_g{gain}_{seq:05}.fits"),
+            &frame(),
+        );
+        assert_eq!(name.unwrap(), PathBuf::from("2024-03-01/M31_light_2.5s_g120_00007"));
+
+        let name = render_file_name(&config("{timestamp}_{temp}C_bin{bin}_{filter}"), &frame());
+        assert_eq!(name.unwrap(), PathBuf::from("20240301_211507_-10.0C_bin2x2_none"));
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        assert!(validate_template(&NamingConfig::default()).is_ok());
+        assert!(validate_template(&config("{target")).is_err());
+        assert!(validate_template(&config("{unknown}")).is_err());
+        assert!(validate_template(&config("{gain:03}")).is_err());
+        assert!(validate_template(&config("../{seq}")).is_err());
+        assert!(validate_template(&config("/data/{seq}")).is_err());
+        assert!(validate_template(&config(".fits")).is_err());
+    }
+
+    #[test]
+    fn values_cannot_escape_the_directory() {
+        let frame = FrameInfo { target: String::from("../a b"), ..frame() };
+        let name = render_file_name(&config("{target}"), &frame).unwrap();
+        assert_eq!(name, PathBuf::from(".._a_b"));
+    }
+}
@@ -0,0 +1,267 @@
+use std::{
+    collections::VecDeque,
+    sync::{mpsc::RecvTimeoutError, Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use ccdi_common::{QueueStatus, StorageMessage};
+
+use crate::{QueuePolicy, StorageQueueConfig};
+
+// ============================================ PUBLIC =============================================
+
+/// Bounded channel to the storage thread, only frames count towards the capacity. Other messages
+/// are never dropped and keep their order relative to the frames.
+pub fn storage_channel(config: &StorageQueueConfig) -> (StorageSender, StorageReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(QueueState {
+            messages: VecDeque::new(),
+            frames: 0,
+            capacity: config.capacity.max(1),
+            policy: config.policy,
+            dropped: 0,
+            senders: 1,
+        }),
+        changed: Condvar::new(),
+    });
+
+    (StorageSender { shared: shared.clone() }, StorageReceiver { shared })
+}
+
+pub struct StorageSender {
+    shared: Arc<Shared>,
+}
+
+impl StorageSender {
+    /// Full queue drops a frame according to the policy, the block policy keeps every frame
+    /// because the exposure loop waits for free space before the next exposure
+    pub fn send(&self, message: StorageMessage) -> Result<(), String> {
+        let mut state = self.shared.lock();
+        let frame = is_frame(&message);
+
+        if frame && state.frames >= state.capacity {
+            match state.policy {
+                QueuePolicy::Block => {}
+                QueuePolicy::DropNewest => {
+                    state.dropped += 1;
+                    return Ok(());
+                }
+                QueuePolicy::DropOldest => {
+                    let oldest = state.messages.iter().position(is_frame);
+                    if let Some(index) = oldest {
+                        state.messages.remove(index);
+                        state.frames -= 1;
+                        state.dropped += 1;
+                    }
+                }
+            }
+        }
+
+        state.frames += frame as usize;
+        state.messages.push_back(message);
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    /// Next exposure must wait until the storage catches up
+    pub fn blocks_exposures(&self) -> bool {
+        let state = self.shared.lock();
+        state.policy == QueuePolicy::Block && state.frames >= state.capacity
+    }
+}
+
+impl Clone for StorageSender {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl Drop for StorageSender {
+    fn drop(&mut self) {
+        self.shared.lock().senders -= 1;
+        self.shared.changed.notify_all();
+    }
+}
+
+pub struct StorageReceiver {
+    shared: Arc<Shared>,
+}
+
+impl StorageReceiver {
+    /// Same semantics as mpsc, disconnected when all senders are dropped and the queue is empty
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<StorageMessage, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+
+        loop {
+            if let Some(message) = state.messages.pop_front() {
+                state.frames -= is_frame(&message) as usize;
+                return Ok(message);
+            }
+
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                return Err(RecvTimeoutError::Timeout);
+            }
+
+            state = self
+                .shared
+                .changed
+                .wait_timeout(state, remaining)
+                .map(|(state, _)| state)
+                .unwrap_or_else(|error| error.into_inner().0);
+        }
+    }
+
+    /// Smaller capacity keeps the waiting frames, new frames follow the policy until it drains
+    pub fn configure(&self, config: &StorageQueueConfig) {
+        let mut state = self.shared.lock();
+        state.capacity = config.capacity.max(1);
+        state.policy = config.policy;
+        self.shared.changed.notify_all();
+    }
+
+    /// Throughput is measured by the storage and left at zero
+    pub fn status(&self) -> QueueStatus {
+        let state = self.shared.lock();
+        QueueStatus {
+            depth: state.frames,
+            capacity: state.capacity,
+            dropped: state.dropped,
+            throughput: 0.0,
+        }
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+struct Shared {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+impl Shared {
+    /// Panic of a thread holding the lock leaves the queue consistent, the guard is recovered
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+struct QueueState {
+    messages: VecDeque<StorageMessage>,
+    /// Number of ProcessImage messages in the queue
+    frames: usize,
+    capacity: usize,
+    policy: QueuePolicy,
+    dropped: usize,
+    senders: usize,
+}
+
+fn is_frame(message: &StorageMessage) -> bool {
+    matches!(message, StorageMessage::ProcessImage(_))
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use cameraunit::DynamicSerialImage;
+
+    use crate::storage::tests::test_image;
+
+    use super::*;
+
+    fn frame() -> StorageMessage {
+        let data = DynamicSerialImage::from_vec_u16(2, 2, vec![0; 4]).unwrap();
+        StorageMessage::ProcessImage(Arc::new(test_image(data)))
+    }
+
+    #[test]
+    fn oldest_frame_is_dropped_and_commands_are_kept() {
+        let config = StorageQueueConfig { capacity: 2, policy: QueuePolicy::DropOldest };
+        let (tx, rx) = storage_channel(&config);
+
+        tx.send(frame()).unwrap();
+        tx.send(StorageMessage::EnableStore).unwrap();
+        tx.send(frame()).unwrap();
+        tx.send(frame()).unwrap();
+        assert_eq!(rx.status().depth, 2);
+        assert_eq!(rx.status().dropped, 1);
+        assert!(!tx.blocks_exposures());
+
+        let received = |rx: &StorageReceiver| rx.recv_timeout(Duration::ZERO).unwrap();
+        assert!(matches!(received(&rx), StorageMessage::EnableStore));
+        assert!(matches!(received(&rx), StorageMessage::ProcessImage(_)));
+        drop(tx);
+        assert!(matches!(received(&rx), StorageMessage::ProcessImage(_)));
+        assert_eq!(rx.recv_timeout(Duration::ZERO).err(), Some(RecvTimeoutError::Disconnected));
+    }
+
+    #[test]
+    fn newest_frame_is_dropped() {
+        let config = StorageQueueConfig { capacity: 1, policy: QueuePolicy::DropNewest };
+        let (tx, rx) = storage_channel(&config);
+
+        tx.send(frame()).unwrap();
+        tx.send(frame()).unwrap();
+        tx.send(StorageMessage::EnableStore).unwrap();
+        assert_eq!(rx.status().depth, 1);
+        assert_eq!(rx.status().dropped, 1);
+        assert!(!tx.blocks_exposures());
+
+        assert!(matches!(rx.recv_timeout(Duration::ZERO), Ok(StorageMessage::ProcessImage(_))));
+        assert!(matches!(rx.recv_timeout(Duration::ZERO), Ok(StorageMessage::EnableStore)));
+    }
+
+    #[test]
+    fn block_policy_keeps_frames_and_blocks_exposures() {
+        let config = StorageQueueConfig { capacity: 2, policy: QueuePolicy::Block };
+        let (tx, rx) = storage_channel(&config);
+
+        tx.send(frame()).unwrap();
+        assert!(!tx.blocks_exposures());
+        tx.send(frame()).unwrap();
+        assert!(tx.blocks_exposures());
+
+        // Frame read out before the exposure loop stopped is kept
+        tx.send(frame()).unwrap();
+        assert_eq!(rx.status().depth, 3);
+        assert_eq!(rx.status().dropped, 0);
+
+        rx.recv_timeout(Duration::ZERO).unwrap();
+        assert!(tx.blocks_exposures());
+        rx.recv_timeout(Duration::ZERO).unwrap();
+        assert!(!tx.blocks_exposures());
+    }
+
+    #[test]
+    fn smaller_capacity_keeps_waiting_frames() {
+        let config = StorageQueueConfig { capacity: 3, policy: QueuePolicy::DropNewest };
+        let (tx, rx) = storage_channel(&config);
+
+        for _ in 0..3 {
+            tx.send(frame()).unwrap();
+        }
+
+        rx.configure(&StorageQueueConfig { capacity: 1, policy: QueuePolicy::DropNewest });
+        assert_eq!(rx.status().depth, 3);
+        assert_eq!(rx.status().capacity, 1);
+
+        tx.send(frame()).unwrap();
+        assert_eq!(rx.status().dropped, 1);
+
+        for _ in 0..3 {
+            rx.recv_timeout(Duration::ZERO).unwrap();
+        }
+
+        tx.send(frame()).unwrap();
+        assert_eq!(rx.status().depth, 1);
+        assert_eq!(rx.status().dropped, 1);
+    }
+}
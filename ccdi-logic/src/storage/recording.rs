@@ -0,0 +1,150 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant, SystemTime},
+};
+
+use ccdi_common::{RawImage, RecordingLimits, RecordingStatus};
+
+use super::ser::SerWriter;
+
+// ============================================ PUBLIC =============================================
+
+/// Video recording session, every frame is written regardless of the save cadence. The file is
+/// created with the first frame because its size is not known before.
+pub struct Recording {
+    writer: Option<SerWriter>,
+    status: RecordingStatus,
+    started: Instant,
+    /// Start times of the last frames for the frame rate
+    recent: VecDeque<SystemTime>,
+}
+
+impl Recording {
+    pub fn new(limits: RecordingLimits) -> Self {
+        Self {
+            writer: None,
+            status: RecordingStatus {
+                active: true,
+                file: String::new(),
+                limits,
+                frames: 0,
+                dropped: 0,
+                fps: 0.0,
+                elapsed: Duration::ZERO,
+                error: None,
+            },
+            started: Instant::now(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn status(&self) -> RecordingStatus {
+        match self.status.active {
+            true => RecordingStatus { elapsed: self.started.elapsed(), ..self.status.clone() },
+            false => self.status.clone(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status.active
+    }
+
+    pub fn needs_writer(&self) -> bool {
+        self.status.active && self.writer.is_none()
+    }
+
+    pub fn start_writer(&mut self, writer: SerWriter) {
+        self.status.file = writer.path().to_string_lossy().to_string();
+        self.writer = Some(writer);
+    }
+
+    /// Frames dropped by the storage queue while recording
+    pub fn queue_dropped(&mut self, count: usize) {
+        if self.status.active {
+            self.status.dropped += count;
+        }
+    }
+
+    /// Frames which do not match the first frame are dropped, write errors stop the recording
+    pub fn append(&mut self, image: &RawImage) -> Result<(), String> {
+        let writer = match self.writer.as_mut() {
+            Some(writer) if self.status.active => writer,
+            _ => return Ok(()),
+        };
+
+        if !writer.accepts(&image.data) {
+            self.status.dropped += 1;
+            return Ok(());
+        }
+
+        writer.append(&image.data, image.context.start)?;
+        self.status.frames += 1;
+        self.recent.push_back(image.context.start);
+
+        while self.recent.len() > FPS_FRAMES {
+            self.recent.pop_front();
+        }
+
+        self.status.fps = frame_rate(&self.recent);
+        Ok(())
+    }
+
+    pub fn limit_reached(&self) -> bool {
+        let limits = &self.status.limits;
+        let frames = limits.frames.map(|frames| self.status.frames >= frames);
+        let duration = limits.duration.map(|duration| self.started.elapsed() >= duration);
+        frames.unwrap_or(false) || duration.unwrap_or(false)
+    }
+
+    /// Close the file, the error is the reason of an early stop
+    pub fn finish(&mut self, error: Option<String>) {
+        if !self.status.active {
+            return;
+        }
+
+        self.status.elapsed = self.started.elapsed();
+        self.status.active = false;
+        self.status.error = error;
+
+        if let Some(writer) = self.writer.take() {
+            if let Err(error) = writer.finish() {
+                self.status.error.get_or_insert(error);
+            }
+        }
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+const FPS_FRAMES: usize = 30;
+
+fn frame_rate(recent: &VecDeque<SystemTime>) -> f64 {
+    match (recent.front(), recent.back()) {
+        (Some(first), Some(last)) if recent.len() > 1 => {
+            let span = last.duration_since(*first).unwrap_or_default().as_secs_f64();
+            match span > 0.0 {
+                true => (recent.len() - 1) as f64 / span,
+                false => 0.0,
+            }
+        }
+        _ => 0.0,
+    }
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_drops_are_counted_while_active() {
+        let mut recording = Recording::new(RecordingLimits { frames: None, duration: None });
+        recording.queue_dropped(2);
+        assert_eq!(recording.status().dropped, 2);
+
+        recording.finish(None);
+        recording.queue_dropped(3);
+        assert_eq!(recording.status().dropped, 2);
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use ccdi_common::RemovableDrive;
+
+use crate::RemovableConfig;
+
+use super::check_storage;
+
+// ============================================ PUBLIC =============================================
+
+/// Drives mounted below the automount directories or at a configured mount point
+pub fn scan_drives(config: &RemovableConfig) -> Vec<RemovableDrive> {
+    let mounts = std::fs::read_to_string(MOUNTS_FILE).unwrap_or_default();
+
+    parse_mounts(&mounts, &config.mount_points)
+        .into_iter()
+        .map(|(device, mount_point)| RemovableDrive {
+            capacity: check_storage(Path::new(&mount_point), 0.0),
+            device,
+            mount_point,
+        })
+        .collect()
+}
+
+// =========================================== PRIVATE =============================================
+
+const MOUNTS_FILE: &str = "/proc/mounts";
+/// Directories where desktop automounters mount removable drives
+const AUTOMOUNT_DIRS: [&str; 2] = ["/media/", "/run/media/"];
+
+/// Device and mount point of matching entries, the last of stacked mounts is visible
+fn parse_mounts(mounts: &str, mount_points: &[String]) -> Vec<(String, String)> {
+    let entries = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some((unescape(fields.next()?), unescape(fields.next()?)))
+        })
+        .filter(|(_, mount_point)| {
+            AUTOMOUNT_DIRS.iter().any(|dir| mount_point.starts_with(dir))
+                || mount_points.iter().any(|path| path.trim_end_matches('/') == mount_point)
+        });
+
+    let mut drives: Vec<(String, String)> = Vec::new();
+
+    for (device, mount_point) in entries {
+        drives.retain(|(_, existing)| *existing != mount_point);
+        drives.push((device, mount_point));
+    }
+
+    drives
+}
+
+/// Spaces and other special characters are written as octal escapes, e.g. \040
+fn unescape(field: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = field.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        let code = tail.get(..3).and_then(|digits| {
+            u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok()
+        });
+
+        match (byte, code) {
+            (b'\\', Some(code)) => {
+                bytes.push(code);
+                rest = &tail[3..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removable_mounts_are_found() {
+        let mounts = concat!(
+            "/dev/mmcblk0p2 / ext4 rw,noatime 0 0\n",
+            "/dev/sda1 /media/pi/NIKON\\040D5 vfat rw,nosuid 0 0\n",
+            "/dev/sdb1 /mnt/usb exfat rw 0 0\n",
+            "/dev/sdc1 /mnt/other exfat rw 0 0\n",
+            "/dev/sdd1 /mnt/usb exfat rw 0 0\n",
+        );
+
+        let drives = parse_mounts(mounts, &[String::from("/mnt/usb/")]);
+        assert_eq!(
+            drives,
+            vec![
+                (String::from("/dev/sda1"), String::from("/media/pi/NIKON D5")),
+                (String::from("/dev/sdd1"), String::from("/mnt/usb")),
+            ]
+        );
+    }
+}
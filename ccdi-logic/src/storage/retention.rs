@@ -0,0 +1,234 @@
+use std::{
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use ccdi_common::{to_string, OutputFormat, StorageLogRecord, StorageLogStatus};
+use log::{info, warn};
+
+use crate::{RetentionConfig, RetentionMode};
+
+// ============================================ PUBLIC =============================================
+
+/// Delete the oldest sessions or frames in the root until the age and size limits are met. Paths
+/// in keep, directories containing them and files inside them are being written and are never
+/// deleted.
+pub fn apply_retention(
+    root: &Path,
+    config: &RetentionConfig,
+    keep: &[PathBuf],
+    now: SystemTime,
+) -> Vec<StorageLogRecord> {
+    let items = match config.mode {
+        RetentionMode::Disabled => return Vec::new(),
+        RetentionMode::Sessions => sessions(root),
+        RetentionMode::Frames => frames(root),
+    };
+
+    // Kept items count towards the size limit
+    let mut total: u64 = items.iter().map(|item| item.bytes).sum();
+    let mut candidates: Vec<Item> = items
+        .into_iter()
+        .filter(|item| {
+            !keep.iter().any(|path| path.starts_with(&item.path) || item.path.starts_with(path))
+        })
+        .collect();
+
+    candidates.sort_by_key(|item| item.modified);
+    let max_age = config.max_age_days.map(|days| Duration::from_secs_f64(days * 86400.0));
+    let max_bytes = config.max_gigabytes.map(|gigabytes| (gigabytes * GIGABYTE) as u64);
+    let mut records = Vec::new();
+
+    for item in candidates {
+        let age = now.duration_since(item.modified).unwrap_or_default();
+        let too_old = max_age.is_some_and(|max_age| age > max_age);
+        let too_big = max_bytes.is_some_and(|max_bytes| total > max_bytes);
+
+        // Items are sorted by age and the total only decreases, the rest is within limits
+        if !too_old && !too_big {
+            break;
+        }
+
+        let status = match remove(&item) {
+            Ok(()) => {
+                info!("Retention deleted {}", item.path.display());
+                total = total.saturating_sub(item.bytes);
+                StorageLogStatus::Success
+            }
+            Err(error) => {
+                warn!("Retention could not delete {}: {}", item.path.display(), error);
+                StorageLogStatus::Error(error)
+            }
+        };
+
+        records.push(StorageLogRecord {
+            name: item.path.to_string_lossy().to_string(),
+            status,
+            compression_ratio: None,
+            fallback: false,
+        });
+    }
+
+    records
+}
+
+// =========================================== PRIVATE =============================================
+
+const GIGABYTE: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Session directory or single frame file
+struct Item {
+    path: PathBuf,
+    /// Newest modification inside a directory
+    modified: SystemTime,
+    bytes: u64,
+    directory: bool,
+}
+
+/// Video files, written next to the frames
+const VIDEO_EXTENSION: &str = "ser";
+/// Temporary files of a frame being saved
+const STAGING_DIR: &str = ".staging";
+
+/// Directories in the root, each was selected as storage directory in the client
+fn sessions(root: &Path) -> Vec<Item> {
+    let entries = fs::read_dir(root).into_iter().flatten().flatten();
+
+    entries
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+        .filter(|entry| entry.file_name() != STAGING_DIR)
+        .map(|entry| {
+            let mut files = Vec::new();
+            walk(&entry.path(), &mut files);
+            let modified = files.iter().filter_map(|(_, metadata)| metadata.modified().ok()).max();
+            let directory_modified = entry.metadata().and_then(|metadata| metadata.modified());
+
+            Item {
+                path: entry.path(),
+                modified: modified.or(directory_modified.ok()).unwrap_or(SystemTime::UNIX_EPOCH),
+                bytes: files.iter().map(|(_, metadata)| metadata.len()).sum(),
+                directory: true,
+            }
+        })
+        .collect()
+}
+
+/// Files written by the service in any directory below the root, other files are kept
+fn frames(root: &Path) -> Vec<Item> {
+    let mut files = Vec::new();
+    walk(root, &mut files);
+
+    files
+        .into_iter()
+        .filter(|(path, _)| is_written_file(path))
+        .map(|(path, metadata)| Item {
+            path,
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            bytes: metadata.len(),
+            directory: false,
+        })
+        .collect()
+}
+
+fn is_written_file(path: &Path) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+
+    extension == VIDEO_EXTENSION
+        || OutputFormat::ALL.iter().any(|format| format.extension() == extension)
+}
+
+/// Regular files below the directory, symbolic links and staging directories are not followed
+fn walk(directory: &Path, files: &mut Vec<(PathBuf, Metadata)>) {
+    for entry in fs::read_dir(directory).into_iter().flatten().flatten() {
+        match entry.metadata() {
+            Ok(_) if entry.file_name() == STAGING_DIR => {}
+            Ok(metadata) if metadata.is_dir() => walk(&entry.path(), files),
+            Ok(metadata) if metadata.is_file() => files.push((entry.path(), metadata)),
+            _ => {}
+        }
+    }
+}
+
+fn remove(item: &Item) -> Result<(), String> {
+    match item.directory {
+        true => fs::remove_dir_all(&item.path).map_err(to_string),
+        false => fs::remove_file(&item.path).map_err(to_string),
+    }
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    #[test]
+    fn oldest_sessions_are_deleted_and_current_is_kept() {
+        let root = std::env::temp_dir().join(format!("ccdi-retention-{}", std::process::id()));
+        let now = SystemTime::now();
+        let days = |days: u64| now - Duration::from_secs(days * 86400);
+
+        for (session, modified) in [("old", days(10)), ("recent", days(5)), ("current", days(20))] {
+            fs::create_dir_all(root.join(session)).unwrap();
+            let file = File::create(root.join(session).join("frame.fits")).unwrap();
+            file.set_len(1000).unwrap();
+            file.set_modified(modified).unwrap();
+        }
+
+        let keep = [root.join("current")];
+        let mut config = RetentionConfig {
+            mode: RetentionMode::Sessions,
+            max_age_days: Some(7.0),
+            max_gigabytes: None,
+        };
+
+        let deleted = apply_retention(&root, &config, &keep, now);
+        assert_eq!(deleted.len(), 1);
+        assert!(deleted[0].name.ends_with("old"));
+        assert!(root.join("recent").exists());
+
+        // Kept session counts towards the size limit
+        config.max_gigabytes = Some(1500.0 / GIGABYTE);
+        assert_eq!(apply_retention(&root, &config, &keep, now).len(), 1);
+        assert!(!root.join("recent").exists());
+        assert!(root.join("current").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn oldest_frames_are_deleted_and_current_session_is_kept() {
+        let root = std::env::temp_dir().join(format!("ccdi-frames-{}", std::process::id()));
+        let now = SystemTime::now();
+        let days = |days: u64| now - Duration::from_secs(days * 86400);
+
+        for (session, frame, modified) in [
+            ("old", "a.fits", days(10)),
+            ("old", "b.fits", days(3)),
+            ("old", "notes.txt", days(30)),
+            ("old/.staging", "frame.fits", days(30)),
+            ("current", "c.fits", days(20)),
+        ] {
+            fs::create_dir_all(root.join(session)).unwrap();
+            let file = File::create(root.join(session).join(frame)).unwrap();
+            file.set_modified(modified).unwrap();
+        }
+
+        let config = RetentionConfig {
+            mode: RetentionMode::Frames,
+            max_age_days: Some(7.0),
+            max_gigabytes: None,
+        };
+
+        let deleted = apply_retention(&root, &config, &[root.join("current")], now);
+        assert_eq!(deleted.len(), 1);
+        assert!(deleted[0].name.ends_with("a.fits"));
+        assert!(root.join("old").join("b.fits").exists());
+        assert!(root.join("old").join("notes.txt").exists());
+        assert!(root.join("old/.staging").join("frame.fits").exists());
+        assert!(root.join("current").join("c.fits").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
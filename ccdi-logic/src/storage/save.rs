@@ -1,33 +1,189 @@
-use std::path::PathBuf;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
 
-use ccdi_common::{to_string, RawImage};
+use ccdi_common::{to_string, FitsCompression, OutputFormat, RawImage};
+
+use super::{formats::write_image_file, header::HeaderCard};
 
 use log::debug;
 use simple_expand_tilde::*;
 
 // ============================================ PUBLIC =============================================
 
-pub fn save_fits_file(image: &RawImage, file_name: &str) -> Result<PathBuf, String> {
-    let path = PathBuf::from(file_name);
-    let prefix = path.parent().ok_or("Invalid path parent".to_string())?;
-    debug!("Prefix: {:?}", prefix);
-    let prefix = if prefix.starts_with("~") {
-        expand_tilde(prefix).ok_or("Could not un-tilde".to_string())?
-    } else {
-        prefix.to_owned()
-    };
-    debug!("Prefix with tilde expansion: {:?}", prefix);
-    // let prefix = prefix
-    //     .canonicalize()
-    //     .map_err(|err| format!("Invalid path {:?} could not canonicalize", err))?;
-    // println!("After canonicalize: Prefix: {:?}", prefix);
-    std::fs::create_dir_all(&prefix).map_err(to_string)?;
-    debug!(
-        "Saving to: {:?}", prefix
-    );
-    let img = image.data.clone();
-    let path = img.savefits(&prefix, "ccdi", Some("CCDI ASI"), true, true)
-        .map_err(to_string)?;
-
-    Ok(path)
+/// Path of a saved file, or the intended path and error
+pub type SavedFile = (PathBuf, Result<(), String>);
+
+/// Save image in each format to the base path with the format extension appended. If any of the
+/// files exists, all files of the frame get the first free numeric suffix, existing files are
+/// never overwritten. Returns the result of each format.
+pub fn save_frame(
+    image: &RawImage,
+    base: &Path,
+    formats: &[OutputFormat],
+    cards: &[HeaderCard],
+    compression: FitsCompression,
+) -> Result<Vec<SavedFile>, String> {
+    let base = expand_path(base)?;
+    let dir = base.parent().ok_or("Invalid path parent".to_string())?;
+    fs::create_dir_all(dir).map_err(to_string)?;
+
+    // Files are written to a staging directory first, then linked to the final names
+    let staging = dir.join(STAGING_DIR);
+    let _ = fs::remove_dir_all(&staging);
+    fs::create_dir_all(&staging).map_err(to_string)?;
+    debug!("Saving {:?} to: {:?}", formats, base);
+
+    let staged = formats
+        .iter()
+        .map(|format| {
+            let staged = staging.join(format!("frame.{}", format.extension()));
+            let result = write_image_file(*format, image, &staged, cards, compression);
+            let result = result.map(|_| staged);
+            (with_extension(&base, format.extension()), result)
+        })
+        .collect::<Vec<_>>();
+
+    let suffix = (0..MAX_SUFFIX)
+        .find(|suffix| staged.iter().all(|(path, _)| !numbered_path(path, *suffix).exists()))
+        .unwrap_or(0);
+
+    let results = staged
+        .into_iter()
+        .map(|(path, result)| {
+            let path = numbered_path(&path, suffix);
+            match result.and_then(|staged| move_to_free_path(&staged, &path)) {
+                Ok(saved) => (saved, Ok(())),
+                Err(error) => (path, Err(error)),
+            }
+        })
+        .collect();
+
+    // Also removes partially written files
+    let _ = fs::remove_dir_all(&staging);
+    Ok(results)
+}
+
+/// Create a new file for data written as frames arrive, e.g. a video. Like saved frames, the file
+/// gets the first free numeric suffix if the path exists.
+pub fn create_free_file(base: &Path, extension: &str) -> Result<(File, PathBuf), String> {
+    let path = with_extension(&expand_path(base)?, extension);
+    let dir = path.parent().ok_or("Invalid path parent".to_string())?;
+    fs::create_dir_all(dir).map_err(to_string)?;
+    create_free(&path)
+}
+
+// =========================================== PRIVATE =============================================
+
+const STAGING_DIR: &str = ".staging";
+const MAX_SUFFIX: usize = 10000;
+
+fn expand_path(path: &Path) -> Result<PathBuf, String> {
+    match path.starts_with("~") {
+        true => expand_tilde(path).ok_or("Could not un-tilde".to_string()),
+        false => Ok(path.to_owned()),
+    }
+}
+
+/// Extension is appended, the stem may contain dots, e.g. M31_2.5s
+fn with_extension(base: &Path, extension: &str) -> PathBuf {
+    let mut path = base.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// Hard link fails if the target exists, so a file saved in between is not overwritten
+fn move_to_free_path(staged: &Path, path: &Path) -> Result<PathBuf, String> {
+    for suffix in 0..MAX_SUFFIX {
+        let candidate = numbered_path(path, suffix);
+
+        match fs::hard_link(staged, &candidate) {
+            Ok(()) => return Ok(candidate),
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => continue,
+            // Filesystems without hard links, e.g. FAT on removable media
+            Err(_) => return copy_to_free_path(staged, path),
+        }
+    }
+
+    Err(format!("No free file name for {:?}", path))
+}
+
+/// Copy to a file created exclusively, a file saved in between is not replaced
+fn copy_to_free_path(staged: &Path, path: &Path) -> Result<PathBuf, String> {
+    let (mut file, candidate) = create_free(path)?;
+    let copied = File::open(staged).and_then(|mut source| io::copy(&mut source, &mut file));
+
+    match copied {
+        Ok(_) => Ok(candidate),
+        Err(error) => {
+            let _ = fs::remove_file(&candidate);
+            Err(error.to_string())
+        }
+    }
+}
+
+/// Create the path or the path with the first free numeric suffix
+fn create_free(path: &Path) -> Result<(File, PathBuf), String> {
+    for suffix in 0..MAX_SUFFIX {
+        let candidate = numbered_path(path, suffix);
+
+        match OpenOptions::new().write(true).create_new(true).open(&candidate) {
+            Ok(file) => return Ok((file, candidate)),
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error.to_string()),
+        }
+    }
+
+    Err(format!("No free file name for {:?}", path))
+}
+
+/// Path with _1, _2, ... appended to the file stem, suffix 0 is the path itself
+fn numbered_path(path: &Path, suffix: usize) -> PathBuf {
+    match suffix {
+        0 => path.to_owned(),
+        _ => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let name = match path.extension() {
+                Some(extension) => format!("{}_{}.{}", stem, suffix, extension.to_string_lossy()),
+                None => format!("{}_{}", stem, suffix),
+            };
+            path.with_file_name(name)
+        }
+    }
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn existing_files_are_not_overwritten() {
+        let dir = std::env::temp_dir().join(format!("ccdi-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("frame.fits");
+
+        for content in ["first", "second", "third"] {
+            let staged = dir.join("staged");
+            fs::write(&staged, content).unwrap();
+            move_to_free_path(&staged, &path).unwrap();
+            let _ = fs::remove_file(&staged);
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+        assert_eq!(fs::read_to_string(dir.join("frame_1.fits")).unwrap(), "second");
+        assert_eq!(fs::read_to_string(dir.join("frame_2.fits")).unwrap(), "third");
+
+        // Copy used on filesystems without hard links
+        let staged = dir.join("staged");
+        fs::write(&staged, "fourth").unwrap();
+        assert_eq!(copy_to_free_path(&staged, &path).unwrap(), dir.join("frame_3.fits"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+        assert_eq!(fs::read_to_string(dir.join("frame_3.fits")).unwrap(), "fourth");
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
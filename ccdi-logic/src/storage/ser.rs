@@ -0,0 +1,208 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use cameraunit::{DynamicSerialImage, Primitive, SerialImageBuffer};
+use ccdi_common::to_string;
+
+use super::formats::channel_planes;
+
+// ============================================ PUBLIC =============================================
+
+/// Texts stored in the SER header
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SerInfo {
+    pub observer: String,
+    pub instrument: String,
+    pub telescope: String,
+}
+
+/// SER video written frame by frame, the frame count and timestamps are written by finish()
+pub struct SerWriter {
+    file: BufWriter<File>,
+    path: PathBuf,
+    geometry: FrameGeometry,
+    timestamps: Vec<i64>,
+}
+
+impl SerWriter {
+    /// Write the header to a new empty file, all frames must have the geometry of the first one
+    pub fn create(
+        file: File,
+        path: PathBuf,
+        first: &DynamicSerialImage,
+        start: SystemTime,
+        info: &SerInfo,
+    ) -> Result<Self, String> {
+        let geometry = FrameGeometry::new(first)?;
+        let mut writer = Self {
+            file: BufWriter::new(file),
+            path,
+            geometry,
+            timestamps: Vec::new(),
+        };
+
+        writer.write(&header(&geometry, start, info))?;
+        Ok(writer)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Frames with another size or sample format cannot be stored in the video
+    pub fn accepts(&self, data: &DynamicSerialImage) -> bool {
+        FrameGeometry::new(data).map(|geometry| geometry == self.geometry).unwrap_or(false)
+    }
+
+    pub fn append(&mut self, data: &DynamicSerialImage, time: SystemTime) -> Result<(), String> {
+        if !self.accepts(data) {
+            return Err(String::from("Frame size differs from the first frame"));
+        }
+
+        self.write(&frame_bytes(data)?)?;
+        self.timestamps.push(ticks(time));
+        Ok(())
+    }
+
+    /// Write the timestamp trailer and the final frame count
+    pub fn finish(mut self) -> Result<(PathBuf, usize), String> {
+        let trailer = self.timestamps.iter().flat_map(|time| time.to_le_bytes());
+        self.write(&trailer.collect::<Vec<_>>())?;
+
+        let count = self.timestamps.len();
+        self.file.seek(SeekFrom::Start(FRAME_COUNT_OFFSET)).map_err(to_string)?;
+        self.write(&(count as i32).to_le_bytes())?;
+        self.file.flush().map_err(to_string)?;
+        Ok((self.path, count))
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+const HEADER_SIZE: usize = 178;
+const FRAME_COUNT_OFFSET: u64 = 38;
+const COLOR_MONO: i32 = 0;
+const COLOR_RGB: i32 = 100;
+/// .NET ticks of 100 ns between 0001-01-01 and the unix epoch, used by SER timestamps
+const UNIX_EPOCH_TICKS: i64 = 621_355_968_000_000_000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct FrameGeometry {
+    width: usize,
+    height: usize,
+    planes: usize,
+    depth: usize,
+}
+
+impl FrameGeometry {
+    fn new(data: &DynamicSerialImage) -> Result<Self, String> {
+        let (depth, planes) = match data {
+            DynamicSerialImage::U8(buffer) => (8, channel_planes(buffer)?.len()),
+            DynamicSerialImage::U16(buffer) => (16, channel_planes(buffer)?.len()),
+            DynamicSerialImage::F32(buffer) => (16, channel_planes(buffer)?.len()),
+        };
+
+        Ok(Self { width: data.width(), height: data.height(), planes, depth })
+    }
+}
+
+impl SerWriter {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.file.write_all(bytes).map_err(to_string)
+    }
+}
+
+fn header(geometry: &FrameGeometry, start: SystemTime, info: &SerInfo) -> Vec<u8> {
+    let color = match geometry.planes {
+        3 => COLOR_RGB,
+        _ => COLOR_MONO,
+    };
+
+    let mut header = b"LUCAM-RECORDER".to_vec();
+    // Most readers treat 0 as little endian despite the specification
+    let numbers = [0, color, 0, geometry.width as i32, geometry.height as i32];
+    header.extend(numbers.iter().flat_map(|value: &i32| value.to_le_bytes()));
+    header.extend((geometry.depth as i32).to_le_bytes());
+    // Frame count is written by finish()
+    header.extend(0i32.to_le_bytes());
+
+    for text in [&info.observer, &info.instrument, &info.telescope] {
+        let mut field = [0u8; 40];
+        let bytes = text.as_bytes();
+        let length = bytes.len().min(field.len());
+        field[..length].copy_from_slice(&bytes[..length]);
+        header.extend(field);
+    }
+
+    // Local time is not known to the service, both times are UTC
+    header.extend(ticks(start).to_le_bytes());
+    header.extend(ticks(start).to_le_bytes());
+    debug_assert_eq!(header.len(), HEADER_SIZE);
+    header
+}
+
+/// Little endian samples interleaved pixel by pixel, float frames are converted to 16 bits
+fn frame_bytes(data: &DynamicSerialImage) -> Result<Vec<u8>, String> {
+    match data {
+        DynamicSerialImage::U8(buffer) => interleaved(buffer, |value| [value]),
+        DynamicSerialImage::U16(buffer) => interleaved(buffer, u16::to_le_bytes),
+        DynamicSerialImage::F32(buffer) => interleaved(buffer, |value: f32| {
+            ((value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16).to_le_bytes()
+        }),
+    }
+}
+
+fn interleaved<T: Primitive, B: IntoIterator<Item = u8>>(
+    buffer: &SerialImageBuffer<T>,
+    bytes: impl Fn(T) -> B,
+) -> Result<Vec<u8>, String> {
+    let planes = channel_planes(buffer)?;
+    let pixels = planes.first().map(|plane| plane.len()).unwrap_or(0);
+    let bytes = &bytes;
+    let planes = &planes;
+
+    Ok((0..pixels)
+        .flat_map(|index| planes.iter().flat_map(move |plane| bytes(plane[index])))
+        .collect())
+}
+
+fn ticks(time: SystemTime) -> i64 {
+    let nanos = time.duration_since(UNIX_EPOCH).map(|time| time.as_nanos()).unwrap_or(0);
+    UNIX_EPOCH_TICKS + (nanos / 100) as i64
+}
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn frames_and_timestamps_are_written() {
+        let path = std::env::temp_dir().join(format!("ccdi-ser-{}.ser", std::process::id()));
+        let frame = |value| DynamicSerialImage::from_vec_u16(4, 2, vec![value; 8]).unwrap();
+        let start = UNIX_EPOCH + Duration::from_secs(1709327707);
+
+        let file = File::create(&path).unwrap();
+        let mut writer =
+            SerWriter::create(file, path.clone(), &frame(1), start, &SerInfo::default()).unwrap();
+        writer.append(&frame(1), start).unwrap();
+        writer.append(&frame(2), start + Duration::from_millis(10)).unwrap();
+        assert!(!writer.accepts(&DynamicSerialImage::from_vec_u16(2, 2, vec![0; 4]).unwrap()));
+        assert_eq!(writer.finish().unwrap().1, 2);
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), HEADER_SIZE + 2 * 16 + 2 * 8);
+        assert_eq!(&bytes[38..42], &2i32.to_le_bytes());
+        assert_eq!(&bytes[HEADER_SIZE + 16..HEADER_SIZE + 18], &2u16.to_le_bytes());
+        let last = &bytes[bytes.len() - 8..];
+        assert_eq!(last, &(ticks(start) + 100_000).to_le_bytes());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
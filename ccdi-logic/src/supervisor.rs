@@ -0,0 +1,135 @@
+use std::{
+    any::Any,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use ccdi_common::{WorkerState, WorkerStatus};
+use log::{error, info, warn};
+
+// ============================================ PUBLIC =============================================
+
+/// Shared health of all supervised worker threads
+#[derive(Clone, Default)]
+pub struct WorkerMonitor {
+    workers: Arc<Mutex<Vec<WorkerStatus>>>,
+}
+
+impl WorkerMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn statuses(&self) -> Vec<WorkerStatus> {
+        match self.workers.lock() {
+            Ok(workers) => workers.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+}
+
+/// Spawn a named thread running the worker until it returns Ok. The worker is started again with
+/// increasing delay when it panics or returns an error.
+pub fn start_supervised_thread<F>(
+    name: &str,
+    monitor: WorkerMonitor,
+    mut worker: F,
+) -> Result<JoinHandle<()>, String>
+where
+    F: FnMut() -> Result<(), String> + Send + 'static,
+{
+    let name = name.to_owned();
+    monitor.update(&name, WorkerState::Running, None);
+
+    thread::Builder::new()
+        .name(name.clone())
+        .spawn(move || {
+            let mut backoff = INITIAL_BACKOFF;
+
+            loop {
+                let started = Instant::now();
+
+                let error = match catch_unwind(AssertUnwindSafe(&mut worker)) {
+                    Ok(Ok(())) => {
+                        info!("Worker '{}' finished", name);
+                        monitor.update(&name, WorkerState::Stopped, None);
+                        return;
+                    }
+                    Ok(Err(error)) => error,
+                    Err(payload) => format!("Panicked: {}", panic_message(payload)),
+                };
+
+                if started.elapsed() > STABLE_RUN {
+                    backoff = INITIAL_BACKOFF;
+                }
+
+                error!("Worker '{}' failed: {}, restarting in {:?}", name, error, backoff);
+                monitor.update(&name, WorkerState::Restarting, Some(error));
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+
+                warn!("Restarting worker '{}'", name);
+                monitor.restarted(&name);
+            }
+        })
+        .map_err(|err| format!("{:?}", err))
+}
+
+// =========================================== PRIVATE =============================================
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Worker running at least this long before failure restarts with initial backoff again
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+impl WorkerMonitor {
+    fn update(&self, name: &str, state: WorkerState, error: Option<String>) {
+        self.modify(name, |status| {
+            status.state = state;
+            if error.is_some() {
+                status.last_error = error;
+            }
+        });
+    }
+
+    fn restarted(&self, name: &str) {
+        self.modify(name, |status| {
+            status.state = WorkerState::Running;
+            status.restarts += 1;
+        });
+    }
+
+    fn modify(&self, name: &str, change: impl FnOnce(&mut WorkerStatus)) {
+        let mut workers = match self.workers.lock() {
+            Ok(workers) => workers,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let index = match workers.iter().position(|status| status.name == name) {
+            Some(index) => index,
+            None => {
+                workers.push(WorkerStatus {
+                    name: name.to_owned(),
+                    state: WorkerState::Running,
+                    restarts: 0,
+                    last_error: None,
+                });
+                workers.len() - 1
+            }
+        };
+
+        change(&mut workers[index]);
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
@@ -2,205 +2,216 @@ use std::sync::mpsc::{Receiver, Sender};
 use std::{
     io::Cursor,
     sync::{mpsc::RecvTimeoutError, Arc},
-    thread::{self, JoinHandle},
-    time::Duration,
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
 use ccdi_common::{
-    log_err, ClientMessage, IoMessage, ProcessMessage, StateMessage, StorageMessage,
+    log_err, ClientMessage, ConvertRawImage, IoMessage, ProcessMessage, StateMessage,
+    StorageMessage,
 };
 use image::{DynamicImage, ImageFormat};
-use log::{debug, error};
-
-use crate::{io::IoManager, state::BackendState, storage::Storage, ServiceConfig};
+use log::{debug, error, info};
+
+use crate::{
+    io::IoManager,
+    state::BackendState,
+    storage::{Storage, StorageReceiver, StorageSender},
+    supervisor::{start_supervised_thread, WorkerMonitor},
+    SharedConfig,
+};
 
 // ============================================ PUBLIC =============================================
 
 pub struct LogicParams {
     pub demo_mode: String,
+    pub workers: WorkerMonitor,
 }
 
 pub fn start_logic_thread(
     params: LogicParams,
-    config: Arc<ServiceConfig>,
+    config: SharedConfig,
     server_rx: Receiver<StateMessage>,
     clients_tx: Sender<ClientMessage>,
     io_tx: Sender<IoMessage>,
     process_tx: Sender<ProcessMessage>,
-    storage_tx: Sender<StorageMessage>,
+    storage_tx: StorageSender,
 ) -> Result<JoinHandle<()>, String> {
-    thread::Builder::new()
-        .name("logic".to_string())
-        .spawn(move || {
-            let mut state =
-                BackendState::new(&params.demo_mode, process_tx, storage_tx.clone(), config);
-
-            loop {
-                match server_rx.recv_timeout(Duration::from_millis(50)) {
-                    // Process the received message
-                    Ok(message) => {
-                        receive_message(&mut state, message, &clients_tx, &storage_tx, &io_tx)
-                    }
-                    // Last sender disconnected - exit thread
-                    Err(RecvTimeoutError::Disconnected) => return,
-                    // No messages received within timeout - perform periodic tasks
-                    Err(RecvTimeoutError::Timeout) => {
-                        periodic_tasks(&mut state, &clients_tx, &storage_tx, &io_tx)
-                    }
+    start_supervised_thread("logic", params.workers.clone(), move || {
+        let mut state = BackendState::new(
+            &params.demo_mode,
+            process_tx.clone(),
+            storage_tx.clone(),
+            config.clone(),
+            params.workers.clone(),
+        )?;
+
+        loop {
+            match server_rx.recv_timeout(Duration::from_millis(50)) {
+                // Process the received message
+                Ok(message) => {
+                    receive_message(&mut state, message, &clients_tx, &storage_tx, &io_tx)
+                }
+                // Last sender disconnected - exit thread
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                // No messages received within timeout - perform periodic tasks
+                Err(RecvTimeoutError::Timeout) => {
+                    periodic_tasks(&mut state, &clients_tx, &storage_tx, &io_tx)
                 }
             }
-        })
-        .map_err(|err| format!("{:?}", err))
+
+            // Shutdown finished - exit thread, other threads exit when their channels close
+            if state.finished() {
+                info!("Logic thread finished");
+                return Ok(());
+            }
+        }
+    })
 }
 
 pub fn start_process_thread(
+    workers: WorkerMonitor,
     process_rx: Receiver<ProcessMessage>,
     clients_tx: Sender<ClientMessage>,
     server_tx: Sender<StateMessage>,
 ) -> Result<JoinHandle<()>, String> {
-    thread::Builder::new()
-        .name("logic".to_string())
-        .spawn(move || {
-            loop {
-                match process_rx.recv() {
-                    // Process the received message
-                    Ok(message) => {
-                        debug!("Handling image process request");
-
-                        // let reply = handle_process_message(message);
-                        let reply = match message {
-                            ProcessMessage::ConvertRawImage(message) => {
-                                let img = message.image;
-                                let size = message.size;
-                                let img = DynamicImage::from(&img.data);
-                                let img = img.resize(
-                                    size.x as u32,
-                                    size.y as u32,
-                                    image::imageops::FilterType::Nearest,
-                                );
-                                let mut buf = Cursor::new(Vec::new());
-                                let img = img
-                                    .write_to(&mut buf, ImageFormat::Png)
-                                    .map_err(|err| format!("Error converting to PNG: {:?}", err));
-                                match img {
-                                    Ok(_) => {
-                                        let img = buf.into_inner();
-                                        vec![ClientMessage::PngImage(Arc::new(img))]
-                                    }
-                                    Err(err) => {
-                                        error!("Error converting to PNG: {:?}", err);
-                                        return;
-                                    }
-                                }
-                            }
-                        };
-
-                        debug!("Image process finished");
-
-                        for message in reply.into_iter() {
-                            if let ClientMessage::PngImage(ref image) = message {
-                                log_err(
-                                    "Send process message to server",
-                                    server_tx.send(StateMessage::ImageDisplayed(image.clone())),
-                                );
-                            }
-
-                            log_err("Send process message to client", clients_tx.send(message));
-                        }
-                    }
-                    // Last sender disconnected - exit thread
-                    Err(_) => return,
-                }
+    start_supervised_thread("process", workers, move || {
+        loop {
+            match process_rx.recv() {
+                // Process the received message
+                Ok(message) => process_message(message, &clients_tx, &server_tx),
+                // Last sender disconnected - exit thread
+                Err(_) => return Ok(()),
             }
-        })
-        .map_err(|err| format!("{:?}", err))
+        }
+    })
 }
 
 pub fn start_storage_thread(
-    config: Arc<ServiceConfig>,
-    storage_rx: Receiver<StorageMessage>,
+    config: SharedConfig,
+    workers: WorkerMonitor,
+    storage_rx: StorageReceiver,
     server_tx: Sender<StateMessage>,
 ) -> Result<JoinHandle<()>, String> {
-    thread::Builder::new()
-        .name("logic".to_string())
-        .spawn(move || {
-            let mut storage = Storage::new(config);
-
-            let send_results = |result: Result<Vec<StateMessage>, String>| match result {
-                Ok(messages) => {
-                    for message in messages {
-                        log_err(
-                            "Send message from storage to server",
-                            server_tx.send(message),
-                        );
-                    }
-                }
-                Err(error) => error!(
-                    "Processing storage messages or periodic task failed: {}",
-                    error
-                ),
-            };
-
-            loop {
-                match storage_rx.recv_timeout(Duration::from_millis(1000)) {
-                    // Process the received message
-                    Ok(message) => send_results(storage.process(message)),
-                    // Last sender disconnected - exit thread
-                    Err(RecvTimeoutError::Disconnected) => return,
-                    // No messages received within timeout - perform periodic tasks
-                    Err(RecvTimeoutError::Timeout) => send_results(storage.periodic_tasks()),
-                }
+    start_supervised_thread("storage", workers, move || {
+        let mut version = config.version();
+        let mut storage = Storage::new(config.get());
+        let mut last_periodic = Instant::now();
+
+        loop {
+            let message = storage_rx.recv_timeout(STORAGE_PERIOD);
+            storage.update_queue_status(storage_rx.status());
+
+            match message {
+                // Process the received message
+                Ok(message) => send_results(storage.process(message), &server_tx),
+                // Last sender disconnected - exit thread
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                Err(RecvTimeoutError::Timeout) => {}
             }
-        })
-        .map_err(|err| format!("{:?}", err))
+
+            // Frames arriving faster than the period must not postpone periodic tasks
+            if last_periodic.elapsed() >= STORAGE_PERIOD {
+                last_periodic = Instant::now();
+                send_results(storage.periodic_tasks(), &server_tx);
+            }
+
+            if let Some(new_config) = config.changed(&mut version) {
+                storage_rx.configure(&new_config.storage_queue);
+                storage.update_config(new_config);
+            }
+        }
+    })
 }
 
 pub fn start_io_thread(
-    config: Arc<ServiceConfig>,
-    storage_rx: Receiver<IoMessage>,
+    config: SharedConfig,
+    workers: WorkerMonitor,
+    io_rx: Receiver<IoMessage>,
     server_tx: Sender<StateMessage>,
 ) -> Result<JoinHandle<()>, String> {
-    thread::Builder::new()
-        .name("logic".to_string())
-        .spawn(move || {
-            let mut io = IoManager::new(&config.io);
-
-            let send_results = |result: Result<Vec<StateMessage>, String>| match result {
-                Ok(messages) => {
-                    for message in messages {
-                        log_err(
-                            "Send message from storage to server",
-                            server_tx.send(message),
-                        );
-                    }
-                }
-                Err(error) => error!(
-                    "Processing storage messages or periodic task failed: {}",
-                    error
-                ),
-            };
-
-            loop {
-                match storage_rx.recv_timeout(Duration::from_millis(20)) {
-                    // Process the received message
-                    Ok(message) => send_results(io.process(message)),
-                    // Last sender disconnected - exit thread
-                    Err(RecvTimeoutError::Disconnected) => return,
-                    // No messages received within timeout - perform periodic tasks
-                    Err(RecvTimeoutError::Timeout) => send_results(io.periodic_tasks()),
-                }
+    start_supervised_thread("io", workers, move || {
+        let mut version = config.version();
+        let current = config.get();
+        let mut io = IoManager::new(&current.io, &current.power);
+
+        loop {
+            match io_rx.recv_timeout(Duration::from_millis(20)) {
+                // Process the received message
+                Ok(message) => send_results(io.process(message), &server_tx),
+                // Last sender disconnected - exit thread
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                // No messages received within timeout - perform periodic tasks
+                Err(RecvTimeoutError::Timeout) => send_results(io.periodic_tasks(), &server_tx),
+            }
+
+            if let Some(new_config) = config.changed(&mut version) {
+                io.update_config(&new_config.io, &new_config.power);
             }
-        })
-        .map_err(|err| format!("{:?}", err))
+        }
+    })
 }
 
 // =========================================== PRIVATE =============================================
 
+/// Interval of storage periodic tasks: free space check and retention
+const STORAGE_PERIOD: Duration = Duration::from_millis(1000);
+
+fn process_message(
+    message: ProcessMessage,
+    clients_tx: &Sender<ClientMessage>,
+    server_tx: &Sender<StateMessage>,
+) {
+    debug!("Handling image process request");
+
+    let image = match message {
+        ProcessMessage::ConvertRawImage(message) => match convert_raw_image(message) {
+            Ok(image) => Arc::new(image),
+            Err(error) => {
+                error!("{}", error);
+                return;
+            }
+        },
+    };
+
+    debug!("Image process finished");
+
+    log_err(
+        "Send process message to server",
+        server_tx.send(StateMessage::ImageDisplayed(image.clone())),
+    );
+
+    log_err("Send process message to client", clients_tx.send(ClientMessage::PngImage(image)));
+}
+
+fn convert_raw_image(message: ConvertRawImage) -> Result<Vec<u8>, String> {
+    let size = message.size;
+    let img = DynamicImage::from(&message.image.data);
+    let img = img.resize(size.x as u32, size.y as u32, image::imageops::FilterType::Nearest);
+    let mut buf = Cursor::new(Vec::new());
+
+    img.write_to(&mut buf, ImageFormat::Png)
+        .map_err(|err| format!("Error converting to PNG: {:?}", err))?;
+
+    Ok(buf.into_inner())
+}
+
+fn send_results(result: Result<Vec<StateMessage>, String>, server_tx: &Sender<StateMessage>) {
+    match result {
+        Ok(messages) => {
+            for message in messages {
+                log_err("Send message to server", server_tx.send(message));
+            }
+        }
+        Err(error) => error!("Processing messages or periodic task failed: {}", error),
+    }
+}
+
 fn receive_message(
     state: &mut BackendState,
     message: StateMessage,
     clients_tx: &Sender<ClientMessage>,
-    storage_tx: &Sender<StorageMessage>,
+    storage_tx: &StorageSender,
     io_tx: &Sender<IoMessage>,
 ) {
     if let Some(responses) = log_err("Process state message", state.process(message)) {
@@ -213,7 +224,7 @@ fn receive_message(
 fn periodic_tasks(
     state: &mut BackendState,
     clients_tx: &Sender<ClientMessage>,
-    storage_tx: &Sender<StorageMessage>,
+    storage_tx: &StorageSender,
     io_tx: &Sender<IoMessage>,
 ) {
     if let Some(responses) = log_err("Perform periodic tasks", state.periodic()) {
@@ -235,7 +246,7 @@ fn send_io_messages(messages: Vec<IoMessage>, io_tx: &Sender<IoMessage>) {
     }
 }
 
-fn send_storage_messages(messages: Vec<StorageMessage>, storage_tx: &Sender<StorageMessage>) {
+fn send_storage_messages(messages: Vec<StorageMessage>, storage_tx: &StorageSender) {
     for message in messages {
         log_err("Send storage response", storage_tx.send(message));
     }
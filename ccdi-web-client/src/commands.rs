@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use ccdi_common::{ClientCommand, CommandResult, StateMessage};
+
+// ============================================ PUBLIC =============================================
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum CommandStatus {
+    Pending,
+    Succeeded,
+    /// Accepted with a value adjusted to the limits, carries the reason
+    Adjusted(String),
+    Failed(String),
+}
+
+/// Status of the last command of each kind, see command_key
+pub type CommandStatuses = HashMap<String, CommandStatus>;
+
+/// Assigns ids to commands sent to the server and pairs them with their results
+#[derive(Default)]
+pub struct CommandTracker {
+    next_id: u64,
+    /// Keys of commands waiting for result by command id
+    pending: HashMap<u64, String>,
+    statuses: CommandStatuses,
+}
+
+impl CommandTracker {
+    pub fn command(&mut self, message: StateMessage) -> StateMessage {
+        self.next_id += 1;
+        let key = command_key(&message);
+        self.statuses.insert(key.clone(), CommandStatus::Pending);
+        self.pending.insert(self.next_id, key);
+
+        StateMessage::Command(ClientCommand {
+            id: self.next_id,
+            connection: 0,
+            message: Box::new(message),
+        })
+    }
+
+    pub fn receive(&mut self, result: CommandResult) {
+        if let Some(key) = self.pending.remove(&result.id) {
+            self.statuses.insert(key, match result.result {
+                Ok(None) => CommandStatus::Succeeded,
+                Ok(Some(reason)) => CommandStatus::Adjusted(reason),
+                Err(error) => CommandStatus::Failed(error),
+            });
+        }
+    }
+
+    /// Results of pending commands are lost with the connection
+    pub fn connection_lost(&mut self) {
+        for (_, key) in self.pending.drain() {
+            self.statuses.insert(key, CommandStatus::Failed(String::from("Connection lost")));
+        }
+    }
+
+    pub fn statuses(&self) -> &CommandStatuses {
+        &self.statuses
+    }
+}
+
+/// Command kind made of variant names, e.g. CameraParam.SetGain or StorageMessage.SetDirectory
+pub fn command_key(message: &StateMessage) -> String {
+    let mut key = Vec::new();
+    let mut value = serde_json::to_value(message).unwrap_or_default();
+
+    // State message variant and variant of the enum it carries, values are not part of the key
+    while key.len() < 2 {
+        match value {
+            serde_json::Value::String(name) => {
+                key.push(name);
+                break;
+            }
+            serde_json::Value::Object(object) if object.len() == 1 => {
+                let (name, inner) = object.into_iter().next().unwrap_or_default();
+                key.push(name);
+                value = inner;
+            }
+            _ => break,
+        }
+    }
+
+    key.join(".")
+}
@@ -0,0 +1,38 @@
+use yew::Properties;
+use crate::commands::{CommandStatus, CommandStatuses};
+use super::*;
+
+// ============================================ PUBLIC =============================================
+
+/// Shows whether the last command of the kind is pending, succeeded, adjusted or failed with its error
+pub struct CommandStatusIcon;
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct CommandStatusData {
+    pub commands: CommandStatuses,
+    /// Command key, see commands::command_key
+    pub command: &'static str,
+}
+
+impl Component for CommandStatusIcon {
+    type Message = ();
+    type Properties = CommandStatusData;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {}
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        match ctx.props().commands.get(ctx.props().command) {
+            None => html! {},
+            Some(CommandStatus::Pending) => html! { <span class="command-status">{"⏳"}</span> },
+            Some(CommandStatus::Succeeded) => html! { <span class="command-status">{"✅"}</span> },
+            Some(CommandStatus::Adjusted(reason)) => html! {
+                <span class="command-status orange">{format!("⚠️ {}", reason)}</span>
+            },
+            Some(CommandStatus::Failed(error)) => html! {
+                <span class="command-status red">{format!("❌ {}", error)}</span>
+            },
+        }
+    }
+}
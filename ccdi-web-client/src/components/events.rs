@@ -0,0 +1,102 @@
+use yew::Properties;
+use super::*;
+
+// ============================================ PUBLIC =============================================
+
+pub struct Events {
+    min_severity: Severity,
+    source: Option<EventSource>,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct EventsData {
+    pub events: Vec<Event>,
+}
+
+pub enum Msg {
+    SetMinSeverity(Severity),
+    SetSource(Option<EventSource>),
+}
+
+impl Component for Events {
+    type Message = Msg;
+    type Properties = EventsData;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            min_severity: Severity::Info,
+            source: None,
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::SetMinSeverity(severity) => self.min_severity = severity,
+            Msg::SetSource(source) => self.source = source,
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let severity_button = |name: &'static str, severity: Severity| {
+            let selected = (self.min_severity == severity).then_some("button-selected");
+            let onclick = ctx.link().callback(move |_| Msg::SetMinSeverity(severity));
+            html! { <button class={classes!(selected)} {onclick}>{name}</button> }
+        };
+
+        let source_button = |name: &'static str, source: Option<EventSource>| {
+            let selected = (self.source == source).then_some("button-selected");
+            let onclick = ctx.link().callback(move |_| Msg::SetSource(source));
+            html! { <button class={classes!(selected)} {onclick}>{name}</button> }
+        };
+
+        let rows = ctx
+            .props()
+            .events
+            .iter()
+            .rev()
+            .filter(|event| event.severity >= self.min_severity)
+            .filter(|event| self.source.map(|source| event.source == source).unwrap_or(true))
+            .map(render_event)
+            .collect::<Html>();
+
+        html! {
+            <div>
+                <p>
+                    {severity_button("All", Severity::Info)}
+                    {severity_button("Warnings", Severity::Warning)}
+                    {severity_button("Errors", Severity::Error)}
+                </p>
+                <p>
+                    {source_button("Any", None)}
+                    {source_button("Camera", Some(EventSource::Camera))}
+                    {source_button("Storage", Some(EventSource::Storage))}
+                    {source_button("IO", Some(EventSource::Io))}
+                    {source_button("Client", Some(EventSource::Client))}
+                    {source_button("System", Some(EventSource::System))}
+                </p>
+                <div class="div-table event-log">
+                    {rows}
+                </div>
+            </div>
+        }
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+fn render_event(event: &Event) -> Html {
+    let class = match event.severity {
+        Severity::Info => None,
+        Severity::Warning => Some("orange"),
+        Severity::Error => Some("red"),
+    };
+
+    html! {
+        <div class="div-table-row w100p">
+            <div class="div-table-col w30p">{format_utc_time(event.time, "%Y-%m-%d %H:%M:%S")}</div>
+            <div class="div-table-col w20p">{format!("{:?}", event.source)}</div>
+            <div class={classes!("div-table-col", "w45p", class)}>{&event.message}</div>
+        </div>
+    }
+}
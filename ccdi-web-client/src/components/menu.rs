@@ -14,6 +14,8 @@ pub enum MenuItem {
     Cooling,
     Info,
     Shoot,
+    Presets,
+    Events,
     System,
 }
 
@@ -52,6 +54,8 @@ impl Component for Menu {
                 {menu_item("Temperature", Cooling, selected, ctx)}
                 {menu_item("Info", Info, selected, ctx)}
                 {menu_item("Series", Shoot, selected, ctx)}
+                {menu_item("Presets", Presets, selected, ctx)}
+                {menu_item("Events", Events, selected, ctx)}
                 {menu_item("System", System, selected, ctx)}
             </div>
         }
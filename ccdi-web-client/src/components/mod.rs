@@ -6,5 +6,9 @@ pub mod footer;
 pub mod menu;
 pub mod camera;
 pub mod text_input;
+pub mod text_area;
 pub mod shooting_details;
-pub mod system;
\ No newline at end of file
+pub mod system;
+pub mod presets;
+pub mod events;
+pub mod command_status;
\ No newline at end of file
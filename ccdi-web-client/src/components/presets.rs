@@ -0,0 +1,106 @@
+use yew::{Properties, Callback};
+use crate::commands::CommandStatuses;
+use crate::components::command_status::CommandStatusIcon;
+use crate::components::text_input::TextInput;
+use super::*;
+
+// ============================================ PUBLIC =============================================
+
+pub struct Presets {
+    edited_name: String,
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct PresetsData {
+    pub on_action: Callback<StateMessage>,
+    pub presets: Vec<Preset>,
+    pub commands: CommandStatuses,
+}
+
+pub enum Msg {
+    UpdateEditedName(String),
+    ServerAction(PresetMessage),
+}
+
+impl Component for Presets {
+    type Message = Msg;
+    type Properties = PresetsData;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            edited_name: String::new(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::UpdateEditedName(name) => self.edited_name = name,
+            Msg::ServerAction(action) => ctx.props().on_action.emit(StateMessage::Preset(action)),
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        use PresetMessage::*;
+
+        let on_change_name = ctx.link().callback(Msg::UpdateEditedName);
+        let name = self.edited_name.clone();
+        let commands = &ctx.props().commands;
+
+        let server_action = |action: PresetMessage| ctx.link().callback(
+            move |_| Msg::ServerAction(action.clone())
+        );
+
+        let rows = ctx.props().presets.iter().map(|preset| html! {
+            <div class="div-table-row">
+                <div class="div-table-col">{&preset.name}</div>
+                <div class="div-table-col">{describe(preset)}</div>
+                <div class="div-table-col">
+                    <button onclick={server_action(Apply(preset.name.clone()))}>{"Apply"}</button>
+                    <button onclick={server_action(Rename((preset.name.clone(), name.clone())))}>
+                        {"Rename"}
+                    </button>
+                    <button onclick={server_action(Delete(preset.name.clone()))}>{"Delete"}</button>
+                </div>
+            </div>
+        });
+
+        html! {
+            <div>
+                <p>{"Presets"}</p>
+                <div class="div-table">
+                    { for rows }
+                </div>
+                <p>
+                    <CommandStatusIcon commands={commands.clone()} command="Preset.Apply"/>
+                    <CommandStatusIcon commands={commands.clone()} command="Preset.Rename"/>
+                    <CommandStatusIcon commands={commands.clone()} command="Preset.Delete"/>
+                </p>
+                <p>{"Name"}</p>
+                <TextInput on_change={on_change_name} value={self.edited_name.clone()}/>
+                <button onclick={server_action(Create(self.edited_name.clone()))}>
+                    {"Save current"}
+                </button>
+                <CommandStatusIcon commands={commands.clone()} command="Preset.Create"/>
+                <p>{"Rename uses the name above"}</p>
+            </div>
+        }
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+fn describe(preset: &Preset) -> String {
+    let camera = &preset.camera_params;
+    let image = &preset.image_params;
+
+    let exposure = match camera.autoexp {
+        true => String::from("auto"),
+        false => format!("{} s", camera.time),
+    };
+
+    format!(
+        "{}, gain {}, ROI {}x{} at {},{}",
+        exposure, camera.gain, image.w, image.h, image.x, image.y
+    )
+}
@@ -58,7 +58,13 @@ fn render_item(property: &StorageLogRecord) -> Html {
             }
         }/>
         </div>
-        <div class="div-table-col w90p">{name}</div>
+        <div class="div-table-col w90p">{name}{format_ratio(property.compression_ratio)}{
+            if property.fallback { " (internal fallback)" } else { "" }
+        }</div>
         </div>
     }
 }
+
+fn format_ratio(ratio: Option<f64>) -> String {
+    ratio.map(|ratio| format!(" ({:.1}x)", ratio)).unwrap_or_default()
+}
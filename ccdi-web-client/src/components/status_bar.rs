@@ -32,8 +32,9 @@ impl Component for StatusBar {
                 { combined("Loop", main_state, ctx.props().logic.loop_enabled) }
                 // { combined("Trigger Needed", main_state, ctx.props().logic.required) }
                 // { combined("Trigger On", main_state, ctx.props().logic.trigger) }
-                { combined("Exposure", main_state, ctx.props().logic.exposure) }
+                { exposure_view(main_state, &ctx.props().logic.exposure) }
                 { combined("Save On", main_state, ctx.props().logic.save) }
+                { shutdown_view(ctx.props().logic.shutdown) }
             </div>
         }
     }
@@ -50,6 +51,28 @@ fn combined(name: &str, main: ConnectionState, state: ConnectionState) -> Html {
     state_html(name, status_class)
 }
 
+fn exposure_view(main: ConnectionState, exposure: &ExposureStatus) -> Html {
+    let status_class = match main {
+        ConnectionState::Established => status_to_class(exposure.state.as_connection_state()),
+        _other => "unknown"
+    };
+
+    html! {
+        <ul class="float-child">
+            <li class={classes!("status", status_class)} title={exposure.reason.clone()}>
+                {format!("Exposure: {:?}", exposure.state)}
+            </li>
+        </ul>
+    }
+}
+
+fn shutdown_view(stage: Option<ShutdownStage>) -> Html {
+    match stage {
+        None => html! {},
+        Some(stage) => state_html(&format!("Shutting down: {:?}", stage), "warn"),
+    }
+}
+
 fn state_view(name: &str, state: ConnectionState) -> Html {
     state_html(name, status_to_class(state))
 }
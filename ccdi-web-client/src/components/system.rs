@@ -1,20 +1,32 @@
 use yew::{Properties, Callback};
+use crate::commands::CommandStatuses;
+use crate::components::command_status::CommandStatusIcon;
+use crate::components::text_area::TextArea;
 use super::*;
 
 // ============================================ PUBLIC =============================================
 
 pub struct System {
     confirmation_enabled: bool,
+    edited_config: Option<String>,
 }
 
 #[derive(Clone, PartialEq, Properties)]
 pub struct SystemData {
     pub on_action: Callback<StateMessage>,
+    pub host: HostStatus,
+    pub power: PowerStatus,
+    pub watchdog: WatchdogStats,
+    pub workers: Vec<WorkerStatus>,
+    pub config_text: Option<String>,
+    pub commands: CommandStatuses,
 }
 
 pub enum Msg{
     ServerAction(StateMessage),
     ShowConfirmation,
+    UpdateEditedConfig(String),
+    SaveConfig,
 }
 
 impl Component for System {
@@ -24,13 +36,26 @@ impl Component for System {
     fn create(_ctx: &Context<Self>) -> Self {
         Self {
             confirmation_enabled: false,
+            edited_config: None,
         }
     }
 
+    fn changed(&mut self, ctx: &Context<Self>, old_props: &Self::Properties) -> bool {
+        // Config received from the server replaces local edits
+        if ctx.props().config_text != old_props.config_text {
+            self.edited_config = ctx.props().config_text.clone();
+        }
+        true
+    }
+
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             Msg::ServerAction(action) => ctx.props().on_action.emit(action),
             Msg::ShowConfirmation => self.confirmation_enabled = !self.confirmation_enabled,
+            Msg::UpdateEditedConfig(text) => self.edited_config = Some(text),
+            Msg::SaveConfig => if let Some(text) = self.edited_config.clone() {
+                ctx.props().on_action.emit(StateMessage::Config(ConfigMessage::Save(text)))
+            },
         }
         true
     }
@@ -42,6 +67,7 @@ impl Component for System {
             move |_| Msg::ServerAction(action.clone())
         );
 
+        let commands = &ctx.props().commands;
         let show_confirm = || ctx.link().callback(move |_| Msg::ShowConfirmation);
 
         let confirmation_button = match self.confirmation_enabled {
@@ -51,12 +77,158 @@ impl Component for System {
             }
         };
 
+        let config_editor = match self.edited_config.as_ref() {
+            None => html!{},
+            Some(text) => html!{
+                <div>
+                    <p>{"CCDI_* variables and --set options are applied on top of this file"}</p>
+                    <TextArea
+                        value={text.clone()}
+                        rows={30}
+                        on_change={ctx.link().callback(Msg::UpdateEditedConfig)}
+                    />
+                    <button onclick={ctx.link().callback(|_| Msg::SaveConfig)}>
+                        {"Save and Apply"}
+                    </button>
+                    <CommandStatusIcon commands={commands.clone()} command="Config.Save"/>
+                </div>
+            }
+        };
+
         html!{
             <div>
+                <p>{"Host"}</p>
+                {render_host(&ctx.props().host)}
+                {render_power(&ctx.props().power)}
+                <p>{"Exposure Watchdog"}</p>
+                {render_watchdog(&ctx.props().watchdog)}
+                <p>{"Workers"}</p>
+                {render_workers(&ctx.props().workers)}
                 <p>{"Commands"}</p>
                 <button onclick={show_confirm()}>{"Power Off ?"}</button>
                 {confirmation_button}
+                <CommandStatusIcon commands={commands.clone()} command="PowerOff"/>
+                <p>{"Configuration"}</p>
+                <button onclick={server_action(Config(ConfigMessage::Load))}>{"Load Config"}</button>
+                {config_editor}
             </div>
         }
     }
 }
+
+// =========================================== PRIVATE =============================================
+
+fn render_host(host: &HostStatus) -> Html {
+    let unknown = || String::from("?");
+
+    let throttling = match (host.throttle_flags, host.throttling(), host.under_voltage()) {
+        (None, _, _) => unknown(),
+        (Some(_), _, true) => String::from("Under voltage!"),
+        (Some(_), true, false) => String::from("Throttling!"),
+        (Some(_), false, false) if host.throttling_occurred() => String::from("Occurred since boot"),
+        (Some(_), false, false) => String::from("No"),
+    };
+
+    let memory = match (host.memory_available_megabytes, host.memory_total_megabytes) {
+        (Some(available), Some(total)) => format!("{} of {} MB free", available, total),
+        _ => unknown(),
+    };
+
+    let uptime = host.uptime.map(|uptime| {
+        let seconds = uptime.as_secs();
+        format!("{}d {:02}:{:02}", seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60)
+    });
+
+    html! {
+        <div class="div-table">
+            {render_row(
+                "CPU Temperature",
+                &host.cpu_temperature.map(|t| format!("{:.1} C", t)).unwrap_or_else(unknown)
+            )}
+            {render_row("Throttling", &throttling)}
+            {render_row(
+                "Load",
+                &host.load_average
+                    .map(|l| format!("{:.2} {:.2} {:.2}", l[0], l[1], l[2]))
+                    .unwrap_or_else(unknown)
+            )}
+            {render_row("Memory", &memory)}
+            {render_row("Uptime", &uptime.unwrap_or_else(unknown))}
+        </div>
+    }
+}
+
+fn render_power(power: &PowerStatus) -> Html {
+    if power.state == PowerState::Unknown && power.voltage.is_none() {
+        return html! {};
+    }
+
+    let voltage = match power.voltage {
+        None => String::from("?"),
+        Some(voltage) => format!("{:.2} V", voltage),
+    };
+
+    let trend = match power.trend {
+        None => String::from("?"),
+        Some(trend) if trend < 0.0 => format!("↓ {:.2} V/h", -trend),
+        Some(trend) => format!("↑ {:.2} V/h", trend),
+    };
+
+    let state = match power.state {
+        PowerState::Unknown => "?",
+        PowerState::Normal => "OK",
+        PowerState::Low => "Low!",
+        PowerState::Critical => "Critical - shutting down!",
+    };
+
+    html! {
+        <div class="div-table">
+            {render_row("Supply Voltage", &voltage)}
+            {render_row("Voltage Trend", &trend)}
+            {render_row("Supply State", state)}
+        </div>
+    }
+}
+
+fn render_watchdog(watchdog: &WatchdogStats) -> Html {
+    html! {
+        <div class="div-table">
+            {render_row("Timeouts", &watchdog.timeouts.to_string())}
+            {render_row("Retries", &watchdog.retries.to_string())}
+            {render_row("Reconnects", &watchdog.reconnects.to_string())}
+        </div>
+    }
+}
+
+fn render_workers(workers: &[WorkerStatus]) -> Html {
+    let rows = workers.iter().map(|worker| {
+        let state = match worker.state {
+            WorkerState::Running => "Running",
+            WorkerState::Restarting => "Restarting!",
+            WorkerState::Stopped => "Stopped",
+        };
+
+        let value = match (worker.restarts, worker.last_error.as_ref()) {
+            (0, _) => state.to_string(),
+            (restarts, None) => format!("{}, {} restarts", state, restarts),
+            (restarts, Some(error)) => format!("{}, {} restarts, last error: {}", state, restarts, error),
+        };
+
+        render_row(&worker.name, &value)
+    });
+
+    html! {
+        <div class="div-table">
+            { for rows }
+        </div>
+    }
+}
+
+fn render_row(name: &str, value: &str) -> Html {
+    html! {
+        <div class="div-table-row">
+            <div class="div-table-col">{name}</div>
+            <div class="div-table-col">{value}</div>
+        </div>
+    }
+}
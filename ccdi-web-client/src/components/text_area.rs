@@ -0,0 +1,36 @@
+use wasm_bindgen::{JsCast, UnwrapThrowExt};
+use web_sys::{Event, HtmlTextAreaElement, InputEvent};
+use yew::prelude::*;
+
+// ============================================ PUBLIC =============================================
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub value: String,
+    pub rows: usize,
+    pub on_change: Callback<String>,
+}
+
+/// Controlled multi-line Text Input Component
+#[function_component(TextArea)]
+pub fn text_area(props: &Props) -> Html {
+    let Props { value, rows, on_change } = props.clone();
+
+    let oninput = Callback::from(move |input_event: InputEvent| {
+        let value = get_value_from_input_event(input_event);
+        on_change.emit(value);
+    });
+
+    html! {
+        <textarea class="w100p" rows={rows.to_string()} {value} {oninput} />
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+fn get_value_from_input_event(e: InputEvent) -> String {
+    let event: Event = e.dyn_into().unwrap_throw();
+    let event_target = event.target().unwrap_throw();
+    let target: HtmlTextAreaElement = event_target.dyn_into().unwrap_throw();
+    target.value()
+}
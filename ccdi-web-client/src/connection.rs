@@ -3,7 +3,7 @@ use std::sync::Arc;
 use anyhow::Error;
 use yew::{html, Component, Context, Html, Callback, Properties};
 use yew_websocket::websocket::{WebSocketService, WebSocketStatus, WebSocketTask};
-use ccdi_common::{ClientMessage, StateMessage, ConnectionState, to_string};
+use ccdi_common::{ClientCommand, ClientMessage, StateMessage, ConnectionState, to_string};
 use gloo::console;
 use gloo::timers::callback::Interval;
 
@@ -117,7 +117,13 @@ impl Component for ConnectionService {
                 true
             }
             Msg::Established => {
-                ctx.link().send_message(Msg::SendData(StateMessage::ClientConnected));
+                // Not tracked, the view sent in reply acknowledges the connection
+                let connected = ClientCommand {
+                    id: 0,
+                    connection: 0,
+                    message: Box::new(StateMessage::ClientConnected),
+                };
+                ctx.link().send_message(Msg::SendData(StateMessage::Command(connected)));
                 ctx.props().on_state_change.emit(ConnectionState::Established);
                 true
             }
@@ -1,3 +1,4 @@
+mod commands;
 mod components;
 mod connection;
 mod selectors;
@@ -8,6 +9,7 @@ use web_sys::console::log_1;
 
 use ccdi_common::*;
 use ccdi_imager_interface::ExposureArea;
+use commands::CommandTracker;
 use connection::ConnectionService;
 use gloo::console;
 
@@ -21,6 +23,9 @@ use components::status_bar::StatusBar;
 use selectors::composition::CompositionDetail;
 use selectors::picture::Picture;
 
+use crate::components::command_status::CommandStatusIcon;
+use crate::components::events::Events;
+use crate::components::presets::Presets;
 use crate::components::system::System;
 use crate::selectors::autoexp::AutoExpConfig;
 use crate::selectors::bool::BoolSelector;
@@ -59,6 +64,9 @@ pub struct Main {
     pub w: Arc<Mutex<usize>>,
     pub h: Arc<Mutex<usize>>,
     pub time: Arc<Mutex<String>>,
+    pub config_text: Option<String>,
+    pub events: Vec<Event>,
+    pub commands: CommandTracker,
 }
 
 impl Main {
@@ -68,11 +76,24 @@ impl Main {
             ClientMessage::Reconnect => {} // handled elsewhere
             ClientMessage::View(view) => self.view_state = *view,
             ClientMessage::PngImage(image) => self.image = Some(image),
+            ClientMessage::ConfigText(text) => self.config_text = Some(text),
+            ClientMessage::Events(events) => self.receive_events(events),
+            ClientMessage::CommandResult(result) => self.commands.receive(result),
         }
 
         true
     }
 
+    /// Append events not received yet, all events are sent again after reconnect
+    fn receive_events(&mut self, events: Vec<Event>) {
+        let last_id = self.events.last().map(|event| event.id).unwrap_or(0);
+        self.events.extend(events.into_iter().filter(|event| event.id > last_id));
+
+        if self.events.len() > MAX_EVENTS {
+            self.events.drain(..self.events.len() - MAX_EVENTS);
+        }
+    }
+
     fn render_tool(&self, ctx: &Context<Self>) -> Html {
         match self.selected_menu {
             MenuItem::Composition => self.render_composition(ctx),
@@ -81,6 +102,8 @@ impl Main {
             MenuItem::Info => html! {
                 <CameraDetail data={self.view_state.camera_properties.clone()} />
             },
+            MenuItem::Presets => self.render_presets(ctx),
+            MenuItem::Events => html! { <Events events={self.events.clone()} /> },
             MenuItem::System => self.render_system(ctx),
         }
     }
@@ -205,6 +228,8 @@ impl Main {
             *hp.lock().unwrap() = roi.height;
         };
 
+        let commands = self.commands.statuses();
+
         let exposure_str = {
             if exposure < 0.001 {
                 format!("{:5.2} us", (exposure * 1000000.0))
@@ -280,6 +305,7 @@ impl Main {
 
                     <button onclick={roi_changed}>{"Update ROI"}</button>
                     <button onclick={refresh_roi}>{"Refresh ROI"}</button>
+                    <CommandStatusIcon commands={commands.clone()} command="ImageParam.SetRoi"/>
                     </div>
                     <p>
                     <div class="div-table-row w100p">
@@ -309,6 +335,7 @@ impl Main {
                     </div> // div-table-row w100p
                     </p>
                 <button onclick={time_changed_btn}>{"Update Exposure"}</button>
+                <CommandStatusIcon commands={commands.clone()} command="CameraParam.SetTime"/>
                 <button onclick={client_test_message}>{"Send Test Message"}</button>
                 <CompositionDetail
                     on_action={action.clone()}
@@ -333,6 +360,8 @@ impl Main {
             .link()
             .callback(|temp: f64| Msg::CParamUpdate(CameraParamMessage::SetHeatingPwm(temp)));
 
+        let commands = self.commands.statuses();
+
         html! {
             <div>
                 <p>{"Chip temperature: "}
@@ -352,12 +381,14 @@ impl Main {
                     selected_value={self.view_state.camera_params.temperature}
                     value_changed={cooling_changed}
                 />
+                <CommandStatusIcon commands={commands.clone()} command="CameraParam.SetTemp"/>
                 <FloatSelector
                     name="Telescope Heating PWM"
                     config={self.view_state.config.heating.clone()}
                     selected_value={self.view_state.camera_params.heating_pwm}
                     value_changed={heating_changed}
                 />
+                <CommandStatusIcon commands={commands.clone()} command="CameraParam.SetHeatingPwm"/>
             </div>
         }
     }
@@ -374,18 +405,41 @@ impl Main {
                     storage_details={self.view_state.storage_detail.clone()}
                     image_params={self.view_state.image_params.clone()}
                     camera_params={self.view_state.camera_params.clone()}
+                    commands={self.commands.statuses().clone()}
                 />
             </div>
         }
     }
 
+    fn render_presets(&self, ctx: &Context<Self>) -> Html {
+        let action = ctx
+            .link()
+            .callback(|action: StateMessage| Msg::SendMessage(action));
+
+        html! {
+            <Presets
+                on_action={action}
+                presets={self.view_state.presets.clone()}
+                commands={self.commands.statuses().clone()}
+            />
+        }
+    }
+
     fn render_system(&self, ctx: &Context<Self>) -> Html {
         let action = ctx
             .link()
             .callback(|action: StateMessage| Msg::SendMessage(action));
 
         html! {
-            <System on_action={action.clone()}/>
+            <System
+                on_action={action.clone()}
+                host={self.view_state.host.clone()}
+                power={self.view_state.power.clone()}
+                watchdog={self.view_state.watchdog.clone()}
+                workers={self.view_state.workers.clone()}
+                config_text={self.config_text.clone()}
+                commands={self.commands.statuses().clone()}
+            />
         }
     }
 
@@ -423,6 +477,9 @@ impl Component for Main {
             w: W.clone(),
             h: H.clone(),
             time: T.clone(),
+            config_text: None,
+            events: Vec::new(),
+            commands: Default::default(),
         }
     }
 
@@ -432,15 +489,23 @@ impl Component for Main {
             Msg::SendMessage(message) => {
                 match self.connection_context.as_ref() {
                     None => console::warn!("No connection service registered."),
-                    Some(context) => context.send_message(connection::Msg::SendData(message)),
+                    Some(context) => context.send_message(connection::Msg::SendData(
+                        self.commands.command(message),
+                    )),
                 }
-                false
+                true
             }
             Msg::RegisterConnectionService(context) => {
                 self.connection_context = Some(context);
                 false
             }
             Msg::ConnectionState(state) => {
+                // Server may have restarted, its event ids start from the beginning
+                match state {
+                    ConnectionState::Established => self.events.clear(),
+                    _ => self.commands.connection_lost(),
+                }
+
                 self.connection_state = state;
                 true
             }
@@ -505,6 +570,11 @@ impl Component for Main {
     }
 }
 
+// =========================================== PRIVATE =============================================
+
+/// Number of events kept by the client
+const MAX_EVENTS: usize = 500;
+
 fn main() {
     yew::Renderer::<Main>::new().render();
 }
@@ -1,5 +1,7 @@
 use std::time::Duration;
 
+use crate::commands::CommandStatuses;
+use crate::components::command_status::CommandStatusIcon;
 use crate::components::shooting_details::ShootingDetails;
 use crate::components::text_input::TextInput;
 use shooting::{composition::CompositionDetail, intin::IntInput};
@@ -11,6 +13,9 @@ use super::*;
 
 pub struct ShootingDetail {
     pub edited_name: String,
+    /// Video limits, zero means no limit
+    pub recording_frames: i64,
+    pub recording_secs: i64,
 }
 
 #[derive(Clone, PartialEq, Properties)]
@@ -19,12 +24,16 @@ pub struct ShootingDetailData {
     pub storage_details: StorageDetail,
     pub image_params: ImageParams,
     pub camera_params: CameraParams,
+    pub commands: CommandStatuses,
 }
 
 pub enum Msg {
     UpdateEditedName(String),
     UpdateEditedCadence(String),
     SetDirectory,
+    UpdateRecordingFrames(String),
+    UpdateRecordingSecs(String),
+    StartRecording,
     ServerAction(StateMessage),
 }
 
@@ -35,6 +44,8 @@ impl Component for ShootingDetail {
     fn create(_ctx: &Context<Self>) -> Self {
         Self {
             edited_name: String::new(),
+            recording_frames: 0,
+            recording_secs: 0,
         }
     }
 
@@ -51,6 +62,25 @@ impl Component for ShootingDetail {
             Msg::SetDirectory => ctx.props().on_action.emit(StateMessage::StorageMessage(
                 StorageMessage::SetDirectory(self.edited_name.clone()),
             )),
+            Msg::UpdateRecordingFrames(frames) => {
+                self.recording_frames = frames.parse::<i64>().unwrap_or(0).max(0);
+                return true;
+            }
+            Msg::UpdateRecordingSecs(secs) => {
+                self.recording_secs = secs.parse::<i64>().unwrap_or(0).max(0);
+                return true;
+            }
+            Msg::StartRecording => {
+                let limits = RecordingLimits {
+                    frames: Some(self.recording_frames as usize).filter(|frames| *frames > 0),
+                    duration: Some(Duration::from_secs(self.recording_secs as u64))
+                        .filter(|duration| !duration.is_zero()),
+                };
+
+                ctx.props().on_action.emit(StateMessage::StorageMessage(
+                    StorageMessage::StartRecording(limits),
+                ))
+            }
             Msg::ServerAction(action) => ctx.props().on_action.emit(action),
         }
         false
@@ -61,11 +91,15 @@ impl Component for ShootingDetail {
 
         let on_change_name = ctx.link().callback(Msg::UpdateEditedName);
         let on_change_cadence = ctx.link().callback(Msg::UpdateEditedCadence);
+        let on_change_frames = ctx.link().callback(Msg::UpdateRecordingFrames);
+        let on_change_secs = ctx.link().callback(Msg::UpdateRecordingSecs);
         let set_dir_click = || ctx.link().callback(move |_| Msg::SetDirectory);
         let details = &ctx.props().storage_details;
+        let recording = details.recording.as_ref().is_some_and(|recording| recording.active);
         let image_params = &ctx.props().image_params;
         let camera_params = &ctx.props().camera_params;
         let enabled = ctx.props().storage_details.storage_enabled;
+        let commands = &ctx.props().commands;
 
         let action = ctx
             .link()
@@ -76,17 +110,92 @@ impl Component for ShootingDetail {
                 .callback(move |_| Msg::ServerAction(action.clone()))
         };
 
+        let format_button = |format: OutputFormat| {
+            let selected = details.formats.contains(&format);
+            let formats = toggle_format(&details.formats, format);
+
+            html! {
+                <button
+                    class={classes!(if selected { Some("button-selected") } else { None })}
+                    onclick={server_action(StateMessage::StorageMessage(SetFormats(formats)))}
+                    >{format_label(format)}
+                </button>
+            }
+        };
+
+        let compression_button = |compression: FitsCompression| {
+            let selected = details.compression == compression;
+            let message = StateMessage::StorageMessage(SetCompression(compression));
+
+            html! {
+                <button
+                    class={classes!(if selected { Some("button-selected") } else { None })}
+                    onclick={server_action(message)}
+                    >{compression_label(compression)}
+                </button>
+            }
+        };
+
+        let target_button = |target: StorageTarget, label: String| {
+            let selected = details.target == target;
+            let message = StateMessage::StorageMessage(SetTarget(target));
+
+            html! {
+                <button
+                    class={classes!(if selected { Some("button-selected") } else { None })}
+                    onclick={server_action(message)}
+                    >{label}
+                </button>
+            }
+        };
+
+        let drive_buttons = details.drives.iter().map(|drive| {
+            let target = StorageTarget::Removable(drive.mount_point.clone());
+            target_button(target, format!("{} ({})", drive.mount_point, drive_free(drive)))
+        });
+
+        // Selected drive stays visible while it is missing
+        let missing_drive = match &details.target {
+            StorageTarget::Removable(mount_point)
+                if !details.drives.iter().any(|drive| drive.mount_point == *mount_point) =>
+            {
+                Some(target_button(details.target.clone(), format!("{} (missing)", mount_point)))
+            }
+            _ => None,
+        };
+
+        let fallback_notice = match details.fallback {
+            true => html! { <p>{"Selected drive missing, saving to internal storage"}</p> },
+            false => html! {},
+        };
+
         html! {
             <div>
+                <div>
+                    {"Target: "}
+                    {target_button(StorageTarget::Internal, String::from("Internal"))}
+                    {for drive_buttons}
+                    {for missing_drive}
+                    <CommandStatusIcon
+                        commands={commands.clone()}
+                        command="StorageMessage.SetTarget"
+                    />
+                    {fallback_notice}
+                </div>
                 <div>
                     <p>{format_capacity(&details.state)}</p>
                     <p>{format!("Counter: {}", &details.counter)}</p>
                     <p>{format!("Directory: {}", &details.storage_name)}</p>
+                    <p>{format_queue(&details.queue)}</p>
                 </div>
                 <div>
                     <p>{"Change Directory"}</p>
                     <TextInput on_change={on_change_name} value={self.edited_name.clone()}/>
                     <button onclick={set_dir_click()}>{"Set dir"}</button>
+                    <CommandStatusIcon
+                        commands={commands.clone()}
+                        command="StorageMessage.SetDirectory"
+                    />
                 </div>
                 <div>
                     <button
@@ -99,6 +208,32 @@ impl Component for ShootingDetail {
                         onclick={server_action(StateMessage::StorageMessage(EnableStore))}
                         >{"Save ON"}
                     </button>
+                    <CommandStatusIcon
+                        commands={commands.clone()}
+                        command="StorageMessage.DisableStore"
+                    />
+                    <CommandStatusIcon
+                        commands={commands.clone()}
+                        command="StorageMessage.EnableStore"
+                    />
+                </div>
+                <div>
+                    {"Formats: "}
+                    {for OutputFormat::ALL.into_iter().map(format_button)}
+                    <CommandStatusIcon
+                        commands={commands.clone()}
+                        command="StorageMessage.SetFormats"
+                    />
+                </div>
+                <div>
+                    {"FITS compression: "}
+                    {compression_button(FitsCompression::None)}
+                    {compression_button(FitsCompression::Rice)}
+                    {compression_button(FitsCompression::Gzip)}
+                    <CommandStatusIcon
+                        commands={commands.clone()}
+                        command="StorageMessage.SetCompression"
+                    />
                 </div>
                 <div>
                 <p>
@@ -109,8 +244,42 @@ impl Component for ShootingDetail {
                     on_change={on_change_cadence}
                     />
                     {" s"}
+                    <CommandStatusIcon
+                        commands={commands.clone()}
+                        command="StorageMessage.UpdateCadence"
+                    />
                 </p>
                 </div>
+                <div>
+                    <p>
+                        {"Video: "}
+                        <IntInput
+                            value={self.recording_frames}
+                            width = 6
+                            on_change={on_change_frames}
+                        />
+                        {" frames "}
+                        <IntInput value={self.recording_secs} width = 6 on_change={on_change_secs}/>
+                        {" s (0 = no limit)"}
+                    </p>
+                    <button
+                        class={classes!(if recording { Some("button-selected") } else { None })}
+                        onclick={ctx.link().callback(|_| Msg::StartRecording)}
+                        >{"Record SER"}
+                    </button>
+                    <button onclick={server_action(StateMessage::StorageMessage(StopRecording))}>
+                        {"Stop"}
+                    </button>
+                    <CommandStatusIcon
+                        commands={commands.clone()}
+                        command="StorageMessage.StartRecording"
+                    />
+                    <CommandStatusIcon
+                        commands={commands.clone()}
+                        command="StorageMessage.StopRecording"
+                    />
+                    <p>{format_recording(details.recording.as_deref())}</p>
+                </div>
                 <CompositionDetail 
                     on_action={action.clone()}
                     image_params={image_params.clone()}
@@ -132,5 +301,71 @@ fn format_capacity(capacity: &StorageState) -> String {
             "Storage: {:1.1}G of {:1.1}G free",
             details.free_gigabytes, details.total_gigabytes
         ),
+        StorageState::Full(details) => format!(
+            "Storage full, saving paused: {:1.1}G of {:1.1}G free",
+            details.free_gigabytes, details.total_gigabytes
+        ),
+    }
+}
+
+fn drive_free(drive: &RemovableDrive) -> String {
+    match &drive.capacity {
+        StorageState::Available(capacity) | StorageState::Full(capacity) => {
+            format!("{:.1}G free", capacity.free_gigabytes)
+        }
+        StorageState::Error(_) => String::from("not readable"),
+        StorageState::Unknown => String::from("?"),
+    }
+}
+
+fn format_queue(queue: &QueueStatus) -> String {
+    format!(
+        "Write queue: {}/{} frames, {:.1} MB/s, {} dropped",
+        queue.depth, queue.capacity, queue.throughput, queue.dropped
+    )
+}
+
+fn format_recording(recording: Option<&RecordingStatus>) -> String {
+    match recording {
+        None => String::new(),
+        Some(recording) if recording.active => format!(
+            "Recording: {} frames, {:.1} fps, {} dropped, {:.0} s",
+            recording.frames,
+            recording.fps,
+            recording.dropped,
+            recording.elapsed.as_secs_f64()
+        ),
+        Some(recording) => format!(
+            "Last video: {} frames, {} dropped{} {}",
+            recording.frames,
+            recording.dropped,
+            recording.error.as_ref().map(|error| format!(", {}", error)).unwrap_or_default(),
+            recording.file
+        ),
+    }
+}
+
+fn toggle_format(formats: &[OutputFormat], format: OutputFormat) -> Vec<OutputFormat> {
+    match formats.contains(&format) {
+        true => formats.iter().copied().filter(|item| *item != format).collect(),
+        false => formats.iter().copied().chain([format]).collect(),
+    }
+}
+
+fn format_label(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Fits => "FITS",
+        OutputFormat::Tiff => "TIFF",
+        OutputFormat::Png => "PNG",
+        OutputFormat::Xisf => "XISF",
+        OutputFormat::Sidecar => "JSON",
+    }
+}
+
+fn compression_label(compression: FitsCompression) -> &'static str {
+    match compression {
+        FitsCompression::None => "None",
+        FitsCompression::Rice => "Rice",
+        FitsCompression::Gzip => "GZIP",
     }
 }
@@ -20,6 +20,18 @@ pub struct ServerConfig {
     /// log file
     #[argh(option)]
     pub log: Option<String>,
+
+    /// config file path, state and presets are stored next to it
+    #[argh(option)]
+    pub config: Option<String>,
+
+    /// override config key, e.g. --set exp.pixel_tgt=0.5, takes precedence over CCDI_* variables
+    #[argh(option)]
+    pub set: Vec<String>,
+
+    /// print the effective config after applying overrides and exit
+    #[argh(switch)]
+    pub print_config: bool,
 }
 
 fn default_addr() -> u16 {
@@ -1,25 +1,36 @@
 mod bridge;
 mod config;
 mod logger;
+mod signals;
 mod static_files;
 mod websocket;
 
+use std::path::PathBuf;
+use std::thread::JoinHandle;
+
 use ccdi_common::ClientMessage;
 use ccdi_common::IoMessage;
 use ccdi_common::ProcessMessage;
 use ccdi_common::StateMessage;
-use ccdi_common::StorageMessage;
+use ccdi_logic::config_to_string;
 use ccdi_logic::create_default_config_file;
+use ccdi_logic::env_overrides;
+use ccdi_logic::init_config_source;
 use ccdi_logic::load_config_file;
+use ccdi_logic::parse_override;
 use ccdi_logic::start_io_thread;
 use ccdi_logic::start_logic_thread;
 use ccdi_logic::start_process_thread;
 use ccdi_logic::start_storage_thread;
+use ccdi_logic::storage_channel;
 use ccdi_logic::LogicParams;
+use ccdi_logic::SharedConfig;
+use ccdi_logic::WorkerMonitor;
 use config::ServerConfig;
 use log::debug;
 use log::{error, info};
 use logger::init_logger;
+use signals::start_signal_handler;
 use static_files::static_files_rules;
 use tokio::sync::mpsc;
 
@@ -34,11 +45,17 @@ use websocket::start_single_async_to_multiple_clients_sender;
 
 fn main() {
     let serverconf: ServerConfig = argh::from_env();
-    init_logger(serverconf.debug, serverconf.log.as_ref());
 
-    let params = LogicParams {
-        demo_mode: serverconf.camera,
-    };
+    if let Err(error) = init_config(&serverconf) {
+        eprintln!("Invalid config options: {}", error);
+        std::process::exit(2);
+    }
+
+    if serverconf.print_config {
+        print_config();
+    }
+
+    init_logger(serverconf.debug, serverconf.log.as_ref());
 
     match create_default_config_file() {
         Ok(path) => info!(
@@ -57,18 +74,28 @@ fn main() {
     };
 
     debug!("Current config: {:?}", config);
+    let config = SharedConfig::new(config);
 
     let (server_tx, server_rx) = std::sync::mpsc::channel::<StateMessage>();
     let (clients_tx, clients_rx) = std::sync::mpsc::channel::<ClientMessage>();
     let (process_tx, process_rx) = std::sync::mpsc::channel::<ProcessMessage>();
-    let (storage_tx, storage_rx) = std::sync::mpsc::channel::<StorageMessage>();
+    let (storage_tx, storage_rx) = storage_channel(&config.get().storage_queue);
     let (io_tx, io_rx) = std::sync::mpsc::channel::<IoMessage>();
 
-    let _storage_thread = start_storage_thread(config.clone(), storage_rx, server_tx.clone());
-    let _process_thread = start_process_thread(process_rx, clients_tx.clone(), server_tx.clone());
-    let _io_thread = start_io_thread(config.clone(), io_rx, server_tx.clone());
+    let workers = WorkerMonitor::new();
+
+    let params = LogicParams {
+        demo_mode: serverconf.camera,
+        workers: workers.clone(),
+    };
+
+    let storage_thread =
+        start_storage_thread(config.clone(), workers.clone(), storage_rx, server_tx.clone());
+    let process_thread =
+        start_process_thread(workers.clone(), process_rx, clients_tx.clone(), server_tx.clone());
+    let io_thread = start_io_thread(config.clone(), workers, io_rx, server_tx.clone());
 
-    let _server_thread = start_logic_thread(
+    let server_thread = start_logic_thread(
         params,
         config.clone(),
         server_rx,
@@ -82,13 +109,20 @@ fn main() {
         .enable_all()
         .build()
         .expect("Tokio failed to start")
-        .block_on(tokio_main(server_tx, clients_rx, serverconf.addr))
+        .block_on(tokio_main(server_tx, clients_rx, serverconf.addr, server_thread));
+
+    // Logic thread finished, the remaining threads exit once their channels are drained
+    join_thread("storage", storage_thread);
+    join_thread("process", process_thread);
+    join_thread("io", io_thread);
+    info!("Service stopped");
 }
 
 async fn tokio_main(
     sync_server_tx: std::sync::mpsc::Sender<StateMessage>,
     sync_clients_rx: std::sync::mpsc::Receiver<ClientMessage>,
     addr: u16,
+    server_thread: Result<JoinHandle<()>, String>,
 ) {
     let (ws_from_client_tx, ws_from_client_rx) = mpsc::unbounded_channel::<StateMessage>();
     let (async_clients_tx, async_clients_rx) = mpsc::unbounded_channel::<ClientMessage>();
@@ -96,6 +130,7 @@ async fn tokio_main(
 
     let clients = create_clients(ws_from_client_tx);
 
+    start_signal_handler(sync_server_tx.clone());
     start_tokio_to_std_channel_bridge(ws_from_client_rx, sync_server_tx);
     start_single_async_to_multiple_clients_sender(clients.clone(), async_clients_rx);
     let _thread = start_std_to_tokio_channel_bridge(sync_clients_rx, async_clients_tx);
@@ -104,5 +139,48 @@ async fn tokio_main(
 
     let routes = warp::get().and(websocket_service.or(static_files_rules()));
 
-    warp::serve(routes).run(([0, 0, 0, 0], addr)).await;
+    let server_finished = tokio::task::spawn_blocking(move || join_thread("logic", server_thread));
+
+    // Serve clients until the logic thread finishes the shutdown sequence
+    tokio::select! {
+        _ = warp::serve(routes).run(([0, 0, 0, 0], addr)) => {},
+        _ = server_finished => {},
+    }
+}
+
+// =========================================== PRIVATE =============================================
+
+/// Config layers: defaults < config file < CCDI_* environment variables < --set options
+fn init_config(serverconf: &ServerConfig) -> Result<(), String> {
+    let mut overrides = env_overrides();
+
+    for text in serverconf.set.iter() {
+        overrides.push(parse_override(text)?);
+    }
+
+    init_config_source(serverconf.config.as_ref().map(PathBuf::from), overrides)
+}
+
+/// Print effective config to stdout and exit, logging is not initialized to keep output clean
+fn print_config() -> ! {
+    match load_config_file().and_then(|config| config_to_string(&config)) {
+        Ok(text) => {
+            print!("{}", text);
+            std::process::exit(0)
+        }
+        Err(error) => {
+            eprintln!("Config file could not be loaded: {}", error);
+            std::process::exit(1)
+        }
+    }
+}
+
+fn join_thread(name: &str, thread: Result<JoinHandle<()>, String>) {
+    match thread {
+        Err(error) => error!("Thread '{}' was not started: {}", name, error),
+        Ok(handle) => match handle.join() {
+            Ok(_) => debug!("Thread '{}' finished", name),
+            Err(_) => error!("Thread '{}' panicked", name),
+        },
+    }
 }
@@ -0,0 +1,48 @@
+use ccdi_common::{log_err, StateMessage};
+use log::{error, info, warn};
+use tokio::signal::unix::{signal, SignalKind};
+
+// ============================================ PUBLIC =============================================
+
+/// Request orderly shutdown on SIGTERM / SIGINT, second signal exits immediately
+pub fn start_signal_handler(sync_server_tx: std::sync::mpsc::Sender<StateMessage>) {
+    tokio::spawn(async move {
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(error) => {
+                error!("Could not register SIGTERM handler: {}", error);
+                return;
+            }
+        };
+
+        let mut interrupt = match signal(SignalKind::interrupt()) {
+            Ok(interrupt) => interrupt,
+            Err(error) => {
+                error!("Could not register SIGINT handler: {}", error);
+                return;
+            }
+        };
+
+        let mut requested = false;
+
+        loop {
+            tokio::select! {
+                _ = terminate.recv() => {},
+                _ = interrupt.recv() => {},
+            }
+
+            if requested {
+                warn!("Second termination signal received, exiting immediately");
+                std::process::exit(1);
+            }
+
+            info!("Termination signal received, shutting down");
+            requested = true;
+
+            log_err(
+                "Send shutdown request to server",
+                sync_server_tx.send(StateMessage::Shutdown),
+            );
+        }
+    });
+}
@@ -33,6 +33,10 @@ pub fn start_single_async_to_multiple_clients_sender(
             if let Some(message) = async_clients_rx.recv().await {
                 if let Ok(payload) = serialize(&message) {
                     for (index, transmitter) in clients.read().await.transmitters.iter() {
+                        if !is_recipient(&message, *index) {
+                            continue;
+                        }
+
                         if transmitter.capacity() < MIN_CAPACITY {
                             log_err(
                                 "Send reconnect message",
@@ -80,6 +84,14 @@ fn serialize(message: &ClientMessage) -> Result<Message, String> {
     }
 }
 
+/// Command results are sent only to the connection which sent the command
+fn is_recipient(message: &ClientMessage, connection: usize) -> bool {
+    match message {
+        ClientMessage::CommandResult(result) => result.connection == connection,
+        _ => true,
+    }
+}
+
 const CAPACITY: usize = 20;
 const MIN_CAPACITY: usize = 5;
 
@@ -111,7 +123,7 @@ async fn handle_client_connection(websocket: WebSocket, clients: Clients) {
         match result {
             Ok(message) => log_err(
                 "Processing server message from client",
-                process_message(message, &server_tx).await,
+                process_message(message, id, &server_tx).await,
             ),
             Err(e) => {
                 eprintln!("error receiving ws message: {}", e);
@@ -127,11 +139,22 @@ async fn handle_client_connection(websocket: WebSocket, clients: Clients) {
 
 async fn process_message(
     message: Message,
+    connection: usize,
     server_tx: &UnboundedSender<StateMessage>,
 ) -> Result<(), String> {
-    server_tx
-        .send(convert_state_message(message)?)
-        .map_err(to_string)
+    let message = client_command(convert_state_message(message)?, connection)?;
+    server_tx.send(message).map_err(to_string)
+}
+
+/// Clients send only commands, other messages would skip the validation of the logic thread
+fn client_command(message: StateMessage, connection: usize) -> Result<StateMessage, String> {
+    match message {
+        StateMessage::Command(mut command) => {
+            command.connection = connection;
+            Ok(StateMessage::Command(command))
+        }
+        other => Err(format!("Message from client {} is not a command: {:?}", connection, other)),
+    }
 }
 
 fn convert_state_message(message: Message) -> Result<StateMessage, String> {
@@ -174,3 +197,24 @@ impl ClientSharedState {
         Ok(())
     }
 }
+
+// ============================================= TEST ==============================================
+
+#[cfg(test)]
+mod tests {
+    use ccdi_common::{CameraParamMessage, ClientCommand};
+
+    use super::*;
+
+    #[test]
+    fn only_commands_are_accepted() {
+        let param = StateMessage::CameraParam(CameraParamMessage::SetTime(-1.0));
+        assert!(client_command(param.clone(), 3).is_err());
+
+        let message = Box::new(param.clone());
+        let command = |connection| {
+            StateMessage::Command(ClientCommand { id: 1, connection, message: message.clone() })
+        };
+        assert_eq!(client_command(command(0), 3), Ok(command(3)));
+    }
+}